@@ -1,63 +1,88 @@
 use super::cached_image::CachedImage;
 use crate::app::app::egui::ScrollArea;
 use crate::app::widgets::widgets_menu::{SettingType, SettingsMenu};
-use crate::simulation::renderer::Renderer;
-use crate::simulation::renderer_helpers::density_img_path;
+use crate::simulation::checkpoint::Checkpoint;
+use crate::simulation::configs::{LiveChanges, ViewportConfigs};
+use crate::simulation::renderer::{Renderer, RenderingThreads};
+use crate::simulation::renderer_helpers::{BrushStroke, RenderedFrame, RenderingListener};
+use crate::simulation::scenario::Scenario;
 use eframe::egui::global_dark_light_mode_switch;
 use eframe::{egui, epi};
-use image::imageops::FilterType::Triangle;
-use image::GenericImageView;
 use simplelog::*;
 use std::sync::mpsc;
 use std::sync::mpsc::Receiver;
-use std::thread::JoinHandle;
 
 /// Entry-point for the fluid simulation application
-#[cfg_attr(feature = "persistence", derive(serde::Deserialize, serde::Serialize))]
-#[cfg_attr(feature = "persistence", serde(default))]
 pub struct App {
     /// The "id" of the last showed image in the application of a given simulation
     /// in the context of specific simulation
-    #[cfg_attr(feature = "persistence", serde(skip))]
     current_frame: i64,
 
     /// The fluid driver that renders its state in a file
-    #[cfg_attr(feature = "persistence", serde(skip))]
     renderer: Renderer,
 
     /// The progress of the simulation
-    #[cfg_attr(feature = "persistence", serde(skip))]
     simulation_progress: f32,
 
     /// Between-threads receiver, participant in a channel open between the
     /// renderer and the application
-    #[cfg_attr(feature = "persistence", serde(skip))]
-    signal_receiver: Receiver<i64>,
+    signal_receiver: Receiver<RenderedFrame>,
+
+    /// All the frames of the last simulation rendered so far. They are kept in memory, so they
+    /// can be shown without reading them from the disk.
+    rendered_frames: Vec<image::RgbaImage>,
 
     /// Collection of all the widgets in the application
-    #[cfg_attr(feature = "persistence", serde(skip))]
     settings_menu: SettingsMenu,
 
     /// The last showed image is cached.
-    #[cfg_attr(feature = "persistence", serde(skip))]
     cached_image: Option<CachedImage>,
 
     /// The play button has been clicked, and now the simulation should be displayed frame by frame
-    #[cfg_attr(feature = "persistence", serde(skip))]
     is_play_button_on: bool,
 
     /// The simulation is currently in progress
-    #[cfg_attr(feature = "persistence", serde(skip))]
     is_simulation_in_process: bool,
 
+    /// The configurations have changed while the simulation is running, and it should be
+    /// restarted as soon as no mouse button is held, e.g. when a slider is released. See
+    /// [`LiveChanges::Restart`].
+    is_restart_pending: bool,
+
     /// Is a fluid simulated and ready to be showed
-    #[cfg_attr(feature = "persistence", serde(skip))]
     is_simulation_ready: bool,
 
     /// JoinHandle's for the rendering threads: the [`CurrentSimulation`] simulator, and the
     /// [`RenderingListener`] renderer
-    #[cfg_attr(feature = "persistence", serde(skip))]
-    rendering_joinhandlers: Vec<JoinHandle<()>>,
+    rendering_joinhandlers: RenderingThreads,
+
+    /// The path of the scenario file used by the "Save scenario" and "Open scenario" entries of
+    /// the File menu. Relative paths are resolved against the project's root.
+    scenario_path: String,
+
+    /// The path of the checkpoint file used by the "Start from checkpoint" entry of the File
+    /// menu. Relative paths are resolved against the project's root.
+    checkpoint_path: String,
+
+    /// The result of the last scenario operation or animation export, shown in the top panel
+    status: String,
+
+    /// Receives the result of the animation export running on its own thread, if there is one.
+    /// See [`Self::export_animation()`].
+    animation_export: Option<Receiver<std::io::Result<String>>>,
+
+    /// Dragging the mouse over the viewport paints density and velocity into the running
+    /// simulation
+    is_painting_enabled: bool,
+
+    /// The radius of the painting brush in cells
+    brush_radius: u32,
+
+    /// The density added to every cell under the brush while dragging
+    brush_density: f32,
+
+    /// Scales the velocity added under the brush, which follows the mouse's drag direction
+    brush_force: f32,
 }
 
 impl App {
@@ -70,156 +95,381 @@ impl App {
             renderer,
             simulation_progress: 1.0,
             signal_receiver,
+            rendered_frames: Vec::new(),
             settings_menu: SettingsMenu::default(),
             cached_image: None,
             is_play_button_on: false,
             is_simulation_in_process: false,
+            is_restart_pending: false,
             is_simulation_ready: false,
             rendering_joinhandlers: Vec::new(),
+            scenario_path: "scenario.toml".to_string(),
+            checkpoint_path: "rendered_images/checkpoint99.eqcp".to_string(),
+            status: String::new(),
+            animation_export: None,
+            is_painting_enabled: false,
+            brush_radius: 3,
+            brush_density: 0.5,
+            brush_force: 5.0,
         }
     }
 
-    /// Returns how zoomed is the simulation result image in the application.
-    fn get_zoom_factor(&self) -> Option<u8> {
+    /// Saves the configurations from all the widgets into the file at [`Self::scenario_path`].
+    fn save_scenario(&mut self) {
+        let path = RenderingListener::make_save_into_dir(&self.scenario_path);
+        self.status = match self.settings_menu.to_scenario().save(&path) {
+            Ok(()) => format!("Scenario saved into {}", path),
+            Err(error) => {
+                simplelog::error!("Couldn't save scenario into {}: {}", path, error);
+                format!("Couldn't save scenario: {}", error)
+            }
+        };
+    }
+
+    /// Opens the file at [`Self::scenario_path`] and applies it to all the widgets. The
+    /// configurations will be used in the next simulation.
+    fn open_scenario(&mut self) {
+        let path = RenderingListener::make_save_into_dir(&self.scenario_path);
+        self.status = match Scenario::open(&path) {
+            Ok(scenario) => {
+                self.settings_menu.apply_scenario(&scenario);
+                format!("Scenario opened from {}", path)
+            }
+            Err(error) => {
+                simplelog::error!("Couldn't open scenario {}: {}", path, error);
+                format!("Couldn't open scenario: {}", error)
+            }
+        };
+    }
+
+    /// Opens the checkpoint at [`Self::checkpoint_path`]. The next simulations start from it.
+    fn open_checkpoint(&mut self) {
+        let path = RenderingListener::make_save_into_dir(&self.checkpoint_path);
+        self.status = match Checkpoint::open(&path) {
+            Ok(checkpoint) => {
+                let status = format!(
+                    "The next simulations start from frame {} of {}",
+                    checkpoint.frame_number, path
+                );
+                self.renderer.start_from_checkpoint(Some(checkpoint));
+                status
+            }
+            Err(error) => {
+                simplelog::error!("Couldn't open checkpoint {}: {}", path, error);
+                format!("Couldn't open checkpoint: {}", error)
+            }
+        };
+    }
+
+    /// Exports all the rendered frames as an animation, configured in the viewport widget, on its
+    /// own thread. See [`Self::check_animation_export()`].
+    fn export_animation(&mut self) {
+        let animation_configs = self
+            .get_viewport_configs()
+            .map(|viewport_configs| viewport_configs.animation)
+            .unwrap_or_default();
+        let rendering_listener = self.renderer.rendering_listener.clone();
+        let frames = self.rendered_frames.clone();
+
+        // Encoding a long simulation takes a while, so the application isn't blocked meanwhile
+        let (export_tx, export_rx) = mpsc::channel();
+        std::thread::spawn(move || {
+            let _ =
+                export_tx.send(rendering_listener.export_animation(&frames, &animation_configs));
+        });
+        self.animation_export = Some(export_rx);
+        self.status = "Exporting animation...".to_string();
+    }
+
+    /// Shows the result of the running animation export, if it has finished.
+    fn check_animation_export(&mut self, frame: &epi::Frame) {
+        let result = match &self.animation_export {
+            Some(export_rx) => match export_rx.try_recv() {
+                Ok(result) => result,
+                Err(mpsc::TryRecvError::Empty) => {
+                    frame.request_repaint();
+                    return;
+                }
+                Err(mpsc::TryRecvError::Disconnected) => Err(std::io::Error::new(
+                    std::io::ErrorKind::Other,
+                    "the export has been interrupted",
+                )),
+            },
+            None => return,
+        };
+
+        self.animation_export = None;
+        self.status = match result {
+            Ok(path) => format!("Animation exported into {}", path),
+            Err(error) => {
+                simplelog::error!("Couldn't export animation: {}", error);
+                format!("Couldn't export animation: {}", error)
+            }
+        };
+    }
+
+    /// Returns the configurations of the viewport widget.
+    fn get_viewport_configs(&self) -> Option<&ViewportConfigs> {
         for i in self.settings_menu.settings_menu.iter() {
             if let SettingType::Viewport(result) = i {
-                return Some(result.image_resize_factor);
+                return Some(&result.viewport_configs);
             }
         }
         None
     }
 
+    /// Returns how zoomed is the simulation result image in the application.
+    fn get_zoom_factor(&self) -> Option<u8> {
+        self.get_viewport_configs()
+            .map(|viewport_configs| viewport_configs.image_resize_factor)
+    }
+
     /// Shows the cached image if it should. This is a helper function of [`Self::show_image()`].
     fn show_cached_image_in_ui(
         image: &CachedImage,
-        path: &str,
+        frame_number: i64,
         zoom_factor: u8,
         ui: &mut egui::Ui,
-    ) -> Result<(), ()> {
-        if !image.has_changed && image.consists_of(path, zoom_factor) {
-            ui.image(image.rendered_texture, image.dimensions);
-            return Ok(());
+    ) -> Result<egui::Response, ()> {
+        if !image.has_changed && image.consists_of(frame_number, zoom_factor) {
+            return Ok(Self::add_image(
+                ui,
+                image.rendered_texture,
+                image.dimensions,
+            ));
         }
         Err(())
     }
 
-    /// Generates a new image, shows it in the ui, and saves it as a cached image. This is a helper
+    /// Adds the image to the ui. The image senses dragging, so the user can paint on it. See
+    /// [`Self::paint_into_simulation()`].
+    fn add_image(
+        ui: &mut egui::Ui,
+        texture_id: egui::TextureId,
+        size: egui::Vec2,
+    ) -> egui::Response {
+        ui.add(egui::Image::new(texture_id, size).sense(egui::Sense::drag()))
+    }
+
+    /// Generates a new texture from the rendered frame, shows it in the ui, and saves it as a
+    /// cached image. The texture of the previously cached image is freed. This is a helper
     /// function of [`Self::show_image()`].
     fn generate_cached_image(
         &mut self,
-        image_path: &str,
+        frame_number: i64,
         zoom_factor: u8,
         frame: &epi::Frame,
         ui: &mut egui::Ui,
-    ) {
-        let image = image::open(image_path)
-            .expect(&("Couldn't open image ".to_owned() + image_path))
-            .resize(
-                (ui.available_width() * (zoom_factor as f32 / 100.0)) as u32,
-                (ui.available_height() * (zoom_factor as f32 / 100.0)) as u32,
-                Triangle,
-            );
-
-        let size = image.dimensions();
+    ) -> egui::Response {
+        let rendered_image = &self.rendered_frames[frame_number as usize];
+        let dimensions = rendered_image.dimensions();
 
         let image = epi::Image::from_rgba_unmultiplied(
-            [size.0.try_into().unwrap(), size.1.try_into().unwrap()],
-            &image.into_rgba8().into_raw(),
+            [dimensions.0 as usize, dimensions.1 as usize],
+            rendered_image.as_raw(),
         );
 
+        if let Some(cached_image) = &self.cached_image {
+            frame.free_texture(cached_image.rendered_texture);
+        }
         let texture_id = frame.alloc_texture(image);
-        let mut size = egui::Vec2::new(size.0 as f32, size.1 as f32);
-        size *= (ui.available_width() / size.x).min(1.0);
 
-        ui.image(texture_id, size);
+        // The image keeps its aspect ratio and fits into the available space when not zoomed
+        let points_per_pixel = (ui.available_width() / dimensions.0 as f32)
+            .min(ui.available_height() / dimensions.1 as f32)
+            * (zoom_factor as f32 / 100.0);
+        let size = egui::Vec2::new(dimensions.0 as f32, dimensions.1 as f32) * points_per_pixel;
+
+        let response = Self::add_image(ui, texture_id, size);
         self.cached_image = Some(CachedImage {
-            path: image_path.to_string(),
+            frame_number,
             zoom_factor,
             dimensions: size,
             rendered_texture: texture_id,
             has_changed: false,
         });
+        response
     }
 
-    /// Shows the image with the given path in the ui, by either taking it from the cached image,
-    /// or by generating it if it is not cached yet.
-    fn show_image(&mut self, image_path: &str, frame: &epi::Frame, ui: &mut egui::Ui) {
+    /// Shows the rendered frame with the given number in the ui, by either taking it from the
+    /// cached image, or by generating it if it is not cached yet.
+    fn show_image(
+        &mut self,
+        frame_number: i64,
+        frame: &epi::Frame,
+        ui: &mut egui::Ui,
+    ) -> egui::Response {
         let zoom_factor = self
             .get_zoom_factor()
             .expect("A viewport setting should exsist.");
 
-        let mut should_update_cached_image = false;
+        let cached_response = self.cached_image.as_ref().and_then(|image| {
+            Self::show_cached_image_in_ui(image, frame_number, zoom_factor, ui).ok()
+        });
 
-        match &self.cached_image {
-            Some(image) => {
-                should_update_cached_image =
-                    Self::show_cached_image_in_ui(image, image_path, zoom_factor, ui).is_err();
-            }
-            None => self.generate_cached_image(image_path, zoom_factor, frame, ui),
+        match cached_response {
+            Some(response) => response,
+            None => self.generate_cached_image(frame_number, zoom_factor, frame, ui),
         }
+    }
 
-        if should_update_cached_image {
-            self.generate_cached_image(image_path, zoom_factor, frame, ui);
-        }
+    /// Turns the mouse dragging over the shown image into a [`BrushStroke`] and paints it into the
+    /// running simulation. The velocity of the stroke follows the drag direction.
+    fn paint_into_simulation(&self, response: &egui::Response) {
+        let pointer_position = match response.interact_pointer_pos() {
+            Some(position) if response.dragged() && response.rect.contains(position) => position,
+            _ => return,
+        };
+
+        let simulation_configs = &self.renderer.current_simulation.fluid.simulation_configs;
+        let (width, height) = (simulation_configs.width, simulation_configs.height);
+        // The image keeps the aspect ratio of the container, so the cells are square on the screen
+        let cells_per_point = width as f32 / response.rect.width();
+        let cell = (pointer_position - response.rect.min) * cells_per_point;
+        let drag_delta = response.drag_delta() * cells_per_point * self.brush_force;
+
+        self.renderer.paint(BrushStroke {
+            center: (
+                (cell.x as u32).min(width - 1),
+                (cell.y as u32).min(height - 1),
+            ),
+            radius: self.brush_radius,
+            density: self.brush_density,
+            velocity: (drag_delta.x, drag_delta.y),
+        });
     }
 
-    /// Displays the next frame of the simulation in the central panel
-    fn move_simulation_frame(&mut self, next_frame: i64, frame: &epi::Frame, ui: &mut egui::Ui) {
-        let image_path =
-            density_img_path!(&self.renderer.rendering_listener.save_into_dir, next_frame);
-        simplelog::debug!(
-            "Reading from path: {}, save_into_dir: {}",
-            image_path,
-            self.renderer.rendering_listener.save_into_dir
-        );
+    /// Displays the next frame of the simulation in the central panel. Returns the response of
+    /// the shown image, if there is one.
+    fn move_simulation_frame(
+        &mut self,
+        next_frame: i64,
+        frame: &epi::Frame,
+        ui: &mut egui::Ui,
+    ) -> Option<egui::Response> {
+        if !(0..self.rendered_frames.len() as i64).contains(&next_frame) {
+            return None;
+        }
+
+        self.simulation_progress = next_frame as f32 / (self.frames_count() - 1) as f32;
+
+        let response = self.show_image(next_frame, frame, ui);
 
-        if image::open(image_path).is_err() {
-            return;
+        frame.request_repaint();
+        Some(response)
+    }
+
+    /// Joins all the spawned threads regarding the rendering process. The error which has stopped
+    /// the rendering, if any, is shown in the top panel.
+    fn join_rendering_joinhandles(&mut self) {
+        let mut buf: RenderingThreads = Vec::new();
+        std::mem::swap(&mut buf, &mut self.rendering_joinhandlers);
+        for el in buf {
+            if let Ok(Err(error)) = el.join() {
+                self.status = format!("The simulation has been stopped: {}", error);
+            }
         }
+    }
 
-        self.simulation_progress = next_frame as f32
-            / (self
-                .renderer
+    /// Returns the number of frames which can be navigated through: all the frames of the running
+    /// simulation, or only the rendered ones if the simulation has been stopped early.
+    fn frames_count(&self) -> i64 {
+        if self.is_simulation_in_process || self.rendered_frames.is_empty() {
+            self.renderer
                 .current_simulation
                 .fluid
                 .simulation_configs
                 .frames
-                - 1) as f32;
+        } else {
+            self.rendered_frames.len() as i64
+        }
+    }
 
-        self.show_image(&image_path, frame, ui);
+    /// Waits for the rendering threads, and marks the simulation as finished, even if it has been
+    /// stopped before its last frame. The frames rendered in the meantime are kept.
+    fn finish_simulation(&mut self) {
+        self.join_rendering_joinhandles();
+        self.signal_receiver.try_iter().for_each(|rendered_frame| {
+            self.rendered_frames.push(rendered_frame.image);
+        });
 
-        frame.request_repaint();
+        self.is_play_button_on = false;
+        self.is_simulation_in_process = false;
+        self.is_simulation_ready = !self.rendered_frames.is_empty();
+        self.current_frame = self
+            .current_frame
+            .min(self.rendered_frames.len() as i64 - 1)
+            .max(0);
     }
 
-    /// Joins all the spawned threads regarding the rendering process.
-    fn join_rendering_joinhandles(&mut self) {
-        let mut buf: Vec<JoinHandle<()>> = Vec::new();
-        std::mem::swap(&mut buf, &mut self.rendering_joinhandlers);
-        buf.into_iter().for_each(|el| {
-            let _ = el.join();
-        });
+    /// Starts a new simulation with the current configurations. The running simulation, if any,
+    /// is stopped. If the new simulation can't start, e.g. from the chosen checkpoint, the error
+    /// is shown in the top panel and the running simulation goes on.
+    fn start_simulation(&mut self) {
+        let rendering_result = match self.renderer.render() {
+            Ok(rendering_result) => rendering_result,
+            Err(error) => {
+                simplelog::error!("Couldn't start the simulation: {}", error);
+                self.status = format!("Couldn't start the simulation: {}", error);
+                return;
+            }
+        };
+        // The renderer has already stopped the running simulation
+        if self.is_simulation_in_process {
+            self.finish_simulation();
+        }
+
+        self.simulation_progress = 0.0;
+        self.current_frame = 0;
+        self.rendered_frames.clear();
+        if let Some(cached_image) = &mut self.cached_image {
+            cached_image.has_changed = true;
+        }
+
+        self.signal_receiver = rendering_result.0;
+        self.rendering_joinhandlers = rendering_result.1;
+
+        self.is_simulation_in_process = true;
+        self.is_play_button_on = true;
+    }
+
+    fn live_changes_name(live_changes: LiveChanges) -> &'static str {
+        match live_changes {
+            LiveChanges::NextRun => "Apply in the next run",
+            LiveChanges::Restart => "Restart on change",
+            LiveChanges::Continue => "Continue with new parameters",
+        }
+    }
+
+    /// Stops the running simulation. The frames rendered so far are kept.
+    fn stop_simulation(&mut self) {
+        self.renderer.stop();
+        self.finish_simulation();
     }
 
     /// Manages the next frame - either takes it from a channel open between the renderer and the
     /// applicaiton, or directly increments the current_frame.
     fn manage_next_frame(&mut self, frame: &epi::Frame) {
-        let frames_count = self
-            .renderer
-            .current_simulation
-            .fluid
-            .simulation_configs
-            .frames;
+        let frames_count = self.frames_count();
 
         if self.is_simulation_in_process {
             if self.current_frame < frames_count - 1 {
-                let last_frame = self.current_frame;
-
-                self.current_frame = self
-                    .signal_receiver
-                    .try_recv()
-                    .unwrap_or(self.current_frame);
+                loop {
+                    match self.signal_receiver.try_recv() {
+                        Ok(rendered_frame) => {
+                            self.current_frame = rendered_frame.frame_number;
+                            self.rendered_frames.push(rendered_frame.image);
+                        }
+                        Err(mpsc::TryRecvError::Empty) => break,
+                        // The rendering threads have finished before the last frame
+                        Err(mpsc::TryRecvError::Disconnected) => {
+                            self.finish_simulation();
+                            return;
+                        }
+                    }
+                }
 
-                if last_frame != self.current_frame {
+                if !self.rendered_frames.is_empty() {
                     self.is_simulation_ready = true;
                 }
             }
@@ -229,12 +479,8 @@ impl App {
             self.current_frame += 1;
         }
 
-        if self.current_frame == frames_count - 1 {
-            self.is_play_button_on = false;
-            self.is_simulation_in_process = false;
-            self.is_simulation_ready = true;
-
-            self.join_rendering_joinhandles();
+        if self.current_frame >= frames_count - 1 {
+            self.finish_simulation();
         }
     }
 }
@@ -251,22 +497,47 @@ impl epi::App for App {
         _storage: Option<&dyn epi::Storage>,
     ) {
         #[cfg(feature = "persistence")]
-        if let Some(storage) = _storage {
-            *self = epi::get_value(storage, epi::APP_KEY).unwrap_or_default()
+        if let Some(scenario) =
+            _storage.and_then(|storage| epi::get_value::<Scenario>(storage, epi::APP_KEY))
+        {
+            self.settings_menu.apply_scenario(&scenario);
         }
     }
 
     #[cfg(feature = "persistence")]
     fn save(&mut self, storage: &mut dyn epi::Storage) {
-        epi::set_value(storage, epi::APP_KEY, self);
+        epi::set_value(storage, epi::APP_KEY, &self.settings_menu.to_scenario());
     }
 
     fn update(&mut self, ctx: &egui::CtxRef, frame: &epi::Frame) {
-        self.renderer
-            .update_configs(&self.settings_menu.settings_menu);
+        // The removed dyes are followed before the rest of the changes, so the densities of the
+        // remaining dyes are kept
+        let removed_dyes = self.settings_menu.take_removed_dyes();
+        for removed_dye in removed_dyes.iter() {
+            self.renderer.remove_dye(*removed_dye);
+        }
+        let has_changed = self
+            .renderer
+            .update_configs(&self.settings_menu.settings_menu)
+            || !removed_dyes.is_empty();
+        if has_changed
+            && self.is_simulation_in_process
+            && self.renderer.live_changes == LiveChanges::Restart
+        {
+            self.is_restart_pending = true;
+        }
+        // Dragging a value changes it in every frame, so the simulation isn't restarted each time
+        if self.is_restart_pending && !ctx.input().pointer.any_down() {
+            self.is_restart_pending = false;
+            if self.is_simulation_in_process {
+                self.start_simulation();
+            }
+        }
+
+        self.check_animation_export(frame);
 
         egui::TopBottomPanel::top("top_panel").show(ctx, |ui| {
-            Self::bar_content(ui, frame);
+            self.bar_content(ui, frame);
         });
 
         egui::SidePanel::left("left_panel").show(ctx, |ui| {
@@ -288,17 +559,60 @@ impl epi::App for App {
 
 impl App {
     /// The GUI organization for the bar on the top of the application.
-    fn bar_content(ui: &mut egui::Ui, frame: &epi::Frame) {
+    fn bar_content(&mut self, ui: &mut egui::Ui, frame: &epi::Frame) {
         ui.horizontal_wrapped(|ui| {
             global_dark_light_mode_switch(ui);
 
             egui::menu::bar(ui, |ui| {
                 ui.menu_button("File", |ui| {
+                    ui.label("Scenario file:");
+                    ui.text_edit_singleline(&mut self.scenario_path);
+
+                    if ui.button("Save scenario").clicked() {
+                        self.save_scenario();
+                        ui.close_menu();
+                    }
+
+                    if ui.button("Open scenario").clicked() {
+                        self.open_scenario();
+                        ui.close_menu();
+                    }
+
+                    ui.separator();
+
+                    ui.label("Checkpoint file:");
+                    ui.text_edit_singleline(&mut self.checkpoint_path);
+
+                    if ui
+                        .button("Start from checkpoint")
+                        .on_hover_text("The next simulations start from the state of the fluid in the checkpoint")
+                        .clicked()
+                    {
+                        self.open_checkpoint();
+                        ui.close_menu();
+                    }
+
+                    if ui
+                        .add_enabled(
+                            self.renderer.next_checkpoint().is_some(),
+                            egui::Button::new("Start from the beginning"),
+                        )
+                        .clicked()
+                    {
+                        self.renderer.start_from_checkpoint(None);
+                        self.status = "The next simulations start from the beginning".to_string();
+                        ui.close_menu();
+                    }
+
+                    ui.separator();
+
                     if ui.button("Exit").clicked() {
                         frame.quit();
                     }
                 });
             });
+
+            ui.label(&self.status);
         });
     }
 
@@ -306,49 +620,22 @@ impl App {
     fn left_panel(&mut self, ui: &mut egui::Ui) {
         ui.heading("Navigate simulation");
 
+        let frames_count = self.frames_count();
         ui.add(
-            egui::Slider::new(
-                &mut self.current_frame,
-                0..=self
-                    .renderer
-                    .current_simulation
-                    .fluid
-                    .simulation_configs
-                    .frames
-                    - 1,
-            )
-            .text("Current frame"),
+            egui::Slider::new(&mut self.current_frame, 0..=frames_count - 1).text("Current frame"),
         );
 
         ui.horizontal_wrapped(|ui| {
             if ui.button("Previous").clicked() {
-                self.current_frame = (self.current_frame - 1)
-                    % self
-                        .renderer
-                        .current_simulation
-                        .fluid
-                        .simulation_configs
-                        .frames;
+                self.current_frame = (self.current_frame - 1) % frames_count;
 
                 if self.current_frame < 0 {
-                    self.current_frame = self
-                        .renderer
-                        .current_simulation
-                        .fluid
-                        .simulation_configs
-                        .frames
-                        - 1;
+                    self.current_frame = frames_count - 1;
                 }
             }
 
             if ui.button("Next").clicked() {
-                self.current_frame = (self.current_frame + 1)
-                    % self
-                        .renderer
-                        .current_simulation
-                        .fluid
-                        .simulation_configs
-                        .frames;
+                self.current_frame = (self.current_frame + 1) % frames_count;
             }
         });
 
@@ -360,21 +647,79 @@ impl App {
         ui.separator();
 
         if ui.button("Simulate fluid").clicked() {
-            self.simulation_progress = 0.0;
-            self.current_frame = 0;
+            self.start_simulation();
+        }
 
-            let rendering_result = self.renderer.render();
-            self.signal_receiver = rendering_result.0;
-            self.rendering_joinhandlers = rendering_result.1;
+        ui.horizontal(|ui| {
+            ui.label("Changes while running:").on_hover_text(
+                "Continuing applies only the changes of the fluid settings, the dyes, the obstacles and their color",
+            );
+            egui::ComboBox::from_id_source("live_changes")
+                .selected_text(Self::live_changes_name(self.renderer.live_changes))
+                .show_ui(ui, |ui| {
+                    for live_changes in [
+                        LiveChanges::NextRun,
+                        LiveChanges::Restart,
+                        LiveChanges::Continue,
+                    ] {
+                        ui.selectable_value(
+                            &mut self.renderer.live_changes,
+                            live_changes,
+                            Self::live_changes_name(live_changes),
+                        );
+                    }
+                });
+        });
 
-            self.is_simulation_in_process = true;
-            self.is_play_button_on = true;
-        }
+        ui.add_enabled_ui(self.is_simulation_in_process, |ui| {
+            ui.horizontal(|ui| {
+                if self.renderer.is_paused() {
+                    if ui.button("Resume").clicked() {
+                        self.renderer.resume();
+                    }
+                } else if ui.button("Pause").clicked() {
+                    self.renderer.pause();
+                }
+
+                if ui
+                    .button("Stop")
+                    .on_hover_text("Stop the simulation and keep the frames rendered so far")
+                    .clicked()
+                {
+                    self.stop_simulation();
+                }
+            });
+        });
 
         ui.label("Simulation Progress:");
         let progress_bar = egui::ProgressBar::new(self.simulation_progress).show_percentage();
         ui.add(progress_bar);
 
+        if ui
+            .add_enabled(
+                self.is_simulation_ready
+                    && !self.is_simulation_in_process
+                    && self.animation_export.is_none(),
+                egui::Button::new("Export animation"),
+            )
+            .on_hover_text("Save all the rendered frames as a GIF or APNG animation, configured in the viewport widget")
+            .clicked()
+        {
+            self.export_animation();
+        }
+
+        ui.separator();
+
+        ui.checkbox(&mut self.is_painting_enabled, "Paint with the mouse")
+            .on_hover_text(
+                "Drag the mouse over the simulation while it is running to add density and push the fluid",
+            );
+        ui.add_enabled_ui(self.is_painting_enabled, |ui| {
+            ui.add(egui::Slider::new(&mut self.brush_radius, 0..=20).text("Brush radius"));
+            ui.add(egui::Slider::new(&mut self.brush_density, 0.0..=2.0).text("Brush density"));
+            ui.add(egui::Slider::new(&mut self.brush_force, 0.0..=20.0).text("Brush force"));
+        });
+
         ui.with_layout(egui::Layout::bottom_up(egui::Align::LEFT), |ui| {
             ui.horizontal(|ui| {
                 ui.spacing_mut().item_spacing.x = 0.0;
@@ -395,7 +740,11 @@ impl App {
         }
 
         if self.is_simulation_ready {
-            self.move_simulation_frame(self.current_frame, frame, ui);
+            let response = self.move_simulation_frame(self.current_frame, frame, ui);
+
+            if let Some(response) = response.filter(|_| self.is_painting_enabled) {
+                self.paint_into_simulation(&response);
+            }
         }
 
         ui.hyperlink("https://github.com/vkabadzhova/equilibrium");
@@ -457,16 +806,6 @@ impl App {
 
 #[cfg(test)]
 mod tests {
-    use crate::simulation::renderer_helpers::density_img_path;
-
-    #[test]
-    fn density_img_path_str_works() {
-        assert_eq!(
-            density_img_path!("holiday_dir", 0),
-            "holiday_dir/density0.jpg"
-        );
-    }
-
     use crate::app::app::App;
     use crate::simulation::renderer::Renderer;
 
@@ -482,11 +821,13 @@ mod tests {
 
         // 100 miliseconds should be fast, but also enough to check if the test worked out.
         result_joinhandles.push(std::thread::spawn(move || {
-            std::thread::sleep(std::time::Duration::from_millis(100))
+            std::thread::sleep(std::time::Duration::from_millis(100));
+            Ok(())
         }));
 
         result_joinhandles.push(std::thread::spawn(move || {
-            std::thread::sleep(std::time::Duration::from_millis(100))
+            std::thread::sleep(std::time::Duration::from_millis(100));
+            Err(std::io::Error::new(std::io::ErrorKind::Other, "disk full"))
         }));
 
         // ------ Put threads in app ----
@@ -498,5 +839,7 @@ mod tests {
         // ------ Join threads ----
         app.join_rendering_joinhandles();
         assert_eq!(app.rendering_joinhandlers.len(), 0);
+        // The error of the rendering is shown
+        assert!(app.status.contains("disk full"));
     }
 }
@@ -3,9 +3,9 @@ use eframe::egui::TextureId;
 /// A record of a cached image
 #[derive(Clone)]
 pub struct CachedImage {
-    /// The path of the last rendered image is saved. The field is used for comparison if the last
+    /// The number of the last shown frame is saved. The field is used for comparison if the last
     /// texture should be used.
-    pub path: String,
+    pub frame_number: i64,
 
     /// The previous zoom factor for the image is saved. The field is used for comparison if the
     /// last texture should be used.
@@ -23,7 +23,7 @@ pub struct CachedImage {
 
 impl CachedImage {
     /// States if the given structure is already cached in the current object.
-    pub fn consists_of(&self, path: &str, zoom: u8) -> bool {
-        self.path == path && self.zoom_factor == zoom
+    pub fn consists_of(&self, frame_number: i64, zoom: u8) -> bool {
+        self.frame_number == frame_number && self.zoom_factor == zoom
     }
 }
@@ -1,4 +1,5 @@
 /// The fluid simulator's entry point for the GUI.
+#[allow(clippy::module_inception)]
 pub mod app;
 
 /// The module contains structs, etc. for caching images
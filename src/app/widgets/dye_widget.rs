@@ -0,0 +1,141 @@
+use eframe::egui;
+use egui::*;
+
+use crate::simulation::configs::DyeConfigs;
+
+/// Menu for adding, removing and changing the passive dyes carried by the fluid.
+#[derive(Clone)]
+pub struct DyeWidget {
+    enabled: bool,
+    /// Collection of all the dyes. The emitters refer to them by their index.
+    pub dyes: Vec<DyeConfigs>,
+    /// The indices of the dyes removed since the last [`DyeWidget::take_removed_dyes()`], in the
+    /// order of their removal
+    removed_dyes: Vec<usize>,
+}
+
+impl Default for DyeWidget {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            dyes: Vec::new(),
+            removed_dyes: Vec::new(),
+        }
+    }
+}
+
+impl super::Setting for DyeWidget {
+    fn name(&self) -> &'static str {
+        "🎨 Dye"
+    }
+
+    fn show(&mut self, ctx: &egui::CtxRef, open: &mut bool) {
+        egui::Window::new(self.name())
+            .open(open)
+            .resizable(true)
+            .default_width(300.0)
+            .show(ctx, |ui| {
+                use super::View as _;
+                self.ui(ui);
+            });
+    }
+}
+
+impl super::View for DyeWidget {
+    fn ui(&mut self, ui: &mut egui::Ui) {
+        ui.add_enabled_ui(self.enabled, |ui| {
+            ui.label("Dyes:");
+
+            let mut dye_to_remove = None;
+            for (i, dye) in self.dyes.iter_mut().enumerate() {
+                CollapsingHeader::new(format!("Dye {}", i + 1))
+                    .id_source(i)
+                    .default_open(false)
+                    .show(ui, |ui| {
+                        if ui
+                            .button(RichText::new("delete").color(Color32::RED))
+                            .on_hover_text("Deletes the dye together with its emitters")
+                            .clicked()
+                        {
+                            dye_to_remove = Some(i);
+                        }
+                        Self::dye_ui(ui, dye);
+                    });
+            }
+
+            if let Some(i) = dye_to_remove {
+                self.remove_dye(i);
+            }
+
+            if ui.button("+ Dye").clicked() {
+                self.add_dye();
+            }
+        });
+
+        ui.separator();
+
+        ui.horizontal(|ui| {
+            ui.checkbox(&mut self.enabled, "Interactive").on_hover_text(
+                "Uncheck to disable the widget so you could inspect the simulation securely.",
+            );
+        });
+    }
+}
+
+impl DyeWidget {
+    /// Adds a new dye with the default configurations after the other dyes.
+    pub fn add_dye(&mut self) {
+        self.dyes.push(DyeConfigs::default());
+    }
+
+    /// Removes the dye with the given index. The removal is kept until
+    /// [`DyeWidget::take_removed_dyes()`], so the emitters and the running simulation could follow
+    /// it.
+    pub fn remove_dye(&mut self, index: usize) {
+        self.dyes.remove(index);
+        self.removed_dyes.push(index);
+    }
+
+    /// Returns the indices of the dyes removed since the last call, in the order of their removal.
+    pub fn take_removed_dyes(&mut self) -> Vec<usize> {
+        std::mem::take(&mut self.removed_dyes)
+    }
+
+    /// Creates the layout for a single dye.
+    fn dye_ui(ui: &mut Ui, dye: &mut DyeConfigs) {
+        ui.horizontal(|ui| {
+            ui.label("Diffusion: ");
+            ui.add(DragValue::new(&mut dye.diffusion).speed(0.0001));
+        });
+        dye.diffusion = dye.diffusion.max(0.0);
+
+        ui.horizontal(|ui| {
+            ui.label("Color: ").on_hover_text(
+                "The dye is blended over the fluid according to the alpha of its color",
+            );
+            ui.color_edit_button_srgba(&mut dye.color);
+        });
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::app::widgets::dye_widget::*;
+
+    #[test]
+    fn dyes_are_added_and_removed() {
+        let mut dye_widget = DyeWidget::default();
+        for _ in 0..3 {
+            dye_widget.add_dye();
+        }
+        dye_widget.dyes[2].diffusion = 0.5;
+
+        dye_widget.remove_dye(1);
+        dye_widget.remove_dye(0);
+
+        assert_eq!(dye_widget.dyes.len(), 1);
+        assert_eq!(dye_widget.dyes[0].diffusion, 0.5);
+        assert_eq!(dye_widget.take_removed_dyes(), [1, 0]);
+        assert!(dye_widget.take_removed_dyes().is_empty());
+    }
+}
@@ -0,0 +1,164 @@
+use eframe::egui;
+use egui::*;
+
+use crate::simulation::emitter::Emitter;
+
+/// Menu for adding, removing and changing the emitters of the fluid.
+#[derive(Clone)]
+pub struct EmitterWidget {
+    enabled: bool,
+    /// Collection of all the emitters in the fluid's container
+    pub emitters: Vec<Emitter>,
+}
+
+impl Default for EmitterWidget {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            emitters: Vec::new(),
+        }
+    }
+}
+
+impl super::Setting for EmitterWidget {
+    fn name(&self) -> &'static str {
+        "💧 Emitter"
+    }
+
+    fn show(&mut self, ctx: &egui::CtxRef, open: &mut bool) {
+        egui::Window::new(self.name())
+            .open(open)
+            .resizable(true)
+            .default_width(300.0)
+            .show(ctx, |ui| {
+                use super::View as _;
+                self.ui(ui);
+            });
+    }
+}
+
+impl super::View for EmitterWidget {
+    fn ui(&mut self, ui: &mut egui::Ui) {
+        ui.add_enabled_ui(self.enabled, |ui| {
+            ui.label("Emitters:");
+
+            let mut emitter_to_remove = None;
+            for (i, emitter) in self.emitters.iter_mut().enumerate() {
+                CollapsingHeader::new(format!("Emitter {}", i + 1))
+                    .id_source(i)
+                    .default_open(false)
+                    .show(ui, |ui| {
+                        if ui
+                            .button(RichText::new("delete").color(Color32::RED))
+                            .clicked()
+                        {
+                            emitter_to_remove = Some(i);
+                        }
+                        Self::emitter_ui(ui, emitter);
+                    });
+            }
+
+            if let Some(i) = emitter_to_remove {
+                self.emitters.remove(i);
+            }
+
+            if ui.button("+ Emitter").clicked() {
+                self.emitters.push(Emitter::default());
+            }
+        });
+
+        ui.separator();
+
+        ui.horizontal(|ui| {
+            ui.checkbox(&mut self.enabled, "Interactive").on_hover_text(
+                "Uncheck to disable the widget so you could inspect the simulation securely.",
+            );
+        });
+    }
+}
+
+impl EmitterWidget {
+    /// Creates the layout for a single emitter.
+    fn emitter_ui(ui: &mut Ui, emitter: &mut Emitter) {
+        ui.horizontal(|ui| {
+            ui.label("Center: ");
+            ui.add(DragValue::new(&mut emitter.center.0).speed(1.0));
+            ui.add(DragValue::new(&mut emitter.center.1).speed(1.0));
+        });
+        emitter.center.0 = emitter.center.0.max(0);
+        emitter.center.1 = emitter.center.1.max(0);
+
+        ui.horizontal(|ui| {
+            ui.label("Radius: ");
+            ui.add(DragValue::new(&mut emitter.radius).speed(1.0));
+        });
+        emitter.radius = emitter.radius.max(1);
+
+        ui.horizontal(|ui| {
+            ui.label("Density rate: ")
+                .on_hover_text("Negative values remove the density, i.e. make a sink");
+            ui.add(DragValue::new(&mut emitter.density_rate).speed(0.1));
+        });
+
+        let mut emits_dye = emitter.dye.is_some();
+        ui.checkbox(&mut emits_dye, "Emits a dye")
+            .on_hover_text("The emitter adds one of the dyes instead of the fluid's density");
+        emitter.dye = match (emits_dye, emitter.dye) {
+            (true, Some(dye)) => {
+                // The dyes are numbered from 1 in the dye widget
+                let mut dye_number = dye + 1;
+                ui.horizontal(|ui| {
+                    ui.label("Dye: ");
+                    ui.add(DragValue::new(&mut dye_number).speed(0.1));
+                });
+                Some(dye_number.max(1) - 1)
+            }
+            (true, None) => Some(0),
+            (false, _) => None,
+        };
+
+        ui.horizontal(|ui| {
+            ui.label("Heat rate: ").on_hover_text(
+                "Negative values cool the fluid. Only used if the fluid has a temperature",
+            );
+            ui.add(DragValue::new(&mut emitter.heat_rate).speed(0.1));
+        });
+
+        ui.horizontal(|ui| {
+            ui.label("Velocity: ");
+            ui.add(DragValue::new(&mut emitter.velocity.0).speed(0.1));
+            ui.add(DragValue::new(&mut emitter.velocity.1).speed(0.1));
+        });
+
+        ui.horizontal(|ui| {
+            ui.label("Start frame: ");
+            ui.add(DragValue::new(&mut emitter.start_frame).speed(1.0));
+        });
+        emitter.start_frame = emitter.start_frame.max(0);
+
+        let mut works_till_the_end = emitter.end_frame.is_none();
+        ui.checkbox(&mut works_till_the_end, "Works till the end");
+        emitter.end_frame = match (works_till_the_end, emitter.end_frame) {
+            (true, _) => None,
+            (false, Some(mut end_frame)) => {
+                ui.horizontal(|ui| {
+                    ui.label("End frame: ");
+                    ui.add(DragValue::new(&mut end_frame).speed(1.0));
+                });
+                Some(end_frame.max(emitter.start_frame))
+            }
+            (false, None) => Some(emitter.start_frame),
+        };
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::app::widgets::emitter_widget::*;
+
+    #[test]
+    fn no_emitters_by_default() {
+        let emitter_widget = EmitterWidget::default();
+        assert!(emitter_widget.emitters.is_empty());
+    }
+}
@@ -1,13 +1,17 @@
-use crate::simulation::configs::FluidConfigs;
+use crate::simulation::configs::{FluidConfigs, SimulationConfigs};
 use eframe::egui;
 
 /// Shows off one example of each major type of widget.
-#[cfg_attr(feature = "serde", derive(serde::Deserialize, serde::Serialize))]
 #[derive(Copy, Clone)]
 pub struct FluidWidget {
     enabled: bool,
     /// The fluid configurations
     pub fluid_configs: FluidConfigs,
+    /// The simulation configurations the dimensionless numbers of the fluid are derived from.
+    /// They are kept in sync with the
+    /// [`SimulationWidget`](super::simulation_widget::SimulationWidget) by the
+    /// [`SettingsMenu`](super::widgets_menu::SettingsMenu).
+    pub simulation_configs: SimulationConfigs,
 }
 
 impl Default for FluidWidget {
@@ -15,6 +19,7 @@ impl Default for FluidWidget {
         Self {
             enabled: true,
             fluid_configs: FluidConfigs::default(),
+            simulation_configs: SimulationConfigs::default(),
         }
     }
 }
@@ -71,6 +76,7 @@ impl FluidWidget {
         let Self {
             enabled: _,
             fluid_configs,
+            simulation_configs,
         } = self;
 
         ui.label("Setting panel descritpion");
@@ -95,8 +101,83 @@ impl FluidWidget {
         ui.add(egui::Slider::new(&mut fluid_configs.viscousity, 0.0..=1.0).suffix(" m2/s"));
         ui.end_row();
 
+        ui.hyperlink_to(
+            "Reynolds number",
+            "https://en.wikipedia.org/wiki/Reynolds_number",
+        )
+        .on_hover_text(
+            "Sets the viscousity from the domain length and the characteristic velocity",
+        );
+        let mut reynolds_number = fluid_configs.reynolds_number(simulation_configs);
+        if ui
+            .add(egui::DragValue::new(&mut reynolds_number).speed(1.0))
+            .changed()
+            && reynolds_number > 0.0
+        {
+            fluid_configs.set_reynolds_number(reynolds_number, simulation_configs);
+        }
+        ui.end_row();
+
         ui.hyperlink_to("Diffusion", "https://en.wikipedia.org/wiki/Diffusion");
-        ui.add(egui::Slider::new(&mut fluid_configs.diffusion, 0.0..=1.0));
+        ui.add(egui::Slider::new(&mut fluid_configs.diffusion, 0.0..=1.0).suffix(" m2/s"));
+        ui.end_row();
+
+        ui.hyperlink_to(
+            "Péclet number",
+            "https://en.wikipedia.org/wiki/P%C3%A9clet_number",
+        )
+        .on_hover_text("How much faster the density is moved by the fluid than it diffuses");
+        ui.label(format!(
+            "{:.3e}",
+            fluid_configs.peclet_number(simulation_configs)
+        ));
+        ui.end_row();
+
+        ui.label("Vorticity confinement")
+            .on_hover_text("Keeps the small vortices from fading away. 0 disables the confinement");
+        ui.add(egui::Slider::new(
+            &mut fluid_configs.vorticity_confinement,
+            0.0..=20.0,
+        ));
+        ui.end_row();
+
+        ui.hyperlink_to(
+            "Temperature and buoyancy",
+            "https://en.wikipedia.org/wiki/Boussinesq_approximation_(buoyancy)",
+        );
+        ui.checkbox(&mut fluid_configs.has_temperature, "");
+        ui.end_row();
+
+        if !fluid_configs.has_temperature {
+            return;
+        }
+
+        ui.label("Thermal diffusion");
+        ui.add(egui::Slider::new(
+            &mut fluid_configs.thermal_diffusion,
+            0.0..=1.0,
+        ));
+        ui.end_row();
+
+        ui.label("Ambient temperature");
+        ui.add(egui::DragValue::new(&mut fluid_configs.ambient_temperature).speed(0.1));
+        ui.end_row();
+
+        ui.label("Thermal expansion")
+            .on_hover_text("How strongly the warm fluid rises and the cold fluid sinks");
+        ui.add(egui::DragValue::new(&mut fluid_configs.thermal_expansion).speed(0.01));
+        ui.end_row();
+
+        ui.label("Density weight")
+            .on_hover_text("How strongly the dense fluid sinks");
+        ui.add(egui::DragValue::new(&mut fluid_configs.density_weight).speed(0.01));
+        ui.end_row();
+
+        ui.label("Gravity");
+        ui.horizontal(|ui| {
+            ui.add(egui::DragValue::new(&mut fluid_configs.gravity.0).speed(0.1));
+            ui.add(egui::DragValue::new(&mut fluid_configs.gravity.1).speed(0.1));
+        });
         ui.end_row();
     }
 }
@@ -1,3 +1,7 @@
+/// Menu for adding passive dyes carried by the fluid.
+pub mod dye_widget;
+/// Menu for adding emitters of density and velocity to the fluid simulation.
+pub mod emitter_widget;
 /// Menu with settings for the fluid
 pub mod fluid_widget;
 /// Menu for setting obstacles in the fluid simulation.
@@ -1,10 +1,9 @@
 use eframe::egui;
 use egui::*;
 
-use crate::simulation::obstacle::{Obstacle, ObstaclesType, Rectangle};
+use crate::simulation::obstacle::{Circle, Ellipse, Mask, ObstaclesType, Polygon, Rectangle};
 
 /// Shows off one example of each major type of widget.
-#[cfg_attr(feature = "serde", derive(serde::Deserialize, serde::Serialize))]
 //#[derive(Clone)]
 pub struct ObstacleWidget {
     enabled: bool,
@@ -70,9 +69,24 @@ impl super::View for ObstacleWidget {
                 .show(ui, |ui| obstacle.ui(ui));
         }
 
-        if ui.button("+").clicked() {
-            self.add_obstacle();
-        }
+        ui.horizontal_wrapped(|ui| {
+            ui.label("Add:");
+            if ui.button("+ Rectangle").clicked() {
+                self.add_obstacle();
+            }
+            if ui.button("+ Circle").clicked() {
+                self.add_obstacle_of_type(ObstaclesType::Circle(Circle::default()));
+            }
+            if ui.button("+ Ellipse").clicked() {
+                self.add_obstacle_of_type(ObstaclesType::Ellipse(Ellipse::default()));
+            }
+            if ui.button("+ Polygon").clicked() {
+                self.add_obstacle_of_type(ObstaclesType::Polygon(Polygon::default()));
+            }
+            if ui.button("+ Mask").clicked() {
+                self.add_obstacle_of_type(ObstaclesType::Mask(Mask::default()));
+            }
+        });
 
         self.obstacles.retain(|el| el.action == Action::Keep);
     }
@@ -120,16 +134,29 @@ impl ObstacleWidget {
     ///
     /// ```
     pub fn add_obstacle(&mut self) {
-        let default_obstacle_layout = ObstacleLayout::default();
+        self.add_obstacle_of_type(ObstacleLayout::default().obstacle);
+    }
 
+    /// Adds the given obstacle. It is named the same way as in
+    /// [`ObstacleWidget::add_obstacle()`], e.g. "Circle/3".
+    pub fn add_obstacle_of_type(&mut self, obstacle: ObstaclesType) {
         self.last_obstacle_id += 1;
 
         self.obstacles.push(ObstacleLayout {
-            name: format!("{}/{}", default_obstacle_layout.name, self.last_obstacle_id),
-            obstacle: default_obstacle_layout.obstacle,
-            action: default_obstacle_layout.action,
+            name: format!("{}/{}", obstacle.name(), self.last_obstacle_id),
+            obstacle,
+            action: Action::Keep,
         });
     }
+
+    /// Replaces all the obstacles with the given ones, e.g. when a scenario is opened. The new
+    /// obstacles are named the same way as in [`ObstacleWidget::add_obstacle()`].
+    pub fn set_obstacles(&mut self, obstacles: &[ObstaclesType]) {
+        self.obstacles.clear();
+        for obstacle in obstacles.iter() {
+            self.add_obstacle_of_type(obstacle.clone());
+        }
+    }
 }
 
 /// Describes if an element should be deleted or kept alive in the next frame.
@@ -173,18 +200,102 @@ impl ObstacleLayout {
             self.action = Action::Delete;
         }
 
-        let approximate_points = self.obstacle.get_approximate_points();
-        for i in 0..approximate_points.len() {
-            ui.label("Point: ");
-            ui.add(egui::DragValue::new(&mut approximate_points[i].0).speed(1.0));
-            ui.add(egui::DragValue::new(&mut approximate_points[i].1).speed(1.0));
-            if approximate_points[i].0 < 0 {
-                approximate_points[i].0 = 0;
+        match &mut self.obstacle {
+            ObstaclesType::Rectangle(rectangle) => {
+                let points = rectangle.get_approximate_points();
+                Self::point_ui(ui, "Down left point: ", &mut points[0]);
+                Self::point_ui(ui, "Up right point: ", &mut points[1]);
             }
-
-            if approximate_points[i].1 < 0 {
-                approximate_points[i].1 = 0;
+            ObstaclesType::Circle(circle) => {
+                Self::point_ui(ui, "Center: ", &mut circle.center);
+                Self::length_ui(ui, "Radius: ", &mut circle.radius);
+            }
+            ObstaclesType::Ellipse(ellipse) => {
+                Self::point_ui(ui, "Center: ", &mut ellipse.center);
+                Self::length_ui(ui, "Radius x: ", &mut ellipse.radius_x);
+                Self::length_ui(ui, "Radius y: ", &mut ellipse.radius_y);
+                ui.horizontal(|ui| {
+                    ui.label("Rotation: ");
+                    ui.add(egui::Slider::new(&mut ellipse.rotation, -180.0..=180.0).suffix("°"));
+                });
             }
+            ObstaclesType::Polygon(polygon) => {
+                let can_remove_vertex = polygon.vertices.len() > 3;
+                let mut vertex_to_remove = None;
+
+                for (i, vertex) in polygon.vertices.iter_mut().enumerate() {
+                    ui.horizontal(|ui| {
+                        Self::point_ui(ui, &format!("Vertex {}: ", i + 1), vertex);
+                        if ui
+                            .add_enabled(can_remove_vertex, egui::Button::new("✖"))
+                            .clicked()
+                        {
+                            vertex_to_remove = Some(i);
+                        }
+                    });
+                }
+
+                if let Some(i) = vertex_to_remove {
+                    polygon.vertices.remove(i);
+                }
+
+                if ui.button("+ vertex").clicked() {
+                    // Safety note: a polygon has at least 3 vertices.
+                    let last_vertex = *polygon.vertices.last().unwrap();
+                    polygon
+                        .vertices
+                        .push((last_vertex.0 + 5, last_vertex.1 + 5));
+                }
+            }
+            ObstaclesType::Mask(mask) => {
+                ui.horizontal(|ui| {
+                    ui.label("Image: ");
+                    ui.text_edit_singleline(&mut mask.path);
+                });
+                ui.horizontal(|ui| {
+                    ui.label("Scale: ");
+                    ui.add(
+                        egui::DragValue::new(&mut mask.scale)
+                            .speed(0.01)
+                            .clamp_range(0.01..=f32::MAX),
+                    );
+                });
+                Self::point_ui(ui, "Offset: ", &mut mask.offset);
+                ui.horizontal(|ui| {
+                    ui.label("Threshold: ");
+                    ui.add(egui::Slider::new(&mut mask.threshold, 0..=255));
+                });
+            }
+        }
+    }
+
+    /// Creates the layout for a single point of an obstacle. The coordinates cannot be negative.
+    fn point_ui(ui: &mut Ui, label: &str, point: &mut line_drawing::Point<i64>) {
+        ui.horizontal(|ui| {
+            ui.label(label);
+            ui.add(egui::DragValue::new(&mut point.0).speed(1.0));
+            ui.add(egui::DragValue::new(&mut point.1).speed(1.0));
+        });
+
+        if point.0 < 0 {
+            point.0 = 0;
+        }
+
+        if point.1 < 0 {
+            point.1 = 0;
+        }
+    }
+
+    /// Creates the layout for a single length of an obstacle, e.g. a radius. The length is at
+    /// least 1.
+    fn length_ui(ui: &mut Ui, label: &str, length: &mut i64) {
+        ui.horizontal(|ui| {
+            ui.label(label);
+            ui.add(egui::DragValue::new(length).speed(1.0));
+        });
+
+        if *length < 1 {
+            *length = 1;
         }
     }
 }
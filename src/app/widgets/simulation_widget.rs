@@ -1,8 +1,10 @@
-use crate::simulation::configs::SimulationConfigs;
+use crate::simulation::configs::{
+    AdvectionScheme, BoundaryCondition, GridLayout, InletProfile, PressureSolver,
+    SimulationConfigs, SolverStopCondition, TimeStepping,
+};
 use eframe::egui;
 
 /// Shows off one example of each major type of widget.
-#[cfg_attr(feature = "serde", derive(serde::Deserialize, serde::Serialize))]
 #[derive(Copy, Clone)]
 pub struct SimulationWidget {
     enabled: bool,
@@ -81,19 +83,332 @@ impl SimulationWidget {
         }
         ui.end_row();
 
-        ui.label("Simulation step (delta_t)")
-            .on_hover_text("delta_t");
+        ui.label("Simulation step (delta_t)").on_hover_text(
+            "delta_t. With the adaptive time step, it's the time between two frames",
+        );
         ui.add(egui::DragValue::new(&mut simulation_configs.delta_t).speed(0.01));
         if simulation_configs.delta_t < 0.0 {
             simulation_configs.delta_t = 0.00;
         }
         ui.end_row();
 
-        ui.label("Simulation window size");
-        ui.add(egui::DragValue::new(&mut simulation_configs.size).speed(1.0));
-        if simulation_configs.size < 1 {
-            simulation_configs.size = 1;
+        let mut is_adaptive = matches!(
+            simulation_configs.time_stepping,
+            TimeStepping::Adaptive { .. }
+        );
+        ui.label("Adaptive time step").on_hover_text(
+            "Split every frame into sub-steps, so the fluid doesn't move too far in any of them",
+        );
+        ui.checkbox(&mut is_adaptive, "");
+        ui.end_row();
+
+        simulation_configs.time_stepping = match (is_adaptive, simulation_configs.time_stepping) {
+            (
+                true,
+                TimeStepping::Adaptive {
+                    mut cfl,
+                    mut max_substeps,
+                },
+            ) => {
+                ui.label("CFL number").on_hover_text(
+                    "The largest number of cells the fluid may move through in a sub-step",
+                );
+                ui.add(egui::Slider::new(&mut cfl, 0.1..=5.0));
+                ui.end_row();
+
+                ui.label("Max sub-steps")
+                    .on_hover_text("The largest number of sub-steps in a frame");
+                ui.add(egui::DragValue::new(&mut max_substeps).speed(1.0));
+                max_substeps = max_substeps.max(1);
+                ui.end_row();
+                TimeStepping::Adaptive { cfl, max_substeps }
+            }
+            (true, TimeStepping::Fixed) => TimeStepping::Adaptive {
+                cfl: 1.0,
+                max_substeps: 16,
+            },
+            (false, _) => TimeStepping::Fixed,
+        };
+
+        ui.label("Simulation window width")
+            .on_hover_text("Number of cells in every row of the fluid's container");
+        ui.add(egui::DragValue::new(&mut simulation_configs.width).speed(1.0));
+        if simulation_configs.width < SimulationConfigs::MIN_SIZE {
+            simulation_configs.width = SimulationConfigs::MIN_SIZE;
+        }
+        ui.end_row();
+
+        ui.label("Simulation window height")
+            .on_hover_text("Number of cells in every column of the fluid's container");
+        ui.add(egui::DragValue::new(&mut simulation_configs.height).speed(1.0));
+        if simulation_configs.height < SimulationConfigs::MIN_SIZE {
+            simulation_configs.height = SimulationConfigs::MIN_SIZE;
+        }
+        ui.end_row();
+
+        ui.label("Domain length")
+            .on_hover_text("The length of the longer side of the container");
+        ui.add(
+            egui::DragValue::new(&mut simulation_configs.domain_length)
+                .speed(0.01)
+                .suffix(" m"),
+        );
+        simulation_configs.domain_length = simulation_configs.domain_length.max(0.001);
+        ui.end_row();
+
+        ui.label("Characteristic velocity")
+            .on_hover_text("The typical speed of the fluid, e.g. the speed of an inlet");
+        ui.add(
+            egui::DragValue::new(&mut simulation_configs.characteristic_velocity)
+                .speed(0.01)
+                .suffix(" m/s"),
+        );
+        simulation_configs.characteristic_velocity =
+            simulation_configs.characteristic_velocity.max(0.001);
+        ui.end_row();
+
+        ui.label("Cell size");
+        ui.label(format!("{:.3e} m", simulation_configs.cell_size()));
+        ui.end_row();
+
+        ui.label("Solver threads").on_hover_text(
+            "Number of threads the solver runs on. The results are the same for any number of threads.",
+        );
+        ui.add(egui::DragValue::new(&mut simulation_configs.threads).speed(0.1));
+        simulation_configs.threads = simulation_configs.threads.clamp(1, 64);
+        ui.end_row();
+
+        ui.label("Solver iterations").on_hover_text(
+            "Maximal number of Gauss-Seidel sweeps performed for the diffusion and the projection",
+        );
+        ui.add(egui::DragValue::new(&mut simulation_configs.solver_iterations).speed(1.0));
+        if simulation_configs.solver_iterations < 1 {
+            simulation_configs.solver_iterations = 1;
         }
         ui.end_row();
+
+        let mut stop_on_convergence = matches!(
+            simulation_configs.solver_stop_condition,
+            SolverStopCondition::Tolerance(_)
+        );
+        ui.label("Stop solver on convergence").on_hover_text(
+            "Stop iterating as soon as the largest residual drops below the given tolerance",
+        );
+        ui.checkbox(&mut stop_on_convergence, "");
+        ui.end_row();
+
+        simulation_configs.solver_stop_condition = match (
+            stop_on_convergence,
+            simulation_configs.solver_stop_condition,
+        ) {
+            (true, SolverStopCondition::Tolerance(mut tolerance)) => {
+                ui.label("Solver tolerance");
+                ui.add(egui::DragValue::new(&mut tolerance).speed(0.0001));
+                if tolerance < 0.0 {
+                    tolerance = 0.0;
+                }
+                ui.end_row();
+                SolverStopCondition::Tolerance(tolerance)
+            }
+            (true, SolverStopCondition::FixedIterations) => SolverStopCondition::Tolerance(0.0001),
+            (false, _) => SolverStopCondition::FixedIterations,
+        };
+
+        ui.label("Pressure solver").on_hover_text(
+            "All the solvers but Gauss-Seidel keep the fluid out of the obstacles. Multigrid needs the fewest iterations",
+        );
+        egui::ComboBox::from_id_source("pressure_solver")
+            .selected_text(Self::pressure_solver_name(
+                simulation_configs.pressure_solver,
+            ))
+            .show_ui(ui, |ui| {
+                for solver in [
+                    PressureSolver::GaussSeidel,
+                    PressureSolver::Jacobi,
+                    PressureSolver::RedBlackSor { omega: 1.8 },
+                    PressureSolver::ConjugateGradient,
+                    PressureSolver::Multigrid,
+                ] {
+                    let name = Self::pressure_solver_name(solver);
+                    if ui
+                        .selectable_label(
+                            Self::pressure_solver_name(simulation_configs.pressure_solver) == name,
+                            name,
+                        )
+                        .clicked()
+                        && Self::pressure_solver_name(simulation_configs.pressure_solver) != name
+                    {
+                        simulation_configs.pressure_solver = solver;
+                    }
+                }
+            });
+        ui.end_row();
+
+        if let PressureSolver::RedBlackSor { omega } = &mut simulation_configs.pressure_solver {
+            ui.label("Relaxation factor");
+            ui.add(egui::Slider::new(omega, 0.1..=1.99));
+            ui.end_row();
+        }
+
+        ui.label("Advection scheme").on_hover_text(
+            "The higher-order schemes keep the small details longer, but are slower",
+        );
+        egui::ComboBox::from_id_source("advection_scheme")
+            .selected_text(Self::advection_scheme_name(
+                simulation_configs.advection_scheme,
+            ))
+            .show_ui(ui, |ui| {
+                for scheme in [
+                    AdvectionScheme::SemiLagrangian,
+                    AdvectionScheme::MacCormack,
+                    AdvectionScheme::Bfecc,
+                    AdvectionScheme::MonotonicCubic,
+                ] {
+                    ui.selectable_value(
+                        &mut simulation_configs.advection_scheme,
+                        scheme,
+                        Self::advection_scheme_name(scheme),
+                    );
+                }
+            });
+        ui.end_row();
+
+        ui.label("Grid layout").on_hover_text(
+            "Staggered keeps the velocities on the faces of the cells, so the pressure has no checkerboard patterns",
+        );
+        egui::ComboBox::from_id_source("grid_layout")
+            .selected_text(Self::grid_layout_name(simulation_configs.grid_layout))
+            .show_ui(ui, |ui| {
+                for grid_layout in [GridLayout::Collocated, GridLayout::Staggered] {
+                    ui.selectable_value(
+                        &mut simulation_configs.grid_layout,
+                        grid_layout,
+                        Self::grid_layout_name(grid_layout),
+                    );
+                }
+            });
+        ui.end_row();
+
+        let boundaries = &mut simulation_configs.boundaries;
+        let previous_boundaries = *boundaries;
+        Self::boundary_ui(ui, "Left edge", &mut boundaries.left);
+        Self::boundary_ui(ui, "Right edge", &mut boundaries.right);
+        Self::boundary_ui(ui, "Top edge", &mut boundaries.top);
+        Self::boundary_ui(ui, "Bottom edge", &mut boundaries.bottom);
+
+        // Periodic edges come in pairs, so the opposite edge follows the changed one.
+        Self::pair_periodic_edges(
+            (&mut boundaries.left, previous_boundaries.left),
+            (&mut boundaries.right, previous_boundaries.right),
+        );
+        Self::pair_periodic_edges(
+            (&mut boundaries.top, previous_boundaries.top),
+            (&mut boundaries.bottom, previous_boundaries.bottom),
+        );
+    }
+
+    fn pressure_solver_name(pressure_solver: PressureSolver) -> &'static str {
+        match pressure_solver {
+            PressureSolver::GaussSeidel => "Gauss-Seidel",
+            PressureSolver::Jacobi => "Jacobi",
+            PressureSolver::RedBlackSor { .. } => "Red-black SOR",
+            PressureSolver::ConjugateGradient => "Conjugate gradient",
+            PressureSolver::Multigrid => "Multigrid",
+        }
+    }
+
+    fn advection_scheme_name(advection_scheme: AdvectionScheme) -> &'static str {
+        match advection_scheme {
+            AdvectionScheme::SemiLagrangian => "Semi-Lagrangian",
+            AdvectionScheme::MacCormack => "MacCormack",
+            AdvectionScheme::Bfecc => "BFECC",
+            AdvectionScheme::MonotonicCubic => "Monotonic cubic",
+        }
+    }
+
+    fn grid_layout_name(grid_layout: GridLayout) -> &'static str {
+        match grid_layout {
+            GridLayout::Collocated => "Collocated",
+            GridLayout::Staggered => "Staggered (MAC)",
+        }
+    }
+
+    /// Creates the rows for the boundary condition of a single edge of the container.
+    fn boundary_ui(ui: &mut egui::Ui, label: &str, condition: &mut BoundaryCondition) {
+        let name = |condition: &BoundaryCondition| match condition {
+            BoundaryCondition::Wall => "Wall",
+            BoundaryCondition::VelocityInlet { .. } => "Velocity inlet",
+            BoundaryCondition::Outflow => "Outflow",
+            BoundaryCondition::Periodic => "Periodic",
+        };
+
+        ui.label(label);
+        egui::ComboBox::from_id_source(label)
+            .selected_text(name(condition))
+            .show_ui(ui, |ui| {
+                for option in [
+                    BoundaryCondition::Wall,
+                    BoundaryCondition::VelocityInlet {
+                        speed: 1.0,
+                        profile: InletProfile::Uniform,
+                    },
+                    BoundaryCondition::Outflow,
+                    BoundaryCondition::Periodic,
+                ] {
+                    if ui
+                        .selectable_label(name(condition) == name(&option), name(&option))
+                        .clicked()
+                        && name(condition) != name(&option)
+                    {
+                        *condition = option;
+                    }
+                }
+            });
+        ui.end_row();
+
+        if let BoundaryCondition::VelocityInlet { speed, profile } = condition {
+            ui.label("    Inlet speed")
+                .on_hover_text("The speed with which the fluid enters the container");
+            ui.add(egui::DragValue::new(speed).speed(0.1));
+            if *speed < 0.0 {
+                *speed = 0.0;
+            }
+            ui.end_row();
+
+            ui.label("    Inlet profile");
+            ui.horizontal(|ui| {
+                ui.radio_value(profile, InletProfile::Uniform, "Uniform");
+                ui.radio_value(profile, InletProfile::Parabolic, "Parabolic");
+            });
+            ui.end_row();
+        }
+    }
+
+    /// Makes the edge opposite to the changed one periodic (or a wall if the changed edge is not
+    /// periodic anymore).
+    fn pair_periodic_edges(
+        first: (&mut BoundaryCondition, BoundaryCondition),
+        second: (&mut BoundaryCondition, BoundaryCondition),
+    ) {
+        let (first, previous_first) = first;
+        let (second, previous_second) = second;
+
+        if *first != previous_first {
+            Self::follow_periodic_edge(*first, previous_first, second);
+        } else if *second != previous_second {
+            Self::follow_periodic_edge(*second, previous_second, first);
+        }
+    }
+
+    fn follow_periodic_edge(
+        changed: BoundaryCondition,
+        previous: BoundaryCondition,
+        opposite: &mut BoundaryCondition,
+    ) {
+        if changed == BoundaryCondition::Periodic {
+            *opposite = BoundaryCondition::Periodic;
+        } else if previous == BoundaryCondition::Periodic {
+            *opposite = BoundaryCondition::Wall;
+        }
     }
 }
@@ -1,23 +1,22 @@
+use crate::simulation::configs::{
+    AnimationConfigs, AnimationFormat, Colormap, OutputFormat, ValueRange, ViewportConfigs,
+    VisualizedField,
+};
 use eframe::egui;
 
 /// Shows off one example of each major type of widget.
-#[cfg_attr(feature = "serde", derive(serde::Deserialize, serde::Serialize))]
 #[derive(Clone)]
 pub struct ViewportWidget {
     enabled: bool,
-    /// Configurations for the size of the simulation image as regards the size of the central panel
-    /// *Note:* in percents
-    pub image_resize_factor: u8,
-    /// The name of the directory in which the rendered images will be saved.
-    pub save_into_dir: String,
+    /// The viewport configurations
+    pub viewport_configs: ViewportConfigs,
 }
 
 impl Default for ViewportWidget {
     fn default() -> Self {
         Self {
             enabled: true,
-            image_resize_factor: 50,
-            save_into_dir: "rendered_images".to_string(),
+            viewport_configs: ViewportConfigs::default(),
         }
     }
 }
@@ -74,24 +73,218 @@ impl ViewportWidget {
         let Self {
             // Field not used, ignore it. Item placed for completeness.
             enabled: _,
+            viewport_configs,
+        } = self;
+        let ViewportConfigs {
             image_resize_factor,
             save_into_dir,
-        } = self;
+            save_images,
+            output_format,
+            output_fields,
+            animation,
+            checkpoint_interval,
+            visualization,
+        } = viewport_configs;
 
         ui.label("Rendered image resize factor")
         .on_hover_text("Configurations for the size of the simulation image as regards the size of the central panel. *Note:* in percents");
         ui.add(egui::DragValue::new(image_resize_factor).speed(1.0));
-        if *image_resize_factor > 100 {
-            *image_resize_factor = 100;
-        } else if *image_resize_factor <= 1 {
-            *image_resize_factor = 1;
-        }
+        *image_resize_factor = (*image_resize_factor).clamp(1, 100);
+        ui.end_row();
+
+        ui.label("Shown field").on_hover_text(
+            "The field of the fluid shown in the rendered frames. The colors show the density and the dyes.",
+        );
+        egui::ComboBox::from_id_source("visualized_field")
+            .selected_text(Self::visualized_field_name(visualization.field))
+            .show_ui(ui, |ui| {
+                for field in [
+                    VisualizedField::Colors,
+                    VisualizedField::Density,
+                    VisualizedField::VelocityMagnitude,
+                    VisualizedField::Vorticity,
+                    VisualizedField::Pressure,
+                    VisualizedField::Divergence,
+                ] {
+                    ui.selectable_value(
+                        &mut visualization.field,
+                        field,
+                        Self::visualized_field_name(field),
+                    );
+                }
+            });
+        ui.end_row();
+
+        let shows_colormap = visualization.field != VisualizedField::Colors;
+        ui.label("Colormap")
+            .on_hover_text("The colors onto which the values of the shown field are mapped");
+        ui.add_enabled_ui(shows_colormap, |ui| {
+            egui::ComboBox::from_id_source("colormap")
+                .selected_text(Self::colormap_name(visualization.colormap))
+                .show_ui(ui, |ui| {
+                    for colormap in [Colormap::Viridis, Colormap::Inferno, Colormap::Coolwarm] {
+                        ui.selectable_value(
+                            &mut visualization.colormap,
+                            colormap,
+                            Self::colormap_name(colormap),
+                        );
+                    }
+                });
+        });
+        ui.end_row();
+
+        let mut is_auto_range = visualization.range == ValueRange::Auto;
+        ui.label("Automatic value range").on_hover_text(
+            "Map the values of the shown field in every frame onto the whole colormap. The vorticity and the divergence are kept symmetric around zero.",
+        );
+        ui.add_enabled(shows_colormap, egui::Checkbox::new(&mut is_auto_range, ""));
+        ui.end_row();
+
+        visualization.range = match (is_auto_range, visualization.range) {
+            (false, ValueRange::Fixed { mut min, mut max }) => {
+                ui.label("Value range")
+                    .on_hover_text("The values shown with the ends of the colormap");
+                ui.add_enabled_ui(shows_colormap, |ui| {
+                    ui.horizontal(|ui| {
+                        ui.add(egui::DragValue::new(&mut min).speed(0.01));
+                        ui.add(egui::DragValue::new(&mut max).speed(0.01));
+                    });
+                });
+                max = max.max(min);
+                ui.end_row();
+                ValueRange::Fixed { min, max }
+            }
+            (false, ValueRange::Auto) => ValueRange::Fixed { min: 0.0, max: 1.0 },
+            (true, _) => ValueRange::Auto,
+        };
+
+        ui.label("Save images").on_hover_text(
+            "Save every rendered frame on the disk. The frames are always shown from the memory.",
+        );
+        ui.checkbox(save_images, "");
         ui.end_row();
 
         ui.label("Save into directory:")
             .on_hover_text("The name of the directory in which the rendered images will be saved.");
-        ui.text_edit_singleline(save_into_dir);
+        ui.add_enabled(*save_images, egui::TextEdit::singleline(save_into_dir));
+
+        ui.end_row();
 
+        ui.label("Output format").on_hover_text(
+            "PNG and JPEG keep the colors of the simulation, while the rest of the formats keep the values of the fields",
+        );
+        ui.add_enabled_ui(*save_images, |ui| {
+            egui::ComboBox::from_id_source("output_format")
+                .selected_text(Self::output_format_name(*output_format))
+                .show_ui(ui, |ui| {
+                    for format in [
+                        OutputFormat::Png,
+                        OutputFormat::Jpeg,
+                        OutputFormat::Gray16Png,
+                        OutputFormat::Npy,
+                        OutputFormat::Csv,
+                    ] {
+                        ui.selectable_value(
+                            output_format,
+                            format,
+                            Self::output_format_name(format),
+                        );
+                    }
+                });
+        });
+        ui.end_row();
+
+        ui.label("Saved fields")
+            .on_hover_text("Every field is saved into a separate file for every frame");
+        ui.add_enabled_ui(*save_images, |ui| {
+            ui.vertical(|ui| {
+                ui.checkbox(&mut output_fields.density, "Density");
+                ui.checkbox(&mut output_fields.velocity_x, "Velocity x");
+                ui.checkbox(&mut output_fields.velocity_y, "Velocity y");
+                ui.checkbox(&mut output_fields.cells_mask, "Cells mask");
+            });
+        });
         ui.end_row();
+
+        ui.label("Checkpoint every N frames").on_hover_text(
+            "Save the state of the fluid into the directory every that many frames, so a new simulation could start from it. 0 disables the checkpoints.",
+        );
+        ui.add(egui::DragValue::new(checkpoint_interval).speed(1.0));
+        ui.end_row();
+
+        ui.label("Animation format")
+            .on_hover_text("The format of the animation made by the *Export animation* button");
+        egui::ComboBox::from_id_source("animation_format")
+            .selected_text(Self::animation_format_name(animation.format))
+            .show_ui(ui, |ui| {
+                for format in [AnimationFormat::Gif, AnimationFormat::Apng] {
+                    ui.selectable_value(
+                        &mut animation.format,
+                        format,
+                        Self::animation_format_name(format),
+                    );
+                }
+            });
+        ui.end_row();
+
+        ui.label("Animation frame rate")
+            .on_hover_text("Number of frames shown per second");
+        ui.add(egui::DragValue::new(&mut animation.frame_rate).speed(1.0));
+        animation.frame_rate = animation.frame_rate.clamp(
+            *AnimationConfigs::FRAME_RATES.start(),
+            *AnimationConfigs::FRAME_RATES.end(),
+        );
+        ui.end_row();
+
+        ui.label("Loop animation forever");
+        let mut loops_forever = animation.plays == 0;
+        ui.checkbox(&mut loops_forever, "");
+        ui.end_row();
+
+        if loops_forever {
+            animation.plays = 0;
+        } else {
+            ui.label("Animation plays")
+                .on_hover_text("How many times the animation is played");
+            ui.add(egui::DragValue::new(&mut animation.plays).speed(1.0));
+            animation.plays = animation.plays.max(1);
+            ui.end_row();
+        }
+    }
+
+    fn output_format_name(output_format: OutputFormat) -> &'static str {
+        match output_format {
+            OutputFormat::Png => "PNG",
+            OutputFormat::Jpeg => "JPEG",
+            OutputFormat::Gray16Png => "16-bit grayscale PNG",
+            OutputFormat::Npy => "NumPy array (.npy)",
+            OutputFormat::Csv => "CSV",
+        }
+    }
+
+    fn visualized_field_name(field: VisualizedField) -> &'static str {
+        match field {
+            VisualizedField::Colors => "Colors",
+            VisualizedField::Density => "Density",
+            VisualizedField::VelocityMagnitude => "Velocity magnitude",
+            VisualizedField::Vorticity => "Vorticity",
+            VisualizedField::Pressure => "Pressure",
+            VisualizedField::Divergence => "Divergence",
+        }
+    }
+
+    fn colormap_name(colormap: Colormap) -> &'static str {
+        match colormap {
+            Colormap::Viridis => "Viridis",
+            Colormap::Inferno => "Inferno",
+            Colormap::Coolwarm => "Coolwarm",
+        }
+    }
+
+    fn animation_format_name(animation_format: AnimationFormat) -> &'static str {
+        match animation_format {
+            AnimationFormat::Gif => "GIF",
+            AnimationFormat::Apng => "APNG",
+        }
     }
 }
@@ -1,8 +1,12 @@
+use super::dye_widget::DyeWidget;
+use super::emitter_widget::EmitterWidget;
 use super::fluid_widget::FluidWidget;
 use super::obstacle_widget::ObstacleWidget;
 use super::simulation_widget::SimulationWidget;
 use super::viewport_widget::ViewportWidget;
 use super::Setting;
+use crate::simulation::emitter::Emitter;
+use crate::simulation::scenario::Scenario;
 use eframe::egui;
 use std::collections::BTreeSet;
 
@@ -17,6 +21,10 @@ pub enum SettingType {
     Viewport(ViewportWidget),
     /// Used for describing the [`ObstacleWidget`] type
     Obstacle(ObstacleWidget),
+    /// Used for describing the [`EmitterWidget`] type
+    Emitter(EmitterWidget),
+    /// Used for describing the [`DyeWidget`] type
+    Dye(DyeWidget),
 }
 
 impl Setting for SettingType {
@@ -26,6 +34,8 @@ impl Setting for SettingType {
             SettingType::Simulation(simulation_widget) => simulation_widget.name(),
             SettingType::Viewport(viewport_widget) => viewport_widget.name(),
             SettingType::Obstacle(obstacle_widget) => obstacle_widget.name(),
+            SettingType::Emitter(emitter_widget) => emitter_widget.name(),
+            SettingType::Dye(dye_widget) => dye_widget.name(),
         }
     }
 
@@ -35,6 +45,8 @@ impl Setting for SettingType {
             SettingType::Simulation(simulation_widget) => simulation_widget.show(ctx, open),
             SettingType::Viewport(viewport_widget) => viewport_widget.show(ctx, open),
             SettingType::Obstacle(obstacle_widget) => obstacle_widget.show(ctx, open),
+            SettingType::Emitter(emitter_widget) => emitter_widget.show(ctx, open),
+            SettingType::Dye(dye_widget) => dye_widget.show(ctx, open),
         };
     }
 }
@@ -49,16 +61,15 @@ impl SettingType {
             }
             SettingType::Viewport(_) => *self = SettingType::Viewport(ViewportWidget::default()),
             SettingType::Obstacle(_) => *self = SettingType::Obstacle(ObstacleWidget::default()),
+            SettingType::Emitter(_) => *self = SettingType::Emitter(EmitterWidget::default()),
+            SettingType::Dye(_) => *self = SettingType::Dye(DyeWidget::default()),
         }
     }
 }
 
 /// Main window with settings from which the other menus can be pulled out via checkboxes
-#[cfg_attr(feature = "serde", derive(serde::Deserialize, serde::Serialize))]
-#[cfg_attr(feature = "serde", serde(default))]
 pub struct SettingsMenu {
     /// a collection of all settings widgets with checkboxes
-    #[cfg_attr(feature = "serde", serde(skip))]
     pub settings_menu: Vec<SettingType>,
 
     open: BTreeSet<String>,
@@ -72,6 +83,8 @@ impl Default for SettingsMenu {
                 SettingType::Fluid(super::fluid_widget::FluidWidget::default()),
                 SettingType::Viewport(super::viewport_widget::ViewportWidget::default()),
                 SettingType::Obstacle(super::obstacle_widget::ObstacleWidget::default()),
+                SettingType::Emitter(super::emitter_widget::EmitterWidget::default()),
+                SettingType::Dye(super::dye_widget::DyeWidget::default()),
             ],
             true,
         )
@@ -87,7 +100,7 @@ impl SettingsMenu {
     ///
     /// * `settings_menu` - collection of all the menus
     /// * `should_open_first` - the first setting can be automatically opened every
-    /// time the application is started. This parameter defines if this should be the case.
+    ///   time the application is started. This parameter defines if this should be the case.
     ///
     /// <a href="https://imgbb.com/"><img src="https://i.ibb.co/0qNLXV0/checkbox-menus.png" alt="checkbox-menus" border="0"></a><br />
     pub fn from_settings(settings_menu: Vec<SettingType>, should_open_first: bool) -> Self {
@@ -121,6 +134,8 @@ impl SettingsMenu {
 
     /// Open a window
     pub fn windows(&mut self, ctx: &egui::CtxRef) {
+        self.sync_simulation_configs();
+
         let Self {
             settings_menu,
             open,
@@ -132,6 +147,100 @@ impl SettingsMenu {
         }
     }
 
+    /// Returns the indices of the dyes removed in the [`DyeWidget`] since the last call, in the
+    /// order of their removal. The emitters of the [`EmitterWidget`] follow the removals, see
+    /// [`Emitter::remove_dye()`].
+    pub fn take_removed_dyes(&mut self) -> Vec<usize> {
+        let removed_dyes: Vec<usize> = self
+            .settings_menu
+            .iter_mut()
+            .flat_map(|setting| match setting {
+                SettingType::Dye(dye_widget) => dye_widget.take_removed_dyes(),
+                _ => Vec::new(),
+            })
+            .collect();
+
+        for setting in self.settings_menu.iter_mut() {
+            if let SettingType::Emitter(emitter_widget) = setting {
+                for removed_dye in removed_dyes.iter() {
+                    Emitter::remove_dye(&mut emitter_widget.emitters, *removed_dye);
+                }
+            }
+        }
+        removed_dyes
+    }
+
+    /// Copies the configurations of the [`SimulationWidget`] into the [`FluidWidget`], which
+    /// derives the dimensionless numbers of the fluid from them.
+    fn sync_simulation_configs(&mut self) {
+        let simulation_configs = self.settings_menu.iter().find_map(|setting| match setting {
+            SettingType::Simulation(simulation_widget) => {
+                Some(simulation_widget.simulation_configs)
+            }
+            _ => None,
+        });
+
+        if let Some(simulation_configs) = simulation_configs {
+            for setting in self.settings_menu.iter_mut() {
+                if let SettingType::Fluid(fluid_widget) = setting {
+                    fluid_widget.simulation_configs = simulation_configs;
+                }
+            }
+        }
+    }
+
+    /// Collects the configurations from all the widgets into a [`Scenario`].
+    pub fn to_scenario(&self) -> Scenario {
+        let mut result = Scenario::default();
+        for setting in self.settings_menu.iter() {
+            match setting {
+                SettingType::Fluid(fluid_widget) => result.fluid = fluid_widget.fluid_configs,
+                SettingType::Simulation(simulation_widget) => {
+                    result.simulation = simulation_widget.simulation_configs
+                }
+                SettingType::Viewport(viewport_widget) => {
+                    result.viewport = viewport_widget.viewport_configs.clone()
+                }
+                SettingType::Obstacle(obstacle_widget) => {
+                    result.obstacles = obstacle_widget
+                        .obstacles
+                        .iter()
+                        .map(|el| el.obstacle.clone())
+                        .collect();
+                    result.obstacles_color = obstacle_widget.color;
+                }
+                SettingType::Emitter(emitter_widget) => {
+                    result.emitters = emitter_widget.emitters.clone();
+                }
+                SettingType::Dye(dye_widget) => result.dyes = dye_widget.dyes.clone(),
+            }
+        }
+        result
+    }
+
+    /// Sets the configurations of all the widgets to the ones from the given [`Scenario`].
+    pub fn apply_scenario(&mut self, scenario: &Scenario) {
+        for setting in self.settings_menu.iter_mut() {
+            match setting {
+                SettingType::Fluid(fluid_widget) => fluid_widget.fluid_configs = scenario.fluid,
+                SettingType::Simulation(simulation_widget) => {
+                    simulation_widget.simulation_configs = scenario.simulation
+                }
+                SettingType::Viewport(viewport_widget) => {
+                    viewport_widget.viewport_configs = scenario.viewport.clone()
+                }
+                SettingType::Obstacle(obstacle_widget) => {
+                    obstacle_widget.set_obstacles(&scenario.obstacles);
+                    obstacle_widget.color = scenario.obstacles_color;
+                }
+                SettingType::Emitter(emitter_widget) => {
+                    emitter_widget.emitters = scenario.emitters.clone();
+                }
+                SettingType::Dye(dye_widget) => dye_widget.dyes = scenario.dyes.clone(),
+            }
+        }
+    }
+
     /// Close all open windows.
     pub fn close_all(&mut self) {
         self.open.clear();
@@ -152,6 +261,7 @@ fn set_open(open: &mut BTreeSet<String>, key: &'static str, is_open: bool) {
 mod tests {
     use crate::app::widgets::widgets_menu::FluidWidget;
     use crate::app::widgets::widgets_menu::SettingType;
+    use crate::app::widgets::widgets_menu::SettingsMenu;
     use crate::app::widgets::Setting;
 
     #[test]
@@ -160,4 +270,21 @@ mod tests {
         let fluid_setting_type = SettingType::Fluid(fluid_widget);
         assert_eq!(fluid_setting_type.name(), fluid_widget.name());
     }
+
+    #[test]
+    fn fluid_widget_follows_the_simulation_configs() {
+        let mut settings_menu = SettingsMenu::default();
+        for setting in settings_menu.settings_menu.iter_mut() {
+            if let SettingType::Simulation(simulation_widget) = setting {
+                simulation_widget.simulation_configs.domain_length = 4.0;
+            }
+        }
+
+        settings_menu.sync_simulation_configs();
+
+        assert!(settings_menu.settings_menu.iter().any(|setting| matches!(
+            setting,
+            SettingType::Fluid(fluid_widget) if fluid_widget.simulation_configs.domain_length == 4.0
+        )));
+    }
 }
@@ -0,0 +1,259 @@
+use equilibrium::simulation::checkpoint::Checkpoint;
+use equilibrium::simulation::renderer::Renderer;
+use equilibrium::simulation::scenario::Scenario;
+use simplelog::{ColorChoice, Config, LevelFilter, TermLogger, TerminalMode};
+
+const USAGE: &str = "Runs the fluid simulation without the GUI and saves the rendered frames.
+
+USAGE:
+    equilibrium-headless [OPTIONS]
+
+OPTIONS:
+    -c, --config <FILE>              Scenario file with the configurations of the run
+    -o, --save-into-dir <DIR>        Directory in which the rendered images are saved
+    -f, --frames <N>                 Number of frames to be simulated
+    -s, --size <N>                   The width and the height of a square fluid container
+        --width <N>                  The number of cells in every row of the fluid container
+        --height <N>                 The number of cells in every column of the fluid container
+    -t, --delta-t <F>                The size of each step
+    -i, --solver-iterations <N>      Maximal number of sweeps of the linear solver
+    -j, --threads <N>                Number of threads the solver runs on
+        --checkpoint-every <N>       Save a checkpoint of the fluid every N frames
+    -r, --from-checkpoint <FILE>     Start from the state of the fluid in the checkpoint file
+        --no-noise                   Disable the random perlin noise
+    -h, --help                       Print this message
+
+The command line options override the values from the scenario file.";
+
+/// Builds the [`Scenario`] to be simulated from the command line arguments. The scenario file (if
+/// any) is read first, and then overridden by the rest of the options. Returns `None` if the help
+/// message has been requested.
+fn scenario_from_args(args: &[String]) -> Result<Option<Scenario>, String> {
+    let mut result = match option_value(args, "-c", "--config") {
+        Some(path) => {
+            Scenario::open(path).map_err(|e| format!("Couldn't open scenario {}: {}", path, e))?
+        }
+        // The demo obstacle of the GUI is left out, so it doesn't get in the way of small or
+        // rectangular containers
+        None => Scenario {
+            obstacles: Vec::new(),
+            ..Scenario::default()
+        },
+    };
+
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        let mut value = || {
+            args.next()
+                .ok_or_else(|| format!("Missing value for {}", arg))
+        };
+
+        match arg.as_str() {
+            "-h" | "--help" => return Ok(None),
+            "-c" | "--config" | "-r" | "--from-checkpoint" => {
+                value()?;
+            }
+            "-o" | "--save-into-dir" => result.viewport.save_into_dir = value()?.clone(),
+            "-f" | "--frames" => result.simulation.frames = parse_value(arg, value()?)?,
+            "-s" | "--size" => {
+                let size = parse_value(arg, value()?)?;
+                result.simulation.width = size;
+                result.simulation.height = size;
+            }
+            "--width" => result.simulation.width = parse_value(arg, value()?)?,
+            "--height" => result.simulation.height = parse_value(arg, value()?)?,
+            "-t" | "--delta-t" => result.simulation.delta_t = parse_value(arg, value()?)?,
+            "-i" | "--solver-iterations" => {
+                result.simulation.solver_iterations = parse_value(arg, value()?)?
+            }
+            "-j" | "--threads" => result.simulation.threads = parse_value(arg, value()?)?,
+            "--checkpoint-every" => {
+                result.viewport.checkpoint_interval = parse_value(arg, value()?)?
+            }
+            "--no-noise" => result.fluid.has_perlin_noise = false,
+            _ => return Err(format!("Unknown option {}\n\n{}", arg, USAGE)),
+        }
+    }
+
+    if result.simulation.frames < 1 {
+        return Err("The number of frames must be positive".to_string());
+    }
+    if result.simulation.threads < 1 {
+        return Err("The number of threads must be positive".to_string());
+    }
+    result.validate().map_err(|e| e.to_string())?;
+
+    Ok(Some(result))
+}
+
+/// Returns the value that follows the given option, if the option is present.
+fn option_value<'a>(args: &'a [String], short: &str, long: &str) -> Option<&'a String> {
+    args.iter()
+        .position(|arg| arg == short || arg == long)
+        .and_then(|position| args.get(position + 1))
+}
+
+fn parse_value<T: std::str::FromStr>(option: &str, value: &str) -> Result<T, String> {
+    value
+        .parse()
+        .map_err(|_| format!("Invalid value for {}: {}", option, value))
+}
+
+/// Opens the checkpoint given by the command line arguments, if any.
+fn checkpoint_from_args(args: &[String]) -> Result<Option<Checkpoint>, String> {
+    option_value(args, "-r", "--from-checkpoint")
+        .map(|path| {
+            Checkpoint::open(path).map_err(|e| format!("Couldn't open checkpoint {}: {}", path, e))
+        })
+        .transpose()
+}
+
+/// Runs the whole simulation and prints the progress to stdout. If a checkpoint is given, the
+/// simulation starts from it.
+fn run(scenario: Scenario, checkpoint: Option<Checkpoint>) -> Result<(), String> {
+    let frames = scenario.simulation.frames;
+    let mut renderer = Renderer::from_scenario(&scenario);
+    renderer.start_from_checkpoint(checkpoint);
+
+    let (rendering_rx, joinhandles) = renderer
+        .render()
+        .map_err(|e| format!("Couldn't start the simulation: {}", e))?;
+
+    let mut rendered_frames = 0;
+    for rendered_frame in rendering_rx.iter() {
+        rendered_frames += 1;
+        println!(
+            "Rendered frame {}/{} in {} sub-steps (pressure residual {:e} after {} iterations)",
+            rendered_frame.frame_number + 1,
+            frames,
+            rendered_frame.substeps,
+            rendered_frame.pressure_solve.residual,
+            rendered_frame.pressure_solve.iterations
+        );
+    }
+
+    for joinhandle in joinhandles {
+        match joinhandle.join() {
+            Ok(Ok(())) => {}
+            Ok(Err(error)) => return Err(format!("Couldn't save the results: {}", error)),
+            Err(_) => return Err("The simulation has been interrupted".to_string()),
+        }
+    }
+
+    if rendered_frames != frames {
+        return Err(format!(
+            "Only {} out of {} frames have been rendered",
+            rendered_frames, frames
+        ));
+    }
+
+    println!(
+        "Done! The results are saved into {}",
+        renderer.rendering_listener.save_into_dir
+    );
+    Ok(())
+}
+
+fn main() {
+    TermLogger::init(
+        LevelFilter::Error,
+        Config::default(),
+        TerminalMode::Stderr,
+        ColorChoice::Auto,
+    )
+    .expect("Logger failed to load");
+
+    let args: Vec<String> = std::env::args().skip(1).collect();
+
+    let result = match scenario_from_args(&args) {
+        Ok(Some(scenario)) => {
+            checkpoint_from_args(&args).and_then(|checkpoint| run(scenario, checkpoint))
+        }
+        Ok(None) => {
+            println!("{}", USAGE);
+            Ok(())
+        }
+        Err(message) => Err(message),
+    };
+
+    if let Err(message) = result {
+        eprintln!("equilibrium-headless: {}", message);
+        std::process::exit(1);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{checkpoint_from_args, run, scenario_from_args};
+    use equilibrium::simulation::checkpoint::Checkpoint;
+    use equilibrium::simulation::configs::{FluidConfigs, GridLayout};
+    use equilibrium::simulation::fluid::Fluid;
+
+    fn to_args(args: &[&str]) -> Vec<String> {
+        args.iter().map(|arg| arg.to_string()).collect()
+    }
+
+    #[test]
+    fn from_args_overrides_defaults() {
+        let scenario = scenario_from_args(&to_args(&["--frames", "42", "-s", "128", "--no-noise"]))
+            .unwrap()
+            .unwrap();
+
+        assert_eq!(scenario.simulation.frames, 42);
+        assert_eq!(scenario.simulation.width, 128);
+        assert_eq!(scenario.simulation.height, 128);
+        assert!(!scenario.fluid.has_perlin_noise);
+    }
+
+    #[test]
+    fn from_args_sets_rectangular_container() {
+        let args = to_args(&["-s", "256", "--width", "512"]);
+        let scenario = scenario_from_args(&args).unwrap().unwrap();
+
+        assert_eq!(scenario.simulation.width, 512);
+        assert_eq!(scenario.simulation.height, 256);
+        assert!(scenario_from_args(&to_args(&["--height", "2"])).is_err());
+    }
+
+    #[test]
+    fn from_args_rejects_invalid_values() {
+        assert!(scenario_from_args(&to_args(&["--frames", "many"])).is_err());
+        assert!(scenario_from_args(&to_args(&["--frames"])).is_err());
+        assert!(scenario_from_args(&to_args(&["--unknown"])).is_err());
+        assert!(scenario_from_args(&to_args(&["--threads", "0"])).is_err());
+    }
+
+    #[test]
+    fn default_scenario_has_no_obstacles() {
+        let scenario = scenario_from_args(&to_args(&["--size", "64"]))
+            .unwrap()
+            .unwrap();
+
+        assert!(scenario.obstacles.is_empty());
+    }
+
+    #[test]
+    fn from_args_sets_checkpoints() {
+        let args = to_args(&["--checkpoint-every", "10", "-r", "missing.eqcp"]);
+        let scenario = scenario_from_args(&args).unwrap().unwrap();
+
+        assert_eq!(scenario.viewport.checkpoint_interval, 10);
+        assert!(checkpoint_from_args(&args).is_err());
+        assert!(checkpoint_from_args(&to_args(&[])).unwrap().is_none());
+    }
+
+    #[test]
+    fn run_rejects_incompatible_checkpoint() {
+        let scenario = scenario_from_args(&to_args(&["--size", "16", "--frames", "1"]))
+            .unwrap()
+            .unwrap();
+        let mut simulation_configs = scenario.simulation;
+        simulation_configs.grid_layout = GridLayout::Staggered;
+        let checkpoint = Checkpoint {
+            frame_number: 0,
+            fluid: Fluid::new(FluidConfigs::default(), simulation_configs),
+        };
+
+        assert!(run(scenario, Some(checkpoint)).is_err());
+    }
+}
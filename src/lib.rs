@@ -12,13 +12,14 @@
 //! <a href="https://ibb.co/CtNC38P"><img src="https://i.ibb.co/H21wf4h/home-screen.png" alt="home-screen" border="0" width=448 height=359 class="center"></a>
 #![deny(missing_docs)]
 #![warn(rust_2018_idioms)]
-#[macro_use]
 
 /// The application's space. The app itsels is built upon the [`emilk`](github.com/emilk)'s
 /// [`eframe template`](https://github.com/emilk/eframe_template) for
 /// [`egui`](github.com/emilk/egui). It is designed for easier interaction with
 /// the simulation. From here you can adjust the parameters, visualize and control the
 /// simulation.
+#[macro_use]
+#[cfg(feature = "gui")]
 pub mod app;
 
 /// Space for all the structures that concern the fluid's behaviour during the simulation, i.e.
@@ -0,0 +1,388 @@
+use crate::simulation::configs::{DyeConfigs, FluidConfigs, SimulationConfigs};
+use crate::simulation::fluid::{ContainerWall, Fluid};
+use serde::{Deserialize, Serialize};
+use std::io::{Read, Write};
+
+/// The version of the checkpoint format written by the current build. Checkpoints with other
+/// versions are rejected.
+pub const CHECKPOINT_VERSION: u32 = 4;
+
+/// The first bytes of every checkpoint file
+const MAGIC: &[u8; 4] = b"EQCP";
+
+/// The maximum length of the configurations in the checkpoint's header, so a corrupt length
+/// doesn't make [`Checkpoint::read()`] allocate a huge buffer
+const MAX_CONFIGS_LENGTH: u32 = 1 << 20;
+
+/// The extension of the checkpoint files
+pub const CHECKPOINT_EXTENSION: &str = "eqcp";
+
+/// The configurations of the fluid stored in the checkpoint's header as a TOML document
+#[derive(Serialize, Deserialize)]
+struct CheckpointConfigs {
+    fluid: FluidConfigs,
+    simulation: SimulationConfigs,
+    #[serde(default)]
+    dyes: Vec<DyeConfigs>,
+}
+
+/// The state of the fluid after a given frame of the simulation. A new simulation can be started
+/// from it, see [`Checkpoint::restore_into()`].
+///
+/// The checkpoint is stored in a binary format. All the numbers are little-endian:
+/// - the magic bytes `EQCP`;
+/// - the version of the format (`u32`), see [`CHECKPOINT_VERSION`];
+/// - the number of the frame (`i64`);
+/// - the width and the height of the fluid's container (`u32` each);
+/// - the length (`u32`) and the content of a TOML document with the fluid, simulation and dyes
+///   configurations;
+/// - the density, the x and the y velocities and the temperature of every cell (`f32`), row by
+///   row;
+/// - the density of every dye in every cell (`f32`), dye by dye and row by row;
+/// - `1` for every wall cell and `0` for every cell accessible for the fluid (`u8`), row by row.
+#[derive(Clone)]
+pub struct Checkpoint {
+    /// The number of the last simulated frame
+    pub frame_number: i64,
+    /// The state of the fluid after the frame
+    pub fluid: Fluid,
+}
+
+impl Checkpoint {
+    /// The path of the checkpoint of the given frame in the given directory, e.g.
+    /// `rendered_images/checkpoint42.eqcp`.
+    pub fn path(dir: &str, frame_number: i64) -> String {
+        format!(
+            "{}/checkpoint{}.{}",
+            dir, frame_number, CHECKPOINT_EXTENSION
+        )
+    }
+
+    /// Writes the checkpoint in the binary format described in [`Checkpoint`].
+    pub fn write(&self, writer: &mut impl Write) -> std::io::Result<()> {
+        // See `Scenario::to_toml()` for the detour through a `toml::Value`
+        let configs = toml::Value::try_from(CheckpointConfigs {
+            fluid: self.fluid.fluid_configs,
+            simulation: self.fluid.simulation_configs,
+            dyes: self.fluid.dyes.iter().map(|dye| dye.configs).collect(),
+        })
+        .and_then(|value| toml::to_string(&value))
+        .map_err(|e| std::io::Error::new(std::io::ErrorKind::Other, e))?;
+
+        writer.write_all(MAGIC)?;
+        writer.write_all(&CHECKPOINT_VERSION.to_le_bytes())?;
+        writer.write_all(&self.frame_number.to_le_bytes())?;
+        writer.write_all(&self.fluid.simulation_configs.width.to_le_bytes())?;
+        writer.write_all(&self.fluid.simulation_configs.height.to_le_bytes())?;
+        writer.write_all(&(configs.len() as u32).to_le_bytes())?;
+        writer.write_all(configs.as_bytes())?;
+
+        let fields = [
+            &self.fluid.density,
+            &self.fluid.velocities_x,
+            &self.fluid.velocities_y,
+            &self.fluid.temperature,
+        ];
+        let dyes = self.fluid.dyes.iter().map(|dye| &dye.density);
+        for field in fields.into_iter().chain(dyes) {
+            for value in field.iter() {
+                writer.write_all(&value.to_le_bytes())?;
+            }
+        }
+
+        let cells: Vec<u8> = self
+            .fluid
+            .cells_type
+            .iter()
+            .map(|cell_type| u8::from(*cell_type == ContainerWall::DefaultWall))
+            .collect();
+        writer.write_all(&cells)
+    }
+
+    /// Reads a checkpoint written by [`Checkpoint::write()`].
+    pub fn read(reader: &mut impl Read) -> std::io::Result<Self> {
+        let invalid_data =
+            |message: String| std::io::Error::new(std::io::ErrorKind::InvalidData, message);
+
+        let mut magic = [0; 4];
+        reader.read_exact(&mut magic)?;
+        if &magic != MAGIC {
+            return Err(invalid_data("not a checkpoint file".to_string()));
+        }
+
+        let version = read_u32(reader)?;
+        if version != CHECKPOINT_VERSION {
+            return Err(invalid_data(format!(
+                "checkpoint version {} is not supported, expected version {}",
+                version, CHECKPOINT_VERSION
+            )));
+        }
+
+        let mut frame_number = [0; 8];
+        reader.read_exact(&mut frame_number)?;
+        let frame_number = i64::from_le_bytes(frame_number);
+        let width = read_u32(reader)?;
+        let height = read_u32(reader)?;
+
+        let configs_length = read_u32(reader)?;
+        if configs_length > MAX_CONFIGS_LENGTH {
+            return Err(invalid_data(format!(
+                "the configurations are {} bytes long, at most {} bytes are supported",
+                configs_length, MAX_CONFIGS_LENGTH
+            )));
+        }
+        let mut configs = vec![0; configs_length as usize];
+        reader.read_exact(&mut configs)?;
+        let configs: CheckpointConfigs =
+            toml::from_str(std::str::from_utf8(&configs).map_err(|e| invalid_data(e.to_string()))?)
+                .map_err(|e| invalid_data(format!("invalid configurations: {}", e)))?;
+
+        if (configs.simulation.width, configs.simulation.height) != (width, height) {
+            return Err(invalid_data(format!(
+                "the fluid's container is {}x{}, but the configurations say {}x{}",
+                width, height, configs.simulation.width, configs.simulation.height
+            )));
+        }
+
+        // The cells are read before the fluid is created, so a corrupt width or height fails on the
+        // missing data instead of allocating the fields for it
+        let cells_count = width as u64 * height as u64;
+        let fields_count = 4 + configs.dyes.len() as u64;
+        // Every cell has a value of every field and its type
+        let data_length = cells_count
+            .checked_mul(fields_count * std::mem::size_of::<f32>() as u64 + 1)
+            .ok_or_else(|| {
+                invalid_data(format!("the container {}x{} is too large", width, height))
+            })?;
+        let mut data = Vec::new();
+        reader.take(data_length).read_to_end(&mut data)?;
+        if data.len() as u64 != data_length {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::UnexpectedEof,
+                "the checkpoint is truncated",
+            ));
+        }
+        let field_length = cells_count as usize * std::mem::size_of::<f32>();
+        let (values, cells) = data.split_at(field_length * fields_count as usize);
+
+        let mut fluid = Fluid::new(configs.fluid, configs.simulation);
+        fluid.set_dyes(&configs.dyes);
+        let fields = [
+            &mut fluid.density,
+            &mut fluid.velocities_x,
+            &mut fluid.velocities_y,
+            &mut fluid.temperature,
+        ];
+        let dyes = fluid.dyes.iter_mut().map(|dye| &mut dye.density);
+        for (field, values) in fields
+            .into_iter()
+            .chain(dyes)
+            .zip(values.chunks_exact(field_length))
+        {
+            *field = values
+                .chunks_exact(std::mem::size_of::<f32>())
+                .map(|value| f32::from_le_bytes([value[0], value[1], value[2], value[3]]))
+                .collect();
+        }
+
+        fluid.cells_type = cells
+            .iter()
+            .map(|&cell| match cell {
+                0 => ContainerWall::NoWall,
+                _ => ContainerWall::DefaultWall,
+            })
+            .collect();
+
+        Ok(Self {
+            frame_number,
+            fluid,
+        })
+    }
+
+    /// Saves the checkpoint into a file with the given path. The file is overwritten if it exists.
+    pub fn save(&self, path: &str) -> std::io::Result<()> {
+        let mut writer = std::io::BufWriter::new(std::fs::File::create(path)?);
+        self.write(&mut writer)?;
+        writer.flush()
+    }
+
+    /// Opens the checkpoint file with the given path.
+    pub fn open(path: &str) -> std::io::Result<Self> {
+        Self::read(&mut std::io::BufReader::new(std::fs::File::open(path)?))
+    }
+
+    /// Copies the density, the velocities, the temperature and the dyes' densities of the
+    /// checkpoint into the given fluid, so a new simulation can start from them. Only the dyes
+    /// present in both of them are copied, by their order. The configurations and the walls of the
+    /// fluid are kept, so the new simulation may differ from the one the checkpoint comes from,
+    /// e.g. by its viscosity or obstacles. The fluid's container must have the same width, height
+    /// and grid layout as the checkpoint's, since the velocities are stored differently on the
+    /// [`GridLayout::Staggered`](crate::simulation::configs::GridLayout::Staggered) grid.
+    pub fn restore_into(&self, fluid: &mut Fluid) -> std::io::Result<()> {
+        let (checkpoint_configs, configs) =
+            (&self.fluid.simulation_configs, &fluid.simulation_configs);
+        if (checkpoint_configs.width, checkpoint_configs.height) != (configs.width, configs.height)
+        {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::InvalidInput,
+                format!(
+                    "the checkpoint's container is {}x{}, but the simulated one is {}x{}",
+                    checkpoint_configs.width,
+                    checkpoint_configs.height,
+                    configs.width,
+                    configs.height
+                ),
+            ));
+        }
+        if checkpoint_configs.grid_layout != configs.grid_layout {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::InvalidInput,
+                format!(
+                    "the checkpoint's grid layout is {:?}, but the simulated one is {:?}",
+                    checkpoint_configs.grid_layout, configs.grid_layout
+                ),
+            ));
+        }
+
+        fluid.density.clone_from(&self.fluid.density);
+        fluid.velocities_x.clone_from(&self.fluid.velocities_x);
+        fluid.velocities_y.clone_from(&self.fluid.velocities_y);
+        fluid.temperature.clone_from(&self.fluid.temperature);
+        for (dye, checkpoint_dye) in fluid.dyes.iter_mut().zip(self.fluid.dyes.iter()) {
+            dye.density.clone_from(&checkpoint_dye.density);
+        }
+        Ok(())
+    }
+}
+
+fn read_u32(reader: &mut impl Read) -> std::io::Result<u32> {
+    let mut result = [0; 4];
+    reader.read_exact(&mut result)?;
+    Ok(u32::from_le_bytes(result))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::simulation::checkpoint::Checkpoint;
+    use crate::simulation::configs::{DyeConfigs, FluidConfigs, GridLayout, SimulationConfigs};
+    use crate::simulation::fluid::{ContainerWall, Fluid};
+
+    #[test]
+    fn checkpoint_round_trip() {
+        let fluid_configs = FluidConfigs {
+            viscousity: 0.25,
+            ..FluidConfigs::default()
+        };
+        let mut fluid = Fluid::new(
+            fluid_configs,
+            SimulationConfigs::with_dimensions(0.5, 10, 48, 32),
+        );
+        fluid.density[5] = 0.75;
+        fluid.velocities_y[6] = -3.5;
+        fluid.temperature[9] = 12.5;
+        fluid.set_dyes(&[DyeConfigs {
+            diffusion: 0.5,
+            ..DyeConfigs::default()
+        }]);
+        fluid.dyes[0].add_density(8, 0.25);
+        fluid.cells_type[7] = ContainerWall::DefaultWall;
+        let checkpoint = Checkpoint {
+            frame_number: 42,
+            fluid,
+        };
+
+        let mut buffer = Vec::new();
+        checkpoint.write(&mut buffer).unwrap();
+        let result = Checkpoint::read(&mut buffer.as_slice()).unwrap();
+
+        assert_eq!(result.frame_number, 42);
+        assert!(result.fluid.fluid_configs == fluid_configs);
+        assert_eq!(result.fluid.simulation_configs.width, 48);
+        assert_eq!(result.fluid.simulation_configs.height, 32);
+        assert_eq!(result.fluid.density, checkpoint.fluid.density);
+        assert_eq!(result.fluid.velocities_x, checkpoint.fluid.velocities_x);
+        assert_eq!(result.fluid.velocities_y, checkpoint.fluid.velocities_y);
+        assert_eq!(result.fluid.temperature, checkpoint.fluid.temperature);
+        assert_eq!(result.fluid.cells_type, checkpoint.fluid.cells_type);
+        assert_eq!(result.fluid.dyes.len(), 1);
+        assert_eq!(
+            result.fluid.dyes[0].configs,
+            checkpoint.fluid.dyes[0].configs
+        );
+        assert_eq!(
+            result.fluid.dyes[0].density,
+            checkpoint.fluid.dyes[0].density
+        );
+
+        // A truncated checkpoint is rejected
+        assert!(Checkpoint::read(&mut &buffer[..buffer.len() - 1]).is_err());
+        assert!(Checkpoint::read(&mut &b"not a checkpoint"[..]).is_err());
+    }
+
+    #[test]
+    fn read_rejects_corrupt_lengths() {
+        let fluid = Fluid::new(FluidConfigs::default(), SimulationConfigs::new(0.5, 10, 16));
+        let checkpoint = Checkpoint {
+            frame_number: 0,
+            fluid,
+        };
+        let mut buffer = Vec::new();
+        checkpoint.write(&mut buffer).unwrap();
+
+        // The configurations' length follows the magic bytes, the version, the frame number, the
+        // width and the height
+        let mut huge_configs = buffer.clone();
+        huge_configs[24..28].copy_from_slice(&u32::MAX.to_le_bytes());
+        assert!(Checkpoint::read(&mut huge_configs.as_slice()).is_err());
+
+        // The configurations' width and height must match the header's, so a huge container
+        // needs both of them
+        let configs_length = u32::from_le_bytes([buffer[24], buffer[25], buffer[26], buffer[27]]);
+        let configs = std::str::from_utf8(&buffer[28..28 + configs_length as usize])
+            .unwrap()
+            .replace("width = 16", "width = 100000")
+            .replace("height = 16", "height = 100000");
+        let mut huge_container = buffer[..16].to_vec();
+        huge_container.extend_from_slice(&100_000u32.to_le_bytes());
+        huge_container.extend_from_slice(&100_000u32.to_le_bytes());
+        huge_container.extend_from_slice(&(configs.len() as u32).to_le_bytes());
+        huge_container.extend_from_slice(configs.as_bytes());
+        huge_container.extend_from_slice(&buffer[28 + configs_length as usize..]);
+        let error = Checkpoint::read(&mut huge_container.as_slice())
+            .err()
+            .unwrap();
+        assert_eq!(error.kind(), std::io::ErrorKind::UnexpectedEof);
+    }
+
+    #[test]
+    fn restore_into_fluid_with_other_configs() {
+        let mut fluid = Fluid::new(FluidConfigs::default(), SimulationConfigs::new(0.5, 10, 32));
+        fluid.density[5] = 0.75;
+        let checkpoint = Checkpoint {
+            frame_number: 0,
+            fluid,
+        };
+
+        let fluid_configs = FluidConfigs {
+            diffusion: 0.5,
+            ..FluidConfigs::default()
+        };
+        let mut branch = Fluid::new(fluid_configs, SimulationConfigs::new(0.1, 100, 32));
+        checkpoint.restore_into(&mut branch).unwrap();
+        assert_eq!(branch.density[5], 0.75);
+        assert_eq!(branch.fluid_configs.diffusion, 0.5);
+
+        let mut other_size =
+            Fluid::new(FluidConfigs::default(), SimulationConfigs::new(0.5, 10, 64));
+        assert!(checkpoint.restore_into(&mut other_size).is_err());
+        let mut other_height = Fluid::new(
+            FluidConfigs::default(),
+            SimulationConfigs::with_dimensions(0.5, 10, 32, 48),
+        );
+        assert!(checkpoint.restore_into(&mut other_height).is_err());
+        let mut staggered_configs = SimulationConfigs::new(0.5, 10, 32);
+        staggered_configs.grid_layout = GridLayout::Staggered;
+        let mut other_layout = Fluid::new(FluidConfigs::default(), staggered_configs);
+        assert!(checkpoint.restore_into(&mut other_layout).is_err());
+    }
+}
@@ -1,14 +1,239 @@
-use eframe::egui::Color32;
+use epaint::Color32;
+use serde::{Deserialize, Serialize};
+
+/// Describes when the Gauss-Seidel solver used for the diffusion and the projection stops
+/// iterating.
+#[derive(Copy, Clone, PartialEq, Debug, Serialize, Deserialize)]
+pub enum SolverStopCondition {
+    /// Always perform exactly [`SimulationConfigs::solver_iterations`] sweeps.
+    FixedIterations,
+    /// Stop as soon as the largest residual in the grid drops below the given threshold. At most
+    /// [`SimulationConfigs::solver_iterations`] sweeps are performed, even if the solution hasn't
+    /// converged yet.
+    Tolerance(f32),
+}
+
+/// Describes how the time between two frames, i.e. [`SimulationConfigs::delta_t`], is simulated.
+#[derive(Copy, Clone, PartialEq, Debug, Serialize, Deserialize)]
+pub enum TimeStepping {
+    /// Every frame is simulated in a single step.
+    Fixed,
+    /// Every frame is split into sub-steps which are short enough that the fluid doesn't move
+    /// through more than `cfl` cells in any of them (the Courant-Friedrichs-Lewy condition). The
+    /// length of every sub-step is chosen from the largest velocity of the fluid before it.
+    Adaptive {
+        /// The largest number of cells the fluid may move through in a single sub-step
+        cfl: f32,
+        /// The largest number of sub-steps in a frame. If the fluid is too fast for them, the
+        /// sub-steps are longer than the `cfl` allows.
+        max_substeps: u32,
+    },
+}
+
+impl Default for TimeStepping {
+    fn default() -> Self {
+        TimeStepping::Fixed
+    }
+}
+
+impl TimeStepping {
+    /// Checks if the adaptive sub-steps can be chosen, i.e. the `cfl` is positive and there is at
+    /// least one sub-step in a frame.
+    pub fn is_valid(&self) -> bool {
+        match *self {
+            TimeStepping::Fixed => true,
+            TimeStepping::Adaptive { cfl, max_substeps } => {
+                cfl > 0.0 && cfl.is_finite() && max_substeps >= 1
+            }
+        }
+    }
+}
+
+/// Describes how the pressure equation of the projection is solved. Every solver performs at most
+/// [`SimulationConfigs::solver_iterations`] iterations (sweeps, conjugate gradient steps or
+/// multigrid V-cycles) and stops according to [`SimulationConfigs::solver_stop_condition`].
+///
+/// All the solvers but [`PressureSolver::GaussSeidel`] take the walls into account: no pressure
+/// gradient is set up across them, so the fluid doesn't flow into the obstacles.
+#[derive(Copy, Clone, PartialEq, Debug, Serialize, Deserialize)]
+pub enum PressureSolver {
+    /// The red-black Gauss-Seidel sweeps shared with the diffusion. The cells of the obstacles
+    /// are solved as if they were fluid.
+    GaussSeidel,
+    /// Weighted Jacobi iterations. The slowest to converge, but every cell is updated
+    /// independently.
+    Jacobi,
+    /// Red-black successive over-relaxation with the given relaxation factor between `0.0` and
+    /// `2.0`. Converges much faster than Gauss-Seidel with a factor close to `2.0`.
+    RedBlackSor {
+        /// The relaxation factor
+        omega: f32,
+    },
+    /// Conjugate gradient with a diagonal (Jacobi) preconditioner.
+    ConjugateGradient,
+    /// Geometric multigrid V-cycles with red-black Gauss-Seidel smoothing. The number of
+    /// V-cycles needed barely depends on the size of the container.
+    Multigrid,
+}
+
+impl Default for PressureSolver {
+    fn default() -> Self {
+        PressureSolver::GaussSeidel
+    }
+}
+
+/// Describes how the density, the velocities and the other fields are moved through the velocity
+/// field. The higher-order schemes keep the small details longer, but cost more per step.
+#[derive(Copy, Clone, PartialEq, Debug, Serialize, Deserialize)]
+pub enum AdvectionScheme {
+    /// First-order backtracing with bilinear interpolation. The most diffusive, but the cheapest
+    /// scheme.
+    SemiLagrangian,
+    /// A semi-Lagrangian step corrected by the error of tracing its result back, as in
+    /// Selle et al., "An Unconditionally Stable MacCormack Method". Costs two advections.
+    MacCormack,
+    /// Back and Forth Error Compensation and Correction: the advected field is corrected by the
+    /// error of a forward and a backward advection before the final one. Costs three advections.
+    Bfecc,
+    /// Backtracing with a monotonic cubic interpolation, which is sharper than the bilinear one,
+    /// but never overshoots the values around the traced point.
+    MonotonicCubic,
+}
+
+impl Default for AdvectionScheme {
+    fn default() -> Self {
+        AdvectionScheme::SemiLagrangian
+    }
+}
+
+/// Where the velocities are stored on the grid of the fluid's container.
+#[derive(Copy, Clone, PartialEq, Debug, Serialize, Deserialize)]
+pub enum GridLayout {
+    /// Both velocity components are stored at the centers of the cells, together with the
+    /// density. The projection uses central differences, so its pressure can develop a
+    /// checkerboard pattern which doesn't affect the velocities.
+    Collocated,
+    /// A MAC grid: the horizontal velocities are stored on the left faces of the cells and the
+    /// vertical ones on their top faces. The differences in the projection span a single cell,
+    /// so the divergence of the velocities is removed exactly. The velocities are sampled back to
+    /// the centers of the cells to move the density and to be shown.
+    Staggered,
+}
+
+impl Default for GridLayout {
+    fn default() -> Self {
+        GridLayout::Collocated
+    }
+}
+
+/// The shape of the velocity with which the fluid enters through a
+/// [`BoundaryCondition::VelocityInlet`].
+#[derive(Copy, Clone, PartialEq, Debug, Serialize, Deserialize)]
+pub enum InletProfile {
+    /// The same velocity along the whole edge.
+    Uniform,
+    /// The velocity is the largest in the middle of the edge and drops to zero at its ends, as in
+    /// a fully developed flow in a channel.
+    Parabolic,
+}
+
+/// Describes what happens with the fluid on one edge of the container. See
+/// [`BoundaryConditions`].
+#[derive(Copy, Clone, PartialEq, Debug, Serialize, Deserialize)]
+pub enum BoundaryCondition {
+    /// A solid wall which the fluid cannot pass through.
+    Wall,
+    /// The fluid enters the container with the prescribed velocity, which is perpendicular to the
+    /// edge. The speed is the (largest) magnitude of the velocity pointing into the container.
+    VelocityInlet {
+        /// The speed with which the fluid enters the container
+        speed: f32,
+        /// The shape of the velocity along the edge
+        profile: InletProfile,
+    },
+    /// The fluid leaves the container freely: the values on the edge are the same as the ones
+    /// right next to it (zero gradient).
+    Outflow,
+    /// Whatever leaves the container through the edge enters it through the opposite edge. The
+    /// opposite edge must be periodic, too.
+    Periodic,
+}
+
+/// The boundary conditions on the four edges of the fluid's container. The top edge is the first
+/// row of the grid (i.e. `y = 0`), and the left edge is its first column (i.e. `x = 0`).
+#[derive(Copy, Clone, PartialEq, Debug, Serialize, Deserialize)]
+#[serde(default)]
+pub struct BoundaryConditions {
+    /// The edge with `x = 0`
+    pub left: BoundaryCondition,
+    /// The edge with `x = width - 1`
+    pub right: BoundaryCondition,
+    /// The edge with `y = 0`
+    pub top: BoundaryCondition,
+    /// The edge with `y = height - 1`
+    pub bottom: BoundaryCondition,
+}
+
+impl Default for BoundaryConditions {
+    fn default() -> Self {
+        Self {
+            left: BoundaryCondition::Wall,
+            right: BoundaryCondition::Wall,
+            top: BoundaryCondition::Wall,
+            bottom: BoundaryCondition::Wall,
+        }
+    }
+}
+
+impl BoundaryConditions {
+    /// Checks if the periodic edges come in pairs, i.e. an edge is periodic if and only if the
+    /// opposite one is periodic, too.
+    pub fn are_valid(&self) -> bool {
+        let is_periodic = |condition: BoundaryCondition| condition == BoundaryCondition::Periodic;
+        is_periodic(self.left) == is_periodic(self.right)
+            && is_periodic(self.top) == is_periodic(self.bottom)
+    }
+}
 
 /// Major configurations in order to run the simulation
-#[derive(Copy, Clone)]
+#[derive(Copy, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(from = "SimulationConfigsFields")]
 pub struct SimulationConfigs {
-    /// The size of each step
+    /// The size of each step. With the [`TimeStepping::Adaptive`] time stepping, it's the time
+    /// between two frames, which is split into sub-steps.
     pub delta_t: f32,
     /// Number of frames to be simulated
     pub frames: i64,
-    /// The size of the fluid. A square container is used
-    pub size: u32,
+    /// The number of cells in every row of the fluid's container
+    pub width: u32,
+    /// The number of cells in every column of the fluid's container
+    pub height: u32,
+    /// Maximal number of Gauss-Seidel sweeps performed on every solve of a linear equation (i.e.
+    /// during the diffusion and the projection of the fluid). Independent of the number of frames.
+    pub solver_iterations: u32,
+    /// When should the linear solver stop. See [`SolverStopCondition`].
+    pub solver_stop_condition: SolverStopCondition,
+    /// How the fields are moved through the velocity field. See [`AdvectionScheme`].
+    pub advection_scheme: AdvectionScheme,
+    /// How the pressure equation of the projection is solved. See [`PressureSolver`].
+    pub pressure_solver: PressureSolver,
+    /// Where the velocities are stored on the grid. See [`GridLayout`].
+    pub grid_layout: GridLayout,
+    /// How the time between two frames is simulated. See [`TimeStepping`].
+    pub time_stepping: TimeStepping,
+    /// The length of the longer side of the fluid's container in meters. It gives the units to
+    /// the velocities (m/s), the viscosity and the diffusion (m2/s). See
+    /// [`SimulationConfigs::cell_size()`].
+    pub domain_length: f32,
+    /// The typical speed of the fluid in m/s, e.g. the speed of an inlet. Together with the
+    /// [`SimulationConfigs::domain_length`] it sets the dimensionless numbers of the flow. See
+    /// [`FluidConfigs::reynolds_number()`].
+    pub characteristic_velocity: f32,
+    /// What happens with the fluid on the edges of the container. See [`BoundaryConditions`].
+    pub boundaries: BoundaryConditions,
+    /// Number of threads the solver's kernels run on. The results are the same for any number of
+    /// threads.
+    pub threads: u32,
 }
 
 impl Default for SimulationConfigs {
@@ -16,35 +241,200 @@ impl Default for SimulationConfigs {
         SimulationConfigs {
             delta_t: 0.02,
             frames: 16,
-            size: 128,
+            width: 128,
+            height: 128,
+            solver_iterations: 20,
+            solver_stop_condition: SolverStopCondition::FixedIterations,
+            advection_scheme: AdvectionScheme::SemiLagrangian,
+            pressure_solver: PressureSolver::GaussSeidel,
+            grid_layout: GridLayout::Collocated,
+            time_stepping: TimeStepping::Fixed,
+            domain_length: 1.0,
+            characteristic_velocity: 1.0,
+            boundaries: BoundaryConditions::default(),
+            threads: 1,
         }
     }
 }
 
 impl SimulationConfigs {
-    /// Creates new SimulationConfigs struct
+    /// The minimum width and height of the fluid's container. The cells on its edges are the
+    /// boundaries, so it leaves a single cell for the fluid.
+    pub const MIN_SIZE: u32 = 3;
+
+    /// Creates new SimulationConfigs struct for a square container. The solver settings are taken
+    /// from the [`SimulationConfigs::default()`].
     pub fn new(delta_t: f32, frames: i64, fluid_container_size: u32) -> Self {
+        Self::with_dimensions(delta_t, frames, fluid_container_size, fluid_container_size)
+    }
+
+    /// Creates new SimulationConfigs struct for a container with the given width and height. The
+    /// solver settings are taken from the [`SimulationConfigs::default()`].
+    pub fn with_dimensions(delta_t: f32, frames: i64, width: u32, height: u32) -> Self {
         SimulationConfigs {
             delta_t,
             frames,
-            size: fluid_container_size,
+            width,
+            height,
+            ..SimulationConfigs::default()
+        }
+    }
+
+    /// The number of cells in the fluid's container
+    pub fn cells_count(&self) -> usize {
+        self.width as usize * self.height as usize
+    }
+
+    /// The number of cells spanning the unit of length, which is the longer side of the
+    /// container. The cells are square, so the same scale is used along both axes.
+    pub fn scale(&self) -> u32 {
+        self.width.max(self.height)
+    }
+
+    /// The number of inner cells spanning a meter. The cells on the edges of the container are
+    /// left out, since they only hold the boundary conditions.
+    pub fn cells_per_meter(&self) -> f32 {
+        self.scale().saturating_sub(2) as f32 / self.domain_length
+    }
+
+    /// The length of the side of a cell in meters.
+    pub fn cell_size(&self) -> f32 {
+        self.cells_per_meter().recip()
+    }
+
+    /// Checks if the physical units can be derived, i.e. the domain length and the characteristic
+    /// velocity are positive.
+    pub fn are_units_valid(&self) -> bool {
+        self.domain_length > 0.0 && self.characteristic_velocity > 0.0
+    }
+}
+
+/// The serialized form of the [`SimulationConfigs`]. Scenarios of version 1 describe a square
+/// container with a single `size`, which sets both the width and the height.
+#[derive(Deserialize)]
+#[serde(default)]
+struct SimulationConfigsFields {
+    delta_t: f32,
+    frames: i64,
+    size: Option<u32>,
+    width: u32,
+    height: u32,
+    solver_iterations: u32,
+    solver_stop_condition: SolverStopCondition,
+    advection_scheme: AdvectionScheme,
+    pressure_solver: PressureSolver,
+    grid_layout: GridLayout,
+    time_stepping: TimeStepping,
+    domain_length: f32,
+    characteristic_velocity: f32,
+    boundaries: BoundaryConditions,
+    threads: u32,
+}
+
+impl Default for SimulationConfigsFields {
+    fn default() -> Self {
+        SimulationConfigs::default().into()
+    }
+}
+
+impl From<SimulationConfigs> for SimulationConfigsFields {
+    fn from(configs: SimulationConfigs) -> Self {
+        Self {
+            delta_t: configs.delta_t,
+            frames: configs.frames,
+            size: None,
+            width: configs.width,
+            height: configs.height,
+            solver_iterations: configs.solver_iterations,
+            solver_stop_condition: configs.solver_stop_condition,
+            advection_scheme: configs.advection_scheme,
+            pressure_solver: configs.pressure_solver,
+            grid_layout: configs.grid_layout,
+            time_stepping: configs.time_stepping,
+            domain_length: configs.domain_length,
+            characteristic_velocity: configs.characteristic_velocity,
+            boundaries: configs.boundaries,
+            threads: configs.threads,
+        }
+    }
+}
+
+impl From<SimulationConfigsFields> for SimulationConfigs {
+    fn from(fields: SimulationConfigsFields) -> Self {
+        Self {
+            delta_t: fields.delta_t,
+            frames: fields.frames,
+            width: fields.size.unwrap_or(fields.width),
+            height: fields.size.unwrap_or(fields.height),
+            solver_iterations: fields.solver_iterations,
+            solver_stop_condition: fields.solver_stop_condition,
+            advection_scheme: fields.advection_scheme,
+            pressure_solver: fields.pressure_solver,
+            grid_layout: fields.grid_layout,
+            time_stepping: fields.time_stepping,
+            domain_length: fields.domain_length,
+            characteristic_velocity: fields.characteristic_velocity,
+            boundaries: fields.boundaries,
+            threads: fields.threads,
         }
     }
 }
 
+/// Shows how the changed configurations are applied while a simulation is running. See
+/// [`Renderer::update_configs()`](crate::simulation::renderer::Renderer::update_configs()).
+#[derive(Copy, Clone, PartialEq, Debug, Serialize, Deserialize)]
+pub enum LiveChanges {
+    /// The changes are applied in the next run of the simulation.
+    NextRun,
+    /// The running simulation is started over with the changed configurations of the fluid, once
+    /// the changed value is released. The viewport configurations and the color of the obstacles
+    /// don't restart it.
+    Restart,
+    /// The running simulation continues with the changed fluid configurations (viscosity,
+    /// diffusion, colors and noise), dyes, obstacles and their color from its next step on. The
+    /// rest of the changes are applied in the next run.
+    Continue,
+}
+
+impl Default for LiveChanges {
+    fn default() -> Self {
+        LiveChanges::NextRun
+    }
+}
+
 /// Struct describing general fluid-related configurations
-#[derive(Copy, Clone)]
+#[derive(Copy, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
 pub struct FluidConfigs {
-    /// Fluid's diffusion
+    /// Fluid's diffusion in m2/s
     pub diffusion: f32,
-    /// Fluid's viscousity
+    /// Fluid's kinematic viscousity in m2/s
     pub viscousity: f32,
     /// Shows if random perlin noise is enabled
     pub has_perlin_noise: bool,
+    /// Strength of the vorticity confinement, which gives back the small vortices smeared out by
+    /// the numerical dissipation of the advection. `0.0` disables it.
+    pub vorticity_confinement: f32,
     /// Fluid's color in simulation
     pub fluid_color: Color32,
     /// World's simulation color
     pub world_color: Color32,
+    /// Shows if the fluid carries a temperature, which is advected and diffused like the density
+    /// and makes the fluid buoyant
+    pub has_temperature: bool,
+    /// Diffusion of the fluid's temperature
+    pub thermal_diffusion: f32,
+    /// Temperature of the surrounding fluid. Warmer fluid rises against the gravity and colder
+    /// fluid sinks along it. The whole fluid starts with this temperature.
+    pub ambient_temperature: f32,
+    /// How strongly a temperature difference from the ambient one makes the fluid buoyant,
+    /// i.e. the thermal expansion coefficient of the Boussinesq approximation
+    pub thermal_expansion: f32,
+    /// How strongly the density of the fluid pulls it along the gravity
+    pub density_weight: f32,
+    /// The gravity vector as `(x, y)`. The y axis points down, so the default gravity is
+    /// positive.
+    pub gravity: (f32, f32),
 }
 
 impl Default for FluidConfigs {
@@ -53,8 +443,323 @@ impl Default for FluidConfigs {
             diffusion: 0.0,
             viscousity: 0.001,
             has_perlin_noise: true,
+            vorticity_confinement: 0.0,
             fluid_color: Color32::from_rgba_premultiplied(208, 88, 157, 220),
             world_color: Color32::from_rgba_premultiplied(94, 146, 162, 128),
+            has_temperature: false,
+            thermal_diffusion: 0.0,
+            ambient_temperature: 0.0,
+            thermal_expansion: 0.1,
+            density_weight: 0.0,
+            gravity: (0.0, 9.81),
         }
     }
 }
+
+impl FluidConfigs {
+    /// The Reynolds number of the flow, i.e. the ratio of the inertial forces to the viscous ones
+    /// with the [`SimulationConfigs::characteristic_velocity`] over the
+    /// [`SimulationConfigs::domain_length`]. It is infinite for an inviscid fluid.
+    pub fn reynolds_number(&self, simulation_configs: &SimulationConfigs) -> f32 {
+        simulation_configs.characteristic_velocity * simulation_configs.domain_length
+            / self.viscousity
+    }
+
+    /// Sets the viscousity which gives the flow the given Reynolds number. See
+    /// [`FluidConfigs::reynolds_number()`].
+    pub fn set_reynolds_number(
+        &mut self,
+        reynolds_number: f32,
+        simulation_configs: &SimulationConfigs,
+    ) {
+        self.viscousity = simulation_configs.characteristic_velocity
+            * simulation_configs.domain_length
+            / reynolds_number;
+    }
+
+    /// The Péclet number of the density, i.e. the ratio of its advection to its diffusion, the
+    /// same way as [`FluidConfigs::reynolds_number()`]. It is infinite without diffusion.
+    pub fn peclet_number(&self, simulation_configs: &SimulationConfigs) -> f32 {
+        simulation_configs.characteristic_velocity * simulation_configs.domain_length
+            / self.diffusion
+    }
+}
+
+/// Configurations of a passive dye carried by the fluid. Every dye has its own density field,
+/// which is diffused with its own rate and advected by the velocity field shared with the rest of
+/// the fluid. See [`Fluid::dyes`](crate::simulation::fluid::Fluid::dyes).
+#[derive(Copy, Clone, PartialEq, Debug, Serialize, Deserialize)]
+#[serde(default)]
+pub struct DyeConfigs {
+    /// The diffusion of the dye
+    pub diffusion: f32,
+    /// The color of the dye. It is blended over the rest of the fluid according to its alpha and
+    /// the density of the dye.
+    pub color: Color32,
+}
+
+impl Default for DyeConfigs {
+    fn default() -> Self {
+        Self {
+            diffusion: 0.0,
+            color: Color32::from_rgba_premultiplied(230, 180, 20, 255),
+        }
+    }
+}
+
+/// The file format in which the frames of the simulation are saved. See
+/// [`crate::simulation::output`].
+#[derive(Copy, Clone, PartialEq, Debug, Serialize, Deserialize)]
+pub enum OutputFormat {
+    /// The density is saved as the colored image shown in the application. The rest of the fields
+    /// are saved as 8-bit grayscale images.
+    Png,
+    /// Like [`OutputFormat::Png`], but the images are compressed with loss of detail, e.g. on the
+    /// sharp fronts of the density.
+    Jpeg,
+    /// Every field is saved as a 16-bit grayscale image.
+    Gray16Png,
+    /// Every field is saved with its exact values as a NumPy array of 32-bit floats.
+    Npy,
+    /// Every field is saved with its exact values as comma-separated values, one row of the grid
+    /// per line.
+    Csv,
+}
+
+impl OutputFormat {
+    /// The extension of the files saved in the given format
+    pub fn extension(&self) -> &'static str {
+        match self {
+            OutputFormat::Png | OutputFormat::Gray16Png => "png",
+            OutputFormat::Jpeg => "jpg",
+            OutputFormat::Npy => "npy",
+            OutputFormat::Csv => "csv",
+        }
+    }
+}
+
+/// Describes which fields of the fluid are saved for every frame. Every field is saved into a
+/// separate file.
+#[derive(Copy, Clone, PartialEq, Debug, Serialize, Deserialize)]
+#[serde(default)]
+pub struct OutputFields {
+    /// Save the density of the fluid
+    pub density: bool,
+    /// Save the x component of the fluid's velocity
+    pub velocity_x: bool,
+    /// Save the y component of the fluid's velocity
+    pub velocity_y: bool,
+    /// Save which cells are walls (`1`) and which are accessible for the fluid (`0`)
+    pub cells_mask: bool,
+}
+
+impl Default for OutputFields {
+    fn default() -> Self {
+        Self {
+            density: true,
+            velocity_x: false,
+            velocity_y: false,
+            cells_mask: false,
+        }
+    }
+}
+
+/// The file format of an exported animation. See [`AnimationConfigs`].
+#[derive(Copy, Clone, PartialEq, Debug, Serialize, Deserialize)]
+pub enum AnimationFormat {
+    /// Animated GIF. The colors are quantized to a palette of 256 colors.
+    Gif,
+    /// Animated PNG. The frames are lossless.
+    Apng,
+}
+
+impl AnimationFormat {
+    /// The extension of the animation files in the given format
+    pub fn extension(&self) -> &'static str {
+        match self {
+            AnimationFormat::Gif => "gif",
+            AnimationFormat::Apng => "png",
+        }
+    }
+}
+
+/// Configurations for exporting all the frames of a simulation as a single animation.
+#[derive(Copy, Clone, PartialEq, Debug, Serialize, Deserialize)]
+#[serde(default)]
+pub struct AnimationConfigs {
+    /// The format of the animation
+    pub format: AnimationFormat,
+    /// Number of frames shown per second
+    pub frame_rate: u32,
+    /// How many times the animation is played. `0` means it loops forever.
+    pub plays: u16,
+}
+
+impl AnimationConfigs {
+    /// The frame rates an animation can be exported with
+    pub const FRAME_RATES: std::ops::RangeInclusive<u32> = 1..=100;
+
+    /// Checks if the frame rate is one of the [`AnimationConfigs::FRAME_RATES`].
+    pub fn is_valid(&self) -> bool {
+        Self::FRAME_RATES.contains(&self.frame_rate)
+    }
+}
+
+impl Default for AnimationConfigs {
+    fn default() -> Self {
+        Self {
+            format: AnimationFormat::Gif,
+            frame_rate: 25,
+            plays: 0,
+        }
+    }
+}
+
+/// The field of the fluid shown in the rendered frames. See [`VisualizationConfigs`].
+#[derive(Copy, Clone, PartialEq, Eq, Debug, Serialize, Deserialize)]
+pub enum VisualizedField {
+    /// The colors of the fluid and the world blended by the fluid's density, with the dyes
+    /// blended over them. The colormap isn't used.
+    Colors,
+    /// The density of the fluid
+    Density,
+    /// The magnitude of the velocities in m/s
+    VelocityMagnitude,
+    /// The curl of the velocities in 1/s. The y axis points down, so the fluid with positive
+    /// vorticity turns clockwise on the screen.
+    Vorticity,
+    /// The kinematic pressure of the last projection of the fluid, in m²/s². See
+    /// [`Fluid::pressure`](crate::simulation::fluid::Fluid::pressure).
+    Pressure,
+    /// The divergence of the velocities in 1/s, which the projection removes
+    Divergence,
+}
+
+impl Default for VisualizedField {
+    fn default() -> Self {
+        VisualizedField::Colors
+    }
+}
+
+/// The colormaps the values of a field are shown with. See [`VisualizationConfigs`].
+#[derive(Copy, Clone, PartialEq, Eq, Debug, Serialize, Deserialize)]
+pub enum Colormap {
+    /// The perceptually uniform blue-green-yellow colormap of matplotlib
+    Viridis,
+    /// The perceptually uniform black-red-yellow colormap of matplotlib
+    Inferno,
+    /// The diverging blue-white-red colormap by Kenneth Moreland. It suits the signed fields,
+    /// since zero is in its (light gray) middle.
+    Coolwarm,
+}
+
+impl Default for Colormap {
+    fn default() -> Self {
+        Colormap::Viridis
+    }
+}
+
+/// The range of the values mapped onto the whole colormap. The values outside of it get the colors
+/// of its ends.
+#[derive(Copy, Clone, PartialEq, Debug, Serialize, Deserialize)]
+pub enum ValueRange {
+    /// The range of the values in the fluid's cells in every frame. The range of the vorticity
+    /// and the divergence is symmetric around zero, so zero stays in the middle of the colormap.
+    Auto,
+    /// The same range in every frame
+    Fixed {
+        /// The value shown with the first color of the colormap
+        min: f32,
+        /// The value shown with the last color of the colormap
+        max: f32,
+    },
+}
+
+impl Default for ValueRange {
+    fn default() -> Self {
+        ValueRange::Auto
+    }
+}
+
+/// Configurations of the way the fluid is shown in the rendered frames
+#[derive(Copy, Clone, PartialEq, Debug, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct VisualizationConfigs {
+    /// The shown field of the fluid
+    pub field: VisualizedField,
+    /// The colormap of the values of the field
+    pub colormap: Colormap,
+    /// The range of the values of the field mapped onto the colormap
+    pub range: ValueRange,
+}
+
+/// Configurations regarding the way the results of the simulation are stored and displayed
+#[derive(Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ViewportConfigs {
+    /// Configurations for the size of the simulation image as regards the size of the central panel
+    /// *Note:* in percents
+    pub image_resize_factor: u8,
+    /// The name of the directory in which the rendered images will be saved.
+    pub save_into_dir: String,
+    /// Should the rendered images be saved into the [`ViewportConfigs::save_into_dir`]. The
+    /// frames are always kept in memory for the preview.
+    pub save_images: bool,
+    /// The format of the saved frames. See [`OutputFormat`].
+    pub output_format: OutputFormat,
+    /// The fields of the fluid which are saved for every frame. See [`OutputFields`].
+    pub output_fields: OutputFields,
+    /// How the simulation is exported as an animation. See [`AnimationConfigs`].
+    pub animation: AnimationConfigs,
+    /// A checkpoint of the fluid is saved into the `save_into_dir` every that many frames. `0`
+    /// means no checkpoints are saved. See
+    /// [`Checkpoint`](crate::simulation::checkpoint::Checkpoint).
+    pub checkpoint_interval: u32,
+    /// How the fluid is shown in the rendered frames. See [`VisualizationConfigs`].
+    pub visualization: VisualizationConfigs,
+}
+
+impl Default for ViewportConfigs {
+    fn default() -> ViewportConfigs {
+        ViewportConfigs {
+            image_resize_factor: 50,
+            save_into_dir: "rendered_images".to_string(),
+            save_images: true,
+            output_format: OutputFormat::Png,
+            output_fields: OutputFields::default(),
+            animation: AnimationConfigs::default(),
+            checkpoint_interval: 0,
+            visualization: VisualizationConfigs::default(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::simulation::configs::{FluidConfigs, SimulationConfigs};
+
+    #[test]
+    fn reynolds_number_sets_the_viscousity() {
+        let mut simulation_configs = SimulationConfigs::new(0.02, 1, 102);
+        simulation_configs.domain_length = 2.0;
+        simulation_configs.characteristic_velocity = 0.5;
+        let mut fluid_configs = FluidConfigs::default();
+
+        fluid_configs.set_reynolds_number(100.0, &simulation_configs);
+
+        assert_eq!(fluid_configs.viscousity, 0.01);
+        assert_eq!(fluid_configs.reynolds_number(&simulation_configs), 100.0);
+        assert_eq!(simulation_configs.cell_size(), 0.02);
+        assert_eq!(
+            fluid_configs.peclet_number(&simulation_configs),
+            f32::INFINITY
+        );
+    }
+
+    #[test]
+    fn container_without_inner_cells_has_no_cells_per_meter() {
+        let simulation_configs = SimulationConfigs::new(0.02, 1, 1);
+
+        assert_eq!(simulation_configs.cells_per_meter(), 0.0);
+    }
+}
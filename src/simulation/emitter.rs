@@ -0,0 +1,224 @@
+use crate::simulation::fluid::{ContainerWall, Fluid};
+use crate::simulation::obstacle::{Circle, Obstacle};
+use serde::{Deserialize, Serialize};
+
+/// A round area of the fluid's container which adds (or, with a negative rate, removes) density
+/// or one of the dyes, heats (or cools) the fluid and drives it with the given velocity. The
+/// emitter works only during the frames between `start_frame` and `end_frame`.
+///
+/// The cells covered by the emitter are chosen the same way as for a [`Circle`] obstacle. Cells
+/// which are walls are left untouched.
+#[derive(Clone, PartialEq, Debug, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Emitter {
+    /// The center of the emitter
+    pub center: line_drawing::Point<i64>,
+    /// The radius of the emitter
+    pub radius: i64,
+    /// The density added to every covered cell per unit of time. Negative values make the
+    /// emitter a sink. The density never drops below zero.
+    pub density_rate: f32,
+    /// The temperature added to every covered cell per unit of time. Negative values cool the
+    /// fluid. Only matters if the fluid has a temperature, see [`FluidConfigs::has_temperature`].
+    ///
+    /// [`FluidConfigs::has_temperature`]: crate::simulation::configs::FluidConfigs::has_temperature
+    pub heat_rate: f32,
+    /// The velocity of the fluid in every covered cell while the emitter works
+    pub velocity: (f32, f32),
+    /// The first frame in which the emitter works
+    pub start_frame: i64,
+    /// The last frame in which the emitter works. If `None`, the emitter works till the end of
+    /// the simulation.
+    pub end_frame: Option<i64>,
+    /// The index of the dye added by the emitter. If `None`, the emitter adds the fluid's density.
+    /// See [`Fluid::dyes`].
+    pub dye: Option<usize>,
+}
+
+impl Default for Emitter {
+    fn default() -> Self {
+        Self {
+            center: (64, 20),
+            radius: 4,
+            density_rate: 10.0,
+            heat_rate: 0.0,
+            velocity: (0.0, 1.0),
+            start_frame: 0,
+            end_frame: None,
+            dye: None,
+        }
+    }
+}
+
+impl Emitter {
+    /// Follows the removal of the dye with the given index from the fluid (see
+    /// [`Fluid::remove_dye()`]): the emitters of the removed dye are dropped, and the emitters of
+    /// the dyes after it refer to their new indices.
+    pub fn remove_dye(emitters: &mut Vec<Emitter>, removed_dye: usize) {
+        emitters.retain(|emitter| emitter.dye != Some(removed_dye));
+        for emitter in emitters.iter_mut() {
+            if let Some(dye) = emitter.dye.as_mut().filter(|dye| **dye > removed_dye) {
+                *dye -= 1;
+            }
+        }
+    }
+
+    /// Checks if the emitter works during the given frame.
+    pub fn is_active(&self, frame: i64) -> bool {
+        frame >= self.start_frame && self.end_frame.map_or(true, |end_frame| frame <= end_frame)
+    }
+
+    /// Checks if the emitter's center is inside the fluid's container, its radius is positive
+    /// and it doesn't end before it starts. The emitted dye (if any) must be one of the given
+    /// number of dyes.
+    pub fn is_valid(
+        &self,
+        fluid_container_width: i64,
+        fluid_container_height: i64,
+        dyes_count: usize,
+    ) -> bool {
+        (0..fluid_container_width).contains(&self.center.0)
+            && (0..fluid_container_height).contains(&self.center.1)
+            && self.radius > 0
+            && self
+                .end_frame
+                .map_or(true, |end_frame| end_frame >= self.start_frame)
+            && self.dye.map_or(true, |dye| dye < dyes_count)
+    }
+
+    /// Applies the emitter to the fluid for a single step of the simulation. An emitter of a dye
+    /// the fluid doesn't have only drives the fluid.
+    pub fn emit(&self, fluid: &mut Fluid) {
+        let width = i64::from(fluid.simulation_configs.width);
+        let height = i64::from(fluid.simulation_configs.height);
+        let delta_t = fluid.simulation_configs.delta_t;
+        let area = Circle {
+            center: self.center,
+            radius: self.radius,
+        };
+        let (down_left_point, up_right_point) = area.bounding_box();
+
+        for x in down_left_point.0.max(0)..up_right_point.0.min(width) {
+            for y in down_left_point.1.max(0)..up_right_point.1.min(height) {
+                let idx = idx!(x, y, width, height);
+                if !area.contains((x, y)) || fluid.cells_type[idx] == ContainerWall::DefaultWall {
+                    continue;
+                }
+
+                let density = match self.dye {
+                    Some(dye) => fluid.dyes.get_mut(dye).map(|dye| &mut dye.density),
+                    None => Some(&mut fluid.density),
+                };
+                if let Some(density) = density {
+                    density[idx] = (density[idx] + self.density_rate * delta_t).max(0.0);
+                }
+                fluid.temperature[idx] += self.heat_rate * delta_t;
+                fluid.set_velocity(x as u32, y as u32, self.velocity.0, self.velocity.1);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::simulation::configs::{DyeConfigs, FluidConfigs, SimulationConfigs};
+    use crate::simulation::emitter::Emitter;
+    use crate::simulation::fluid::Fluid;
+
+    #[test]
+    fn emitter_works_between_its_frames() {
+        let emitter = Emitter {
+            start_frame: 3,
+            end_frame: Some(5),
+            ..Emitter::default()
+        };
+
+        assert!(!emitter.is_active(2));
+        assert!(emitter.is_active(3));
+        assert!(emitter.is_active(5));
+        assert!(!emitter.is_active(6));
+        assert!(Emitter::default().is_active(i64::MAX));
+    }
+
+    #[test]
+    fn emitters_follow_the_removed_dye() {
+        let mut emitters: Vec<Emitter> = [None, Some(0), Some(1), Some(2)]
+            .iter()
+            .map(|dye| Emitter {
+                dye: *dye,
+                ..Emitter::default()
+            })
+            .collect();
+
+        Emitter::remove_dye(&mut emitters, 1);
+
+        let dyes: Vec<_> = emitters.iter().map(|emitter| emitter.dye).collect();
+        assert_eq!(dyes, [None, Some(0), Some(1)]);
+    }
+
+    #[test]
+    fn sink_removes_density() {
+        let simulation_configs = SimulationConfigs::with_dimensions(0.5, 1, 64, 48);
+        let mut fluid = Fluid::new(FluidConfigs::default(), simulation_configs);
+        let (width, height) = (simulation_configs.width, simulation_configs.height);
+        let emitter = Emitter {
+            center: (32, 32),
+            radius: 2,
+            density_rate: -1.0,
+            velocity: (0.5, -0.5),
+            ..Emitter::default()
+        };
+        assert!(emitter.is_valid(i64::from(width), i64::from(height), 0));
+        let below_container = Emitter {
+            center: (32, 50),
+            ..emitter.clone()
+        };
+        assert!(!below_container.is_valid(i64::from(width), i64::from(height), 0));
+
+        let density_before = fluid.density[idx!(32, 32, width, height)];
+        emitter.emit(&mut fluid);
+
+        assert_eq!(
+            fluid.density[idx!(32, 32, width, height)],
+            density_before - 0.5
+        );
+        assert_eq!(fluid.velocities_x[idx!(32, 32, width, height)], 0.5);
+        assert_eq!(fluid.velocities_y[idx!(32, 32, width, height)], -0.5);
+
+        for _ in 0..10 {
+            emitter.emit(&mut fluid);
+        }
+        assert_eq!(fluid.density[idx!(32, 32, width, height)], 0.0);
+        // Cells outside the emitter are untouched
+        assert_eq!(fluid.velocities_x[idx!(40, 32, width, height)], 1.0);
+    }
+
+    #[test]
+    fn emitter_adds_its_dye() {
+        let mut fluid = Fluid::new(FluidConfigs::default(), SimulationConfigs::new(0.5, 1, 32));
+        fluid.set_dyes(&[DyeConfigs::default(), DyeConfigs::default()]);
+        let (width, height) = (
+            fluid.simulation_configs.width,
+            fluid.simulation_configs.height,
+        );
+        let emitter = Emitter {
+            center: (8, 8),
+            radius: 2,
+            density_rate: 1.0,
+            heat_rate: 4.0,
+            dye: Some(1),
+            ..Emitter::default()
+        };
+        assert!(emitter.is_valid(i64::from(width), i64::from(height), 2));
+        assert!(!emitter.is_valid(i64::from(width), i64::from(height), 1));
+
+        let density_before = fluid.density.clone();
+        emitter.emit(&mut fluid);
+
+        assert_eq!(fluid.density, density_before);
+        assert!(fluid.dyes[0].density.iter().all(|density| *density == 0.0));
+        assert_eq!(fluid.dyes[1].density[idx!(8, 8, width, height)], 0.5);
+        assert_eq!(fluid.temperature[idx!(8, 8, width, height)], 2.0);
+        assert_eq!(fluid.velocities_y[idx!(8, 8, width, height)], 1.0);
+    }
+}
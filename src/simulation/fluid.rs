@@ -1,9 +1,15 @@
-use crate::simulation::configs::{FluidConfigs, SimulationConfigs};
+use crate::simulation::configs::{
+    AdvectionScheme, BoundaryCondition, DyeConfigs, FluidConfigs, GridLayout, InletProfile,
+    PressureSolver, SimulationConfigs, SolverStopCondition, TimeStepping,
+};
 use crate::simulation::obstacle::Obstacle;
+use crate::simulation::parallel;
+use crate::simulation::pressure::{self, PressureSolve};
 use geo::algorithm::rotate::RotatePoint;
 use geo::{line_string, point};
 use noise::{NoiseFn, Perlin};
 use rand::Rng;
+use std::borrow::Cow;
 
 use super::obstacle::ObstaclesType;
 
@@ -28,12 +34,81 @@ enum Orientation {
     Passive,
 }
 
+/// The edges of the fluid's container. See [`BoundaryConditions`](crate::simulation::configs::BoundaryConditions).
+#[derive(PartialEq, Eq, Copy, Clone)]
+enum Edge {
+    Left,
+    Right,
+    Top,
+    Bottom,
+}
+
+/// How the values of a field are interpolated between the centers of the cells. See
+/// [`Fluid::advect()`].
+#[derive(PartialEq, Eq, Copy, Clone)]
+enum Interpolation {
+    Bilinear,
+    MonotonicCubic,
+}
+
 macro_rules! idx {
-    ($x:expr, $y:expr, $size: expr) => {
-        ($x.clamp(0, $size - 1) + ($y.clamp(0, $size - 1) * $size)) as usize
+    ($x:expr, $y:expr, $width: expr, $height: expr) => {
+        ($x.clamp(0, $width - 1) + ($y.clamp(0, $height - 1) * $width)) as usize
     };
 }
 
+/// Traces the centers of the cells along the velocity field, in order to find where the fluid in
+/// them comes from (or goes to). See [`Fluid::advect()`].
+#[derive(Copy, Clone)]
+struct Tracer {
+    delta_t: f32,
+    width: u32,
+    height: u32,
+    is_periodic_x: bool,
+    is_periodic_y: bool,
+}
+
+impl Tracer {
+    /// Creates a tracer which traces the cells back in time with a `direction` of `1.0` and
+    /// forward in time with a `direction` of `-1.0`.
+    fn new(simulation_configs: &SimulationConfigs, direction: f32) -> Self {
+        Self {
+            // The velocities are in m/s and the cells are square, so the distances along both
+            // axes are converted into cells the same way
+            delta_t: direction * simulation_configs.delta_t * simulation_configs.cells_per_meter(),
+            width: simulation_configs.width,
+            height: simulation_configs.height,
+            // The periodic edges come in pairs. See [`BoundaryConditions::are_valid()`].
+            is_periodic_x: simulation_configs.boundaries.left == BoundaryCondition::Periodic,
+            is_periodic_y: simulation_configs.boundaries.top == BoundaryCondition::Periodic,
+        }
+    }
+
+    /// The point the center of the cell is traced to. Along the periodic edges the point is
+    /// wrapped around the inner cells, otherwise it is kept between the centers of the cells on
+    /// the edges and the inner cells next to them. This way every cell around the point is in
+    /// the container.
+    fn trace(&self, i: u32, j: u32, velocities_x: &[f32], velocities_y: &[f32]) -> (f32, f32) {
+        let idx = idx!(i, j, self.width, self.height);
+        let x = i as f32 - self.delta_t * velocities_x[idx];
+        let y = j as f32 - self.delta_t * velocities_y[idx];
+
+        (
+            Tracer::fit(x, self.width as f32, self.is_periodic_x),
+            Tracer::fit(y, self.height as f32, self.is_periodic_y),
+        )
+    }
+
+    /// Fits the coordinate into the container with the given length along its axis.
+    fn fit(coordinate: f32, length: f32, is_periodic: bool) -> f32 {
+        if is_periodic {
+            1.0 + (coordinate - 1.0).rem_euclid(length - 2.0)
+        } else {
+            coordinate.clamp(0.5, length - 1.5)
+        }
+    }
+}
+
 /// The struct that is responsible for simulating the fluid's behavour.
 ///
 /// *Note:*
@@ -61,11 +136,13 @@ pub struct Fluid {
     pub density: Vec<f32>,
     /// the distributed velocities in the x direction for the given step.
     /// **Note:** The velocity is a vector formed by both the `velocities_x` and `velocities_y`
-    /// vector structures
+    /// vector structures. On the [`GridLayout::Staggered`] grid, the value of every cell is the
+    /// velocity on its left face. See [`Fluid::cell_velocities()`].
     pub velocities_x: Vec<f32>,
     /// the distributed velocities in the y direction for the given step.
     /// **Note:** The velocity is a vector formed by both the `velocities_x` and `velocities_y`
-    /// vector structures
+    /// vector structures. On the [`GridLayout::Staggered`] grid, the value of every cell is the
+    /// velocity on its top face. See [`Fluid::cell_velocities()`].
     pub velocities_y: Vec<f32>,
     /// The previous state of the velocities_x. Needed in order to make the calculations based on
     /// the previous state of the four neighbour cells, and not based on the newly calculated ones,
@@ -78,6 +155,54 @@ pub struct Fluid {
     /// Defines which cells are "allowed" for the fluid to run into and which are "obsticles"
     /// by also defining which side of a given obstacle a cell is via the [`ContainerWall`]
     pub cells_type: Vec<ContainerWall>,
+    /// The passive dyes carried by the fluid besides its density. See [`Dye`].
+    pub dyes: Vec<Dye>,
+    /// the distributed temperatures for the given step. They are only simulated if
+    /// [`FluidConfigs::has_temperature`] is set.
+    pub temperature: Vec<f32>,
+    /// Scratch space for the temperature, so the old values are kept around while the new ones
+    /// are computed
+    temperature_scratch_space: Vec<f32>,
+    /// The number of iterations and the final residual of the last solve of the pressure
+    /// equation. See [`SimulationConfigs::pressure_solver`].
+    pub pressure_solve: PressureSolve,
+    /// The number of steps the last frame has been simulated in. See
+    /// [`SimulationConfigs::time_stepping`].
+    pub substeps: u32,
+    /// The kinematic pressure (the pressure divided by the fluid's density, in m²/s²) of the last
+    /// projection of the velocities in the given step. Its gradient times the length of the step
+    /// is what has been taken away from the velocities to make them free of divergence.
+    pub pressure: Vec<f32>,
+}
+
+/// A passive dye carried by the fluid. It is moved by the fluid's velocities the same way as the
+/// density, but it doesn't affect them.
+#[derive(Clone)]
+pub struct Dye {
+    /// The configurations of the dye, e.g. its diffusion and color
+    pub configs: DyeConfigs,
+    /// The distributed densities of the dye for the given step
+    pub density: Vec<f32>,
+    /// Scratch space for the density, so the old values are kept around while the new ones are
+    /// computed
+    scratch_space: Vec<f32>,
+}
+
+impl Dye {
+    /// Creates a dye which is not present anywhere in a container with the given number of cells.
+    fn new(configs: DyeConfigs, cells_count: usize) -> Self {
+        Self {
+            configs,
+            density: vec![0.0; cells_count],
+            scratch_space: vec![0.0; cells_count],
+        }
+    }
+
+    /// Adds density of the dye to the cell with the given index.
+    pub fn add_density(&mut self, idx: usize, amount: f32) {
+        self.density[idx] += amount;
+        self.scratch_space[idx] += amount;
+    }
 }
 
 impl Default for Fluid {
@@ -91,7 +216,7 @@ impl Default for Fluid {
 impl Fluid {
     /// Creates new Fluid struct
     pub fn new(init_fluid: FluidConfigs, init_simulation: SimulationConfigs) -> Self {
-        let fluid_field_size = (init_simulation.size * init_simulation.size) as usize;
+        let fluid_field_size = init_simulation.cells_count();
 
         let mut result = Self {
             scratch_space: vec![0.0; fluid_field_size],
@@ -101,6 +226,12 @@ impl Fluid {
             velocities_x0: vec![0.0; fluid_field_size],
             velocities_y0: vec![0.0; fluid_field_size],
             cells_type: vec![ContainerWall::NoWall; fluid_field_size],
+            dyes: Vec::new(),
+            temperature: vec![init_fluid.ambient_temperature; fluid_field_size],
+            temperature_scratch_space: vec![init_fluid.ambient_temperature; fluid_field_size],
+            pressure_solve: PressureSolve::default(),
+            substeps: 0,
+            pressure: vec![0.0; fluid_field_size],
             fluid_configs: init_fluid,
             simulation_configs: init_simulation,
         };
@@ -109,81 +240,349 @@ impl Fluid {
         result
     }
 
-    fn to_coordinate<T>(idx: T, size: T) -> (T, T)
+    #[cfg(test)]
+    fn to_coordinate<T>(idx: T, width: T) -> (T, T)
     where
         T: std::ops::Div<Output = T> + std::ops::Rem<Output = T> + Copy,
     {
-        (idx % size, idx / size)
+        (idx % width, idx / width)
     }
 
-    /// Adds density at given coordinates
-    fn add_density(&mut self, x: u32, y: u32, amount: f32) {
-        let idx = idx!(x, y, self.simulation_configs.size);
+    /// Adds density at given coordinates. Coordinates outside the container are clamped to its
+    /// edges.
+    pub fn add_density(&mut self, x: u32, y: u32, amount: f32) {
+        let idx = idx!(
+            x,
+            y,
+            self.simulation_configs.width,
+            self.simulation_configs.height
+        );
         self.density[idx] += amount;
         self.scratch_space[idx] += amount;
     }
 
-    /// Adds velocity at given coordinates
-    fn add_velocity(&mut self, x: u32, y: u32, amount_x: f32, amount_y: f32) {
-        let idx = idx!(x, y, self.simulation_configs.size);
-        self.velocities_x[idx] += amount_x;
-        self.velocities_y[idx] += amount_y;
+    /// Adds temperature at given coordinates. Coordinates outside the container are clamped to its
+    /// edges.
+    pub fn add_temperature(&mut self, x: u32, y: u32, amount: f32) {
+        let idx = idx!(
+            x,
+            y,
+            self.simulation_configs.width,
+            self.simulation_configs.height
+        );
+        self.temperature[idx] += amount;
+        self.temperature_scratch_space[idx] += amount;
+    }
+
+    /// Sets the configurations of the dyes. The densities of the dyes which are already in the
+    /// fluid are kept, the new dyes are not present anywhere, and the dyes beyond the given ones
+    /// are removed. The dyes are matched by their position, so a dye is removed from the middle
+    /// with [`Fluid::remove_dye()`].
+    pub fn set_dyes(&mut self, dyes: &[DyeConfigs]) {
+        let cells_count = self.simulation_configs.cells_count();
+        self.dyes.truncate(dyes.len());
+        for (dye, configs) in self.dyes.iter_mut().zip(dyes) {
+            dye.configs = *configs;
+        }
+        for configs in &dyes[self.dyes.len()..] {
+            self.dyes.push(Dye::new(*configs, cells_count));
+        }
+    }
+
+    /// Removes the dye with the given index together with its densities. The dyes after it keep
+    /// their densities and move one position down.
+    pub fn remove_dye(&mut self, index: usize) {
+        if index < self.dyes.len() {
+            self.dyes.remove(index);
+        }
+    }
+
+    /// Adds velocity at given coordinates. Coordinates outside the container are clamped to its
+    /// edges. On the [`GridLayout::Staggered`] grid, every face of the cell gets half of the
+    /// velocity along its axis.
+    pub fn add_velocity(&mut self, x: u32, y: u32, amount_x: f32, amount_y: f32) {
+        let (width, height) = (
+            self.simulation_configs.width,
+            self.simulation_configs.height,
+        );
+        let (x, y) = (x.min(width - 1), y.min(height - 1));
+        match self.simulation_configs.grid_layout {
+            GridLayout::Collocated => {
+                let idx = idx!(x, y, width, height);
+                self.velocities_x[idx] += amount_x;
+                self.velocities_y[idx] += amount_y;
+            }
+            GridLayout::Staggered => {
+                self.velocities_x[idx!(x, y, width, height)] += 0.5 * amount_x;
+                if x + 1 < width {
+                    self.velocities_x[idx!(x + 1, y, width, height)] += 0.5 * amount_x;
+                }
+                self.velocities_y[idx!(x, y, width, height)] += 0.5 * amount_y;
+                if y + 1 < height {
+                    self.velocities_y[idx!(x, y + 1, width, height)] += 0.5 * amount_y;
+                }
+            }
+        }
+    }
+
+    /// Sets the velocity of the cell with the given coordinates. On the [`GridLayout::Staggered`]
+    /// grid, the velocity is set on all the faces of the cell.
+    pub fn set_velocity(&mut self, x: u32, y: u32, velocity_x: f32, velocity_y: f32) {
+        let (width, height) = (
+            self.simulation_configs.width,
+            self.simulation_configs.height,
+        );
+        let idx = idx!(x, y, width, height);
+        self.velocities_x[idx] = velocity_x;
+        self.velocities_y[idx] = velocity_y;
+        if self.simulation_configs.grid_layout == GridLayout::Staggered {
+            self.velocities_x[idx!(x + 1, y, width, height)] = velocity_x;
+            self.velocities_y[idx!(x, y + 1, width, height)] = velocity_y;
+        }
+    }
+
+    /// The velocities at the centers of the cells, row by row. On the [`GridLayout::Staggered`]
+    /// grid, they are the averages of the velocities on the opposite faces of the cells.
+    pub fn cell_velocities(&self) -> (Vec<f32>, Vec<f32>) {
+        let (velocities_x, velocities_y) = Fluid::centered_velocities(
+            &self.velocities_x,
+            &self.velocities_y,
+            &self.simulation_configs,
+        );
+        (velocities_x.into_owned(), velocities_y.into_owned())
+    }
+
+    /// The velocities at the centers of the cells. They are borrowed on the
+    /// [`GridLayout::Collocated`] grid.
+    fn centered_velocities<'a>(
+        velocities_x: &'a [f32],
+        velocities_y: &'a [f32],
+        simulation_configs: &SimulationConfigs,
+    ) -> (Cow<'a, [f32]>, Cow<'a, [f32]>) {
+        match simulation_configs.grid_layout {
+            GridLayout::Collocated => (Cow::Borrowed(velocities_x), Cow::Borrowed(velocities_y)),
+            GridLayout::Staggered => (
+                Cow::Owned(Fluid::average_neighbours(
+                    velocities_x,
+                    &[(0, 0), (1, 0)],
+                    simulation_configs,
+                )),
+                Cow::Owned(Fluid::average_neighbours(
+                    velocities_y,
+                    &[(0, 0), (0, 1)],
+                    simulation_configs,
+                )),
+            ),
+        }
+    }
+
+    /// The velocities perpendicular to the ones stored on the faces, i.e. the vertical ones on
+    /// the faces of the horizontal velocities and the horizontal ones on the faces of the
+    /// vertical velocities. Together with the stored velocities, they trace the faces during the
+    /// advection. They are borrowed on the [`GridLayout::Collocated`] grid.
+    fn transverse_velocities<'a>(
+        velocities_x: &'a [f32],
+        velocities_y: &'a [f32],
+        simulation_configs: &SimulationConfigs,
+    ) -> (Cow<'a, [f32]>, Cow<'a, [f32]>) {
+        match simulation_configs.grid_layout {
+            GridLayout::Collocated => (Cow::Borrowed(velocities_y), Cow::Borrowed(velocities_x)),
+            GridLayout::Staggered => (
+                Cow::Owned(Fluid::average_neighbours(
+                    velocities_y,
+                    &[(-1, 0), (0, 0), (-1, 1), (0, 1)],
+                    simulation_configs,
+                )),
+                Cow::Owned(Fluid::average_neighbours(
+                    velocities_x,
+                    &[(0, -1), (1, -1), (0, 0), (1, 0)],
+                    simulation_configs,
+                )),
+            ),
+        }
+    }
+
+    /// Averages the values of the cells at the given offsets from every cell. The offsets beyond
+    /// the edges of the container are clamped to them.
+    fn average_neighbours(
+        values: &[f32],
+        offsets: &[(i64, i64)],
+        simulation_configs: &SimulationConfigs,
+    ) -> Vec<f32> {
+        let (width, height) = (
+            i64::from(simulation_configs.width),
+            i64::from(simulation_configs.height),
+        );
+        let weight = 1.0 / offsets.len() as f32;
+        (0..height)
+            .flat_map(|j| {
+                (0..width).map(move |i| {
+                    offsets
+                        .iter()
+                        .map(|(di, dj)| values[idx!(i + di, j + dj, width, height)])
+                        .sum::<f32>()
+                        * weight
+                })
+            })
+            .collect()
     }
 
     fn manage_single_cell_boundary(
         orientation: Orientation,
         x: &mut [f32],
-        size: i64,
+        (width, height): (i64, i64),
         cells_type: &[ContainerWall],
         i: i64,
         j: i64,
     ) {
-        if cells_type[idx!(i, j, size)] == ContainerWall::DefaultWall {
+        if cells_type[idx!(i, j, width, height)] == ContainerWall::DefaultWall {
             return;
         }
 
-        let up_point_coordinates = (i, (j - 1).clamp(0, size - 1));
-        let down_point_coordinates = (i, (j + 1).clamp(0, size - 1));
-        let left_point_coordinates = ((i - 1).clamp(0, size - 1), j);
-        let right_point_coordinates = ((i + 1).clamp(0, size - 1), j);
+        let up_point_coordinates = (i, (j - 1).clamp(0, height - 1));
+        let down_point_coordinates = (i, (j + 1).clamp(0, height - 1));
+        let left_point_coordinates = ((i - 1).clamp(0, width - 1), j);
+        let right_point_coordinates = ((i + 1).clamp(0, width - 1), j);
 
         match orientation {
             Orientation::AdjustRow => {
-                if cells_type[idx!(left_point_coordinates.0, left_point_coordinates.1, size)]
-                    == ContainerWall::DefaultWall
+                if cells_type[idx!(
+                    left_point_coordinates.0,
+                    left_point_coordinates.1,
+                    width,
+                    height
+                )] == ContainerWall::DefaultWall
                 {
-                    x[idx!(i, j, size)] =
-                        -x[idx!(left_point_coordinates.0, left_point_coordinates.1, size)]
+                    x[idx!(i, j, width, height)] = -x[idx!(
+                        left_point_coordinates.0,
+                        left_point_coordinates.1,
+                        width,
+                        height
+                    )]
                 }
-                if cells_type[idx!(right_point_coordinates.0, right_point_coordinates.1, size)]
-                    == ContainerWall::DefaultWall
+                if cells_type[idx!(
+                    right_point_coordinates.0,
+                    right_point_coordinates.1,
+                    width,
+                    height
+                )] == ContainerWall::DefaultWall
                 {
-                    x[idx!(i, j, size)] =
-                        -x[idx!(right_point_coordinates.0, right_point_coordinates.1, size)]
+                    x[idx!(i, j, width, height)] = -x[idx!(
+                        right_point_coordinates.0,
+                        right_point_coordinates.1,
+                        width,
+                        height
+                    )]
                 }
             }
             Orientation::AdjustColumn => {
-                if cells_type[idx!(down_point_coordinates.0, down_point_coordinates.1, size)]
-                    == ContainerWall::DefaultWall
+                if cells_type[idx!(
+                    down_point_coordinates.0,
+                    down_point_coordinates.1,
+                    width,
+                    height
+                )] == ContainerWall::DefaultWall
                 {
-                    x[idx!(i, j, size)] =
-                        -x[idx!(down_point_coordinates.0, down_point_coordinates.1, size)]
+                    x[idx!(i, j, width, height)] = -x[idx!(
+                        down_point_coordinates.0,
+                        down_point_coordinates.1,
+                        width,
+                        height
+                    )]
                 }
-                if cells_type[idx!(up_point_coordinates.0, up_point_coordinates.1, size)]
-                    == ContainerWall::DefaultWall
+                if cells_type[idx!(
+                    up_point_coordinates.0,
+                    up_point_coordinates.1,
+                    width,
+                    height
+                )] == ContainerWall::DefaultWall
                 {
-                    x[idx!(i, j, size)] =
-                        -x[idx!(up_point_coordinates.0, up_point_coordinates.1, size)]
+                    x[idx!(i, j, width, height)] = -x[idx!(
+                        up_point_coordinates.0,
+                        up_point_coordinates.1,
+                        width,
+                        height
+                    )]
                 }
             }
             Orientation::Passive => {
                 // NB: only for the edges
 
-                x[idx!(i, 0, size)] = x[idx!(i, 1, size)];
-                x[idx!(i, size - 1, size)] = x[idx!(i, size - 2, size)];
+                x[idx!(i, 0, width, height)] = x[idx!(i, 1, width, height)];
+                x[idx!(i, height - 1, width, height)] = x[idx!(i, height - 2, width, height)];
+
+                x[idx!(0, j, width, height)] = x[idx!(1, j, width, height)];
+                x[idx!(width - 1, j, width, height)] = x[idx!(width - 2, j, width, height)];
+            }
+        }
+    }
+
+    /// Sets the values in the cells of the given edge of the container according to its
+    /// [`BoundaryCondition`]. The corners are left untouched. Nothing is done for the
+    /// [`BoundaryCondition::Wall`] since the walls are handled by
+    /// [`Fluid::manage_single_cell_boundary()`].
+    fn manage_edge_boundary(
+        orientation: Orientation,
+        x: &mut [f32],
+        (width, height): (i64, i64),
+        edge: Edge,
+        condition: BoundaryCondition,
+    ) {
+        if condition == BoundaryCondition::Wall {
+            return;
+        }
+
+        // The velocity component perpendicular to the edge and the direction pointing into the
+        // container
+        let (normal_orientation, inward) = match edge {
+            Edge::Left => (Orientation::AdjustRow, 1.0),
+            Edge::Right => (Orientation::AdjustRow, -1.0),
+            Edge::Top => (Orientation::AdjustColumn, 1.0),
+            Edge::Bottom => (Orientation::AdjustColumn, -1.0),
+        };
+
+        // The number of cells along the edge
+        let length = match edge {
+            Edge::Left | Edge::Right => height,
+            Edge::Top | Edge::Bottom => width,
+        };
+
+        for k in 1..length - 1 {
+            // The cell on the edge, its inner neighbour and the inner cell next to the opposite
+            // edge
+            let (edge_cell, inner_cell, opposite_cell) = match edge {
+                Edge::Left => ((0, k), (1, k), (width - 2, k)),
+                Edge::Right => ((width - 1, k), (width - 2, k), (1, k)),
+                Edge::Top => ((k, 0), (k, 1), (k, height - 2)),
+                Edge::Bottom => ((k, height - 1), (k, height - 2), (k, 1)),
+            };
 
-                x[idx!(0, j, size)] = x[idx!(1, j, size)];
-                x[idx!(size - 1, j, size)] = x[idx!(size - 2, j, size)];
+            x[idx!(edge_cell.0, edge_cell.1, width, height)] = match condition {
+                BoundaryCondition::VelocityInlet { speed, profile } => {
+                    if orientation == normal_orientation {
+                        inward * speed * Fluid::inlet_profile_factor(profile, k, length)
+                    } else if orientation == Orientation::Passive {
+                        x[idx!(inner_cell.0, inner_cell.1, width, height)]
+                    } else {
+                        0.0
+                    }
+                }
+                BoundaryCondition::Periodic => {
+                    x[idx!(opposite_cell.0, opposite_cell.1, width, height)]
+                }
+                _ => x[idx!(inner_cell.0, inner_cell.1, width, height)],
+            };
+        }
+    }
+
+    /// The fraction of the inlet's speed in the `k`-th cell along an edge with the given number of
+    /// cells.
+    fn inlet_profile_factor(profile: InletProfile, k: i64, length: i64) -> f32 {
+        match profile {
+            InletProfile::Uniform => 1.0,
+            InletProfile::Parabolic => {
+                let s = (k as f32 - 0.5) / (length - 2) as f32;
+                4.0 * s * (1.0 - s)
             }
         }
     }
@@ -249,200 +648,866 @@ impl Fluid {
     ///  direction of (1)
     ///  (ignore downward arrow's tip :))
     /// ```
+    ///
+    /// *Note 4.:* The edges of the container which are not walls are set according to the
+    /// [`SimulationConfigs::boundaries`]. See [`Fluid::manage_edge_boundary()`].
+    ///
+    /// *Note 5.:* The velocities on the [`GridLayout::Staggered`] grid are set by
+    /// [`Fluid::set_face_boundaries()`] instead.
     fn set_boundaries(
         orientation: Orientation,
         x: &mut [f32],
-        size: u32,
+        simulation_configs: &SimulationConfigs,
         cells_type: &[ContainerWall],
     ) {
-        let size = i64::from(size);
-        for j in 0..=size - 1 {
-            for i in 0..=size - 1 {
-                Self::manage_single_cell_boundary(orientation, x, size, cells_type, i, j);
+        if orientation != Orientation::Passive
+            && simulation_configs.grid_layout == GridLayout::Staggered
+        {
+            Self::set_face_boundaries(orientation, x, simulation_configs, cells_type);
+            return;
+        }
+
+        let (width, height) = (
+            i64::from(simulation_configs.width),
+            i64::from(simulation_configs.height),
+        );
+        for j in 0..=height - 1 {
+            for i in 0..=width - 1 {
+                Self::manage_single_cell_boundary(
+                    orientation,
+                    x,
+                    (width, height),
+                    cells_type,
+                    i,
+                    j,
+                );
+            }
+        }
+
+        let boundaries = &simulation_configs.boundaries;
+        for (edge, condition) in [
+            (Edge::Left, boundaries.left),
+            (Edge::Right, boundaries.right),
+            (Edge::Top, boundaries.top),
+            (Edge::Bottom, boundaries.bottom),
+        ] {
+            Self::manage_edge_boundary(orientation, x, (width, height), edge, condition);
+        }
+
+        x[idx!(0, 0, width, height)] =
+            0.5 * (x[idx!(1, 0, width, height)] + x[idx!(0, 1, width, height)]);
+        x[idx!(0, height - 1, width, height)] =
+            0.5 * (x[idx!(1, height - 1, width, height)] + x[idx!(0, height - 2, width, height)]);
+        x[idx!(width - 1, 0, width, height)] =
+            0.5 * (x[idx!(width - 2, 0, width, height)] + x[idx!(width - 1, 1, width, height)]);
+        x[idx!(width - 1, height - 1, width, height)] = 0.5
+            * (x[idx!(width - 2, height - 1, width, height)]
+                + x[idx!(width - 1, height - 2, width, height)]);
+    }
+
+    /// Sets the velocities on the faces of the cells of the [`GridLayout::Staggered`] grid. Nothing
+    /// flows through the faces of the walls, so the faces touching a
+    /// [`ContainerWall::DefaultWall`] are set to zero. The faces on the edges of the container
+    /// which are not walls are set according to the [`SimulationConfigs::boundaries`]. See
+    /// [`Fluid::manage_face_edge_boundary()`].
+    fn set_face_boundaries(
+        orientation: Orientation,
+        x: &mut [f32],
+        simulation_configs: &SimulationConfigs,
+        cells_type: &[ContainerWall],
+    ) {
+        let (width, height) = (
+            i64::from(simulation_configs.width),
+            i64::from(simulation_configs.height),
+        );
+        // The offset of the cell on the other side of the face stored in a cell
+        let (di, dj) = match orientation {
+            Orientation::AdjustRow => (1, 0),
+            _ => (0, 1),
+        };
+        for j in 0..height {
+            for i in 0..width {
+                if cells_type[idx!(i, j, width, height)] == ContainerWall::DefaultWall
+                    || cells_type[idx!(i - di, j - dj, width, height)] == ContainerWall::DefaultWall
+                {
+                    x[idx!(i, j, width, height)] = 0.0;
+                }
             }
         }
 
-        x[idx!(0, 0, size)] = 0.5 * (x[idx!(1, 0, size)] + x[idx!(0, 1, size)]);
-        x[idx!(0, size - 1, size)] =
-            0.5 * (x[idx!(1, size - 1, size)] + x[idx!(0, size - 2, size)]);
-        x[idx!(size - 1, 0, size)] =
-            0.5 * (x[idx!(size - 2, 0, size)] + x[idx!(size - 1, 1, size)]);
-        x[idx!(size - 1, size - 1, size)] =
-            0.5 * (x[idx!(size - 2, size - 1, size)] + x[idx!(size - 1, size - 2, size)]);
+        let boundaries = &simulation_configs.boundaries;
+        for (edge, condition) in [
+            (Edge::Left, boundaries.left),
+            (Edge::Right, boundaries.right),
+            (Edge::Top, boundaries.top),
+            (Edge::Bottom, boundaries.bottom),
+        ] {
+            Self::manage_face_edge_boundary(orientation, x, (width, height), edge, condition);
+        }
+    }
+
+    /// Sets the faces of the given edge of the container according to its [`BoundaryCondition`],
+    /// the same way [`Fluid::manage_edge_boundary()`] sets the centers of the cells. The faces
+    /// perpendicular to the edge are the ones between the cells on the edge and the inner cells,
+    /// together with the faces beyond the left and the top edges, which lie outside of the
+    /// container. The faces parallel to the edge are the ones of the cells on the edge.
+    fn manage_face_edge_boundary(
+        orientation: Orientation,
+        x: &mut [f32],
+        (width, height): (i64, i64),
+        edge: Edge,
+        condition: BoundaryCondition,
+    ) {
+        if condition == BoundaryCondition::Wall {
+            return;
+        }
+
+        let (normal_orientation, inward) = match edge {
+            Edge::Left => (Orientation::AdjustRow, 1.0),
+            Edge::Right => (Orientation::AdjustRow, -1.0),
+            Edge::Top => (Orientation::AdjustColumn, 1.0),
+            Edge::Bottom => (Orientation::AdjustColumn, -1.0),
+        };
+        let is_normal = orientation == normal_orientation;
+
+        let length = match edge {
+            Edge::Left | Edge::Right => height,
+            Edge::Top | Edge::Bottom => width,
+        };
+
+        for k in 1..length - 1 {
+            // The faces which are set, in order, each with the inner face next to it and the face
+            // across the container. The face between the left (or top) edge and the inner cells
+            // is the same one as the face between the right (or bottom) edge and the inner cells
+            // of a periodic container, so it is kept, and the other one is set from it.
+            let faces = match (edge, is_normal) {
+                (Edge::Left, true) => [
+                    Some(((1, k), (2, k), (1, k))),
+                    Some(((0, k), (1, k), (width - 2, k))),
+                ],
+                (Edge::Top, true) => [
+                    Some(((k, 1), (k, 2), (k, 1))),
+                    Some(((k, 0), (k, 1), (k, height - 2))),
+                ],
+                (Edge::Right, true) => [Some(((width - 1, k), (width - 2, k), (1, k))), None],
+                (Edge::Bottom, true) => [Some(((k, height - 1), (k, height - 2), (k, 1))), None],
+                (Edge::Left, false) => [Some(((0, k), (1, k), (width - 2, k))), None],
+                (Edge::Right, false) => [Some(((width - 1, k), (width - 2, k), (1, k))), None],
+                (Edge::Top, false) => [Some(((k, 0), (k, 1), (k, height - 2))), None],
+                (Edge::Bottom, false) => [Some(((k, height - 1), (k, height - 2), (k, 1))), None],
+            };
+
+            for (face, inner_face, opposite_face) in faces.into_iter().flatten() {
+                x[idx!(face.0, face.1, width, height)] = match condition {
+                    BoundaryCondition::VelocityInlet { speed, profile } => {
+                        if is_normal {
+                            inward * speed * Fluid::inlet_profile_factor(profile, k, length)
+                        } else {
+                            0.0
+                        }
+                    }
+                    BoundaryCondition::Periodic => {
+                        x[idx!(opposite_face.0, opposite_face.1, width, height)]
+                    }
+                    _ => x[idx!(inner_face.0, inner_face.1, width, height)],
+                };
+            }
+        }
     }
 
     /// Diffuses the given matrix by solving a linear equation as regards the given orientation.
-    /// See [`Orientation`].
+    /// See [`Orientation`]. The diffusion is in m2/s, so it is converted into cells with the
+    /// [`SimulationConfigs::cells_per_meter()`].
     fn diffuse(
         orientation: Orientation,
         x: &mut [f32],
         x0: &[f32],
         diffusion: &f32,
-        size: u32,
-        delta_t: &f32,
-        frames: i64,
+        simulation_configs: &SimulationConfigs,
         cells_type: &[ContainerWall],
     ) {
-        let size_float = (size - 2) as f32;
-        let a = delta_t * diffusion * size_float * size_float;
+        let cells_per_meter = simulation_configs.cells_per_meter();
+        let a = simulation_configs.delta_t * diffusion * cells_per_meter * cells_per_meter;
         Fluid::lin_solve(
             orientation,
             x,
             x0,
             a,
             1.0 + 4.0 * a,
-            size,
-            frames,
+            simulation_configs,
             cells_type,
         );
     }
 
-    /// Solves the given linear equation by taking into account the boundaries of the scene
+    /// Solves the given linear equation by taking into account the boundaries of the scene. The
+    /// number of Gauss-Seidel sweeps is bound by [`SimulationConfigs::solver_iterations`] and
+    /// can be shortened by [`SimulationConfigs::solver_stop_condition`]. Returns the number of
+    /// sweeps that have been performed.
+    ///
+    /// The sweeps are red-black, i.e. the cells with even `i + j` are updated first, and then the
+    /// ones with odd `i + j`. Every half of the sweep reads only the cells updated by the other
+    /// half, so the rows are split between [`SimulationConfigs::threads`] threads, and the result
+    /// doesn't depend on their number. The threads are spawned once for all the sweeps.
     fn lin_solve(
         orientation: Orientation,
         x: &mut [f32],
         x0: &[f32],
         a: f32,
         c: f32,
-        size: u32,
-        frames: i64,
+        simulation_configs: &SimulationConfigs,
         cells_type: &[ContainerWall],
-    ) {
+    ) -> u32 {
+        let (width, height) = (simulation_configs.width, simulation_configs.height);
+        let mut workers = parallel::Workers::new(simulation_configs.threads);
         let c_recip = 1.0 / c;
-        for _k in 0..frames {
-            for j in 1..size - 1 {
-                for i in 1..size - 1 {
-                    x[idx!(i, j, size)] = (x0[idx!(i, j, size)]
-                        + a * (x[idx!(i + 1, j, size)]
-                            + x[idx!(i - 1, j, size)]
-                            + x[idx!(i, j + 1, size)]
-                            + x[idx!(i, j - 1, size)]))
-                        * c_recip;
+        for k in 0..simulation_configs.solver_iterations {
+            for parity in 0..2 {
+                workers.for_each_rows_chunk(x, width, height, |mut chunk| {
+                    for j in chunk.rows() {
+                        for i in (1 + (j + 1 + parity) % 2..width - 1).step_by(2) {
+                            let neighbours_sum = chunk.get(i + 1, j)
+                                + chunk.get(i - 1, j)
+                                + chunk.get(i, j + 1)
+                                + chunk.get(i, j - 1);
+                            chunk.set(
+                                i,
+                                j,
+                                (x0[idx!(i, j, width, height)] + a * neighbours_sum) * c_recip,
+                            );
+                        }
+                    }
+                });
+            }
+            Fluid::set_boundaries(orientation, x, simulation_configs, cells_type);
+
+            if let SolverStopCondition::Tolerance(tolerance) =
+                simulation_configs.solver_stop_condition
+            {
+                if Fluid::residual(x, x0, a, c, simulation_configs) < tolerance {
+                    return k + 1;
                 }
             }
-            Fluid::set_boundaries(orientation, x, size, cells_type);
         }
+        simulation_configs.solver_iterations
+    }
+
+    /// Calculates the largest absolute residual of the linear equation solved by
+    /// [`Fluid::lin_solve()`] over the inner cells of the grid.
+    fn residual(
+        x: &[f32],
+        x0: &[f32],
+        a: f32,
+        c: f32,
+        simulation_configs: &SimulationConfigs,
+    ) -> f32 {
+        let (width, height) = (simulation_configs.width, simulation_configs.height);
+        let mut result: f32 = 0.0;
+        for j in 1..height - 1 {
+            for i in 1..width - 1 {
+                let neighbours_sum = x[idx!(i + 1, j, width, height)]
+                    + x[idx!(i - 1, j, width, height)]
+                    + x[idx!(i, j + 1, width, height)]
+                    + x[idx!(i, j - 1, width, height)];
+                result = result.max(
+                    (x0[idx!(i, j, width, height)] + a * neighbours_sum
+                        - c * x[idx!(i, j, width, height)])
+                    .abs(),
+                );
+            }
+        }
+        result
     }
 
     /// Used for conserving the mass since the algorithms supports incompressible fluids. This is
     /// performed by setting the boundaries in all the dimensions and for both the velocities and
-    /// the fluid.
+    /// the fluid. The pressure equation is solved with the [`SimulationConfigs::pressure_solver`],
+    /// and the outcome of the solve is returned. The divergence and the pressure gradient are
+    /// measured per meter, see [`SimulationConfigs::cells_per_meter()`].
+    ///
+    /// On the [`GridLayout::Staggered`] grid, the divergence of a cell and the pressure gradient
+    /// on a face are the differences across the cell and across the face, so the projected
+    /// velocities are free of divergence up to the residual of the pressure equation.
     fn project(
         velocities_x: &mut [f32],
         velocities_y: &mut [f32],
         p: &mut [f32],
         div: &mut [f32],
-        size: u32,
-        frames: i64,
+        simulation_configs: &SimulationConfigs,
         cells_type: &[ContainerWall],
-    ) {
-        for j in 1..size - 1 {
-            for i in 1..size - 1 {
-                div[idx!(i, j, size)] = -0.5
-                    * (velocities_x[idx!(i + 1, j, size)] - velocities_x[idx!(i - 1, j, size)]
-                        + velocities_y[idx!(i, j + 1, size)]
-                        - velocities_y[idx!(i, j - 1, size)])
-                    / size as f32;
-
-                p[idx!(i, j, size)] = 0.0;
+    ) -> PressureSolve {
+        let (width, height) = (simulation_configs.width, simulation_configs.height);
+        let cells_per_meter = simulation_configs.cells_per_meter();
+        let threads = simulation_configs.threads;
+        let grid_layout = simulation_configs.grid_layout;
+        parallel::for_each_rows_chunk(div, width, height, threads, |mut chunk| {
+            for j in chunk.rows() {
+                for i in 1..width - 1 {
+                    let divergence = match grid_layout {
+                        GridLayout::Collocated => {
+                            -0.5 * (velocities_x[idx!(i + 1, j, width, height)]
+                                - velocities_x[idx!(i - 1, j, width, height)]
+                                + velocities_y[idx!(i, j + 1, width, height)]
+                                - velocities_y[idx!(i, j - 1, width, height)])
+                                / cells_per_meter
+                        }
+                        GridLayout::Staggered => {
+                            -(velocities_x[idx!(i + 1, j, width, height)]
+                                - velocities_x[idx!(i, j, width, height)]
+                                + velocities_y[idx!(i, j + 1, width, height)]
+                                - velocities_y[idx!(i, j, width, height)])
+                                / cells_per_meter
+                        }
+                    };
+                    chunk.set(i, j, divergence);
+                }
             }
+        });
+        for j in 1..height - 1 {
+            p[idx!(1, j, width, height)..=idx!(width - 2, j, width, height)].fill(0.0);
         }
 
-        Fluid::set_boundaries(Orientation::Passive, div, size, cells_type);
-        Fluid::set_boundaries(Orientation::Passive, p, size, cells_type);
-        Fluid::lin_solve(
-            Orientation::Passive,
-            p,
-            div,
-            1.0,
-            4.0,
-            size,
-            frames,
-            cells_type,
-        );
+        Fluid::set_boundaries(Orientation::Passive, div, simulation_configs, cells_type);
+        Fluid::set_boundaries(Orientation::Passive, p, simulation_configs, cells_type);
+        let solve = match (grid_layout, simulation_configs.pressure_solver) {
+            (GridLayout::Collocated, PressureSolver::GaussSeidel) => PressureSolve {
+                iterations: Fluid::lin_solve(
+                    Orientation::Passive,
+                    p,
+                    div,
+                    1.0,
+                    4.0,
+                    simulation_configs,
+                    cells_type,
+                ),
+                residual: Fluid::residual(p, div, 1.0, 4.0, simulation_configs),
+            },
+            (_, solver) => {
+                let solve = pressure::solve(solver, p, div, simulation_configs, cells_type);
+                Fluid::set_boundaries(Orientation::Passive, p, simulation_configs, cells_type);
+                solve
+            }
+        };
 
-        for j in 1..size - 1 {
-            for i in 1..size - 1 {
-                velocities_x[idx!(i, j, size)] -=
-                    0.5 * (p[idx!(i + 1, j, size)] - p[idx!(i - 1, j, size)]) * size as f32;
-                velocities_y[idx!(i, j, size)] -=
-                    0.5 * (p[idx!(i, j + 1, size)] - p[idx!(i, j - 1, size)]) * size as f32;
+        let p: &[f32] = p;
+        parallel::for_each_rows_chunk(velocities_x, width, height, threads, |mut chunk| {
+            for j in chunk.rows() {
+                for i in 1..width - 1 {
+                    let gradient = match grid_layout {
+                        GridLayout::Collocated => {
+                            0.5 * (p[idx!(i + 1, j, width, height)]
+                                - p[idx!(i - 1, j, width, height)])
+                        }
+                        GridLayout::Staggered => {
+                            p[idx!(i, j, width, height)] - p[idx!(i - 1, j, width, height)]
+                        }
+                    };
+                    chunk.set(i, j, chunk.get(i, j) - gradient * cells_per_meter);
+                }
             }
-        }
+        });
+        parallel::for_each_rows_chunk(velocities_y, width, height, threads, |mut chunk| {
+            for j in chunk.rows() {
+                for i in 1..width - 1 {
+                    let gradient = match grid_layout {
+                        GridLayout::Collocated => {
+                            0.5 * (p[idx!(i, j + 1, width, height)]
+                                - p[idx!(i, j - 1, width, height)])
+                        }
+                        GridLayout::Staggered => {
+                            p[idx!(i, j, width, height)] - p[idx!(i, j - 1, width, height)]
+                        }
+                    };
+                    chunk.set(i, j, chunk.get(i, j) - gradient * cells_per_meter);
+                }
+            }
+        });
 
-        Fluid::set_boundaries(Orientation::AdjustRow, velocities_x, size, cells_type);
-        Fluid::set_boundaries(Orientation::AdjustColumn, velocities_y, size, cells_type);
+        Fluid::set_boundaries(
+            Orientation::AdjustRow,
+            velocities_x,
+            simulation_configs,
+            cells_type,
+        );
+        Fluid::set_boundaries(
+            Orientation::AdjustColumn,
+            velocities_y,
+            simulation_configs,
+            cells_type,
+        );
+        solve
     }
 
-    /// Moves the density through the set up velocity field.
+    /// Moves the density through the set up velocity field with the
+    /// [`SimulationConfigs::advection_scheme`]. The rows are split between
+    /// [`SimulationConfigs::threads`] threads.
     fn advect(
         orientation: Orientation,
         densities: &mut [f32],
         densities0: &[f32],
         velocities_x: &[f32],
         velocities_y: &[f32],
-        size: u32,
-        delta_t: &f32,
+        simulation_configs: &SimulationConfigs,
         cells_type: &[ContainerWall],
     ) {
-        let (mut i0, mut i1, mut j0, mut j1): (f32, f32, f32, f32);
+        match simulation_configs.advection_scheme {
+            AdvectionScheme::SemiLagrangian => Fluid::advect_once(
+                densities,
+                densities0,
+                (velocities_x, velocities_y),
+                Tracer::new(simulation_configs, 1.0),
+                Interpolation::Bilinear,
+                simulation_configs,
+            ),
+            AdvectionScheme::MonotonicCubic => Fluid::advect_once(
+                densities,
+                densities0,
+                (velocities_x, velocities_y),
+                Tracer::new(simulation_configs, 1.0),
+                Interpolation::MonotonicCubic,
+                simulation_configs,
+            ),
+            AdvectionScheme::MacCormack => {
+                Fluid::advect_once(
+                    densities,
+                    densities0,
+                    (velocities_x, velocities_y),
+                    Tracer::new(simulation_configs, 1.0),
+                    Interpolation::Bilinear,
+                    simulation_configs,
+                );
+                Fluid::set_boundaries(orientation, densities, simulation_configs, cells_type);
 
-        let delta_t_x = *delta_t * (size - 2) as f32;
-        let delta_t_y = delta_t_x;
+                let mut backward = densities0.to_vec();
+                Fluid::advect_once(
+                    &mut backward,
+                    densities,
+                    (velocities_x, velocities_y),
+                    Tracer::new(simulation_configs, -1.0),
+                    Interpolation::Bilinear,
+                    simulation_configs,
+                );
 
-        let (mut s0, mut s1, mut t0, mut t1): (f32, f32, f32, f32);
-        let (mut x, mut y): (f32, f32);
+                for ((density, density0), back) in
+                    densities.iter_mut().zip(densities0).zip(&backward)
+                {
+                    *density += 0.5 * (density0 - back);
+                }
+                Fluid::limit(
+                    densities,
+                    densities0,
+                    (velocities_x, velocities_y),
+                    simulation_configs,
+                );
+            }
+            AdvectionScheme::Bfecc => {
+                let mut forward = densities0.to_vec();
+                Fluid::advect_once(
+                    &mut forward,
+                    densities0,
+                    (velocities_x, velocities_y),
+                    Tracer::new(simulation_configs, 1.0),
+                    Interpolation::Bilinear,
+                    simulation_configs,
+                );
+                Fluid::set_boundaries(orientation, &mut forward, simulation_configs, cells_type);
 
-        let size_float: f32 = size as f32;
+                let mut backward = densities0.to_vec();
+                Fluid::advect_once(
+                    &mut backward,
+                    &forward,
+                    (velocities_x, velocities_y),
+                    Tracer::new(simulation_configs, -1.0),
+                    Interpolation::Bilinear,
+                    simulation_configs,
+                );
+                Fluid::set_boundaries(orientation, &mut backward, simulation_configs, cells_type);
 
-        for j in 1..size - 1 {
-            for i in 1..size - 1 {
-                x = i as f32 - delta_t_x * velocities_x[idx!(i, j, size)];
-                y = j as f32 - delta_t_y * velocities_y[idx!(i, j, size)];
+                let corrected: Vec<f32> = densities0
+                    .iter()
+                    .zip(&backward)
+                    .map(|(density0, back)| density0 + 0.5 * (density0 - back))
+                    .collect();
+                Fluid::advect_once(
+                    densities,
+                    &corrected,
+                    (velocities_x, velocities_y),
+                    Tracer::new(simulation_configs, 1.0),
+                    Interpolation::Bilinear,
+                    simulation_configs,
+                );
+                Fluid::limit(
+                    densities,
+                    densities0,
+                    (velocities_x, velocities_y),
+                    simulation_configs,
+                );
+            }
+        }
+        Fluid::set_boundaries(orientation, densities, simulation_configs, cells_type);
+    }
+
+    /// Sets every inner cell to the value of `densities0` at the point the tracer traces the cell
+    /// to. The edges are left untouched.
+    fn advect_once(
+        densities: &mut [f32],
+        densities0: &[f32],
+        (velocities_x, velocities_y): (&[f32], &[f32]),
+        tracer: Tracer,
+        interpolation: Interpolation,
+        simulation_configs: &SimulationConfigs,
+    ) {
+        let (width, height) = (simulation_configs.width, simulation_configs.height);
+
+        let threads = simulation_configs.threads;
+        parallel::for_each_rows_chunk(densities, width, height, threads, |mut chunk| {
+            for j in chunk.rows() {
+                for i in 1..width - 1 {
+                    let (x, y) = tracer.trace(i, j, velocities_x, velocities_y);
+                    let value = match interpolation {
+                        Interpolation::Bilinear => {
+                            Fluid::interpolate_bilinear(densities0, x, y, width, height)
+                        }
+                        Interpolation::MonotonicCubic => {
+                            Fluid::interpolate_monotonic_cubic(densities0, x, y, width, height)
+                        }
+                    };
+                    chunk.set(i, j, value);
+                }
+            }
+        });
+    }
+
+    /// Clamps every inner cell between the values of `densities0` around the point the cell is
+    /// traced back to, so the corrections of the MacCormack and BFECC schemes don't create new
+    /// extremes. Otherwise they oscillate around the sharp edges and are not stable.
+    fn limit(
+        densities: &mut [f32],
+        densities0: &[f32],
+        (velocities_x, velocities_y): (&[f32], &[f32]),
+        simulation_configs: &SimulationConfigs,
+    ) {
+        let (width, height) = (simulation_configs.width, simulation_configs.height);
+        let tracer = Tracer::new(simulation_configs, 1.0);
+
+        let threads = simulation_configs.threads;
+        parallel::for_each_rows_chunk(densities, width, height, threads, |mut chunk| {
+            for j in chunk.rows() {
+                for i in 1..width - 1 {
+                    let (x, y) = tracer.trace(i, j, velocities_x, velocities_y);
+                    let (i0, j0) = (x.floor() as u32, y.floor() as u32);
+                    let corners = [
+                        densities0[idx!(i0, j0, width, height)],
+                        densities0[idx!(i0 + 1, j0, width, height)],
+                        densities0[idx!(i0, j0 + 1, width, height)],
+                        densities0[idx!(i0 + 1, j0 + 1, width, height)],
+                    ];
+                    let min = corners.iter().copied().fold(f32::INFINITY, f32::min);
+                    let max = corners.iter().copied().fold(f32::NEG_INFINITY, f32::max);
+                    chunk.set(i, j, chunk.get(i, j).clamp(min, max));
+                }
+            }
+        });
+    }
+
+    /// The bilinear interpolation of the values at the given point.
+    fn interpolate_bilinear(values: &[f32], x: f32, y: f32, width: u32, height: u32) -> f32 {
+        let (i0, j0) = (x.floor(), y.floor());
+
+        let s1 = x - i0;
+        let s0 = 1.0 - s1;
+        let t1 = y - j0;
+        let t0 = 1.0 - t1;
+
+        let (i0, j0) = (i0 as u32, j0 as u32);
+        s0 * (t0 * values[idx!(i0, j0, width, height)]
+            + t1 * values[idx!(i0, j0 + 1, width, height)])
+            + s1 * (t0 * values[idx!(i0 + 1, j0, width, height)]
+                + t1 * values[idx!(i0 + 1, j0 + 1, width, height)])
+    }
+
+    /// The monotonic cubic interpolation of the values at the given point. The 4x4 cells around
+    /// the point are interpolated row by row, and then the results are interpolated along the
+    /// column. See [`Fluid::monotonic_cubic()`].
+    fn interpolate_monotonic_cubic(values: &[f32], x: f32, y: f32, width: u32, height: u32) -> f32 {
+        let (width, height) = (i64::from(width), i64::from(height));
+        let (i0, j0) = (x.floor(), y.floor());
+        let (s, t) = (x - i0, y - j0);
+        let (i0, j0) = (i0 as i64, j0 as i64);
 
-                x = x.clamp(0.5, size_float - 1.0);
-                y = y.clamp(0.5, size_float - 1.0);
+        let rows = [-1, 0, 1, 2].map(|dj| {
+            let row = [-1, 0, 1, 2].map(|di| values[idx!(i0 + di, j0 + dj, width, height)]);
+            Fluid::monotonic_cubic(row, s)
+        });
+        Fluid::monotonic_cubic(rows, t)
+    }
+
+    /// The cubic Hermite interpolation between `values[1]` and `values[2]` at `t` from `0.0` to
+    /// `1.0`. The slopes are limited as described by Fritsch and Carlson, so the result is always
+    /// between `values[1]` and `values[2]`.
+    fn monotonic_cubic(values: [f32; 4], t: f32) -> f32 {
+        let delta = values[2] - values[1];
+        let mut slope1 = (values[2] - values[0]) / 2.0;
+        let mut slope2 = (values[3] - values[1]) / 2.0;
+
+        if delta == 0.0 {
+            slope1 = 0.0;
+            slope2 = 0.0;
+        } else {
+            if slope1 * delta <= 0.0 {
+                slope1 = 0.0;
+            }
+            if slope2 * delta <= 0.0 {
+                slope2 = 0.0;
+            }
+            let (alpha, beta) = (slope1 / delta, slope2 / delta);
+            let length = alpha.hypot(beta);
+            if length > 3.0 {
+                slope1 *= 3.0 / length;
+                slope2 *= 3.0 / length;
+            }
+        }
 
-                i0 = x.floor();
-                i1 = i0 + 1.0;
+        let a3 = slope1 + slope2 - 2.0 * delta;
+        let a2 = 3.0 * delta - 2.0 * slope1 - slope2;
+        ((a3 * t + a2) * t + slope1) * t + values[1]
+    }
 
-                j0 = y.floor();
-                j1 = j0 + 1.0;
+    /// Applies the buoyancy force to the velocities following the Boussinesq approximation:
+    /// `f = (density_weight * density - thermal_expansion * (temperature - ambient)) * gravity`,
+    /// so the fluid warmer than the ambient one rises and the denser fluid sinks.
+    fn apply_buoyancy(&mut self) {
+        let configs = &self.fluid_configs;
+        let (gravity_x, gravity_y) = configs.gravity;
+        let delta_t = self.simulation_configs.delta_t;
 
-                s1 = x - i0;
-                s0 = 1.0 - s1;
-                t1 = y - j0;
-                t0 = 1.0 - t1;
+        let mut forces_x = vec![0.0; self.cells_type.len()];
+        let mut forces_y = vec![0.0; self.cells_type.len()];
+        for idx in 0..self.cells_type.len() {
+            if self.cells_type[idx] == ContainerWall::DefaultWall {
+                continue;
+            }
+            let force = configs.density_weight * self.density[idx]
+                - configs.thermal_expansion * (self.temperature[idx] - configs.ambient_temperature);
+            forces_x[idx] = delta_t * force * gravity_x;
+            forces_y[idx] = delta_t * force * gravity_y;
+        }
+        self.add_forces(&forces_x, &forces_y);
+    }
 
-                let (i0_int, i1_int) = (i0 as u32, i1 as u32);
-                let (j0_int, j1_int) = (j0 as u32, j1 as u32);
+    /// Applies the vorticity confinement force to the velocities. The force pushes the fluid
+    /// around the places where the vorticity (the curl of the velocities) is concentrated, in the
+    /// direction of their rotation, so small vortices don't fade away because of the numerical
+    /// dissipation. See Fedkiw, Stam and Jensen, "Visual Simulation of Smoke".
+    fn apply_vorticity_confinement(&mut self) {
+        let (width, height) = (
+            i64::from(self.simulation_configs.width),
+            i64::from(self.simulation_configs.height),
+        );
+        let strength = self.fluid_configs.vorticity_confinement;
+        let delta_t = self.simulation_configs.delta_t;
+
+        let vorticity = self.curl();
 
-                if i1 >= size_float || j1 >= size_float {
-                    densities[idx!(i, j, size)] = densities[idx!(i - 1, j, size)];
-                    break;
+        let mut forces_x = vec![0.0; self.cells_type.len()];
+        let mut forces_y = vec![0.0; self.cells_type.len()];
+        for j in 1..height - 1 {
+            for i in 1..width - 1 {
+                let idx = idx!(i, j, width, height);
+                if self.cells_type[idx] == ContainerWall::DefaultWall {
+                    continue;
                 }
-                densities[idx!(i, j, size)] = s0
-                    * (t0 * densities0[idx!(i0_int, j0_int, size)]
-                        + t1 * densities0[idx!(i0_int, j1_int, size)])
-                    + s1 * (t0 * densities0[idx!(i1_int, j0_int, size)]
-                        + t1 * densities0[idx!(i1_int, j1_int, size)]);
+
+                let gradient_x = 0.5
+                    * (vorticity[idx!(i + 1, j, width, height)].abs()
+                        - vorticity[idx!(i - 1, j, width, height)].abs());
+                let gradient_y = 0.5
+                    * (vorticity[idx!(i, j + 1, width, height)].abs()
+                        - vorticity[idx!(i, j - 1, width, height)].abs());
+                let length = gradient_x.hypot(gradient_y) + f32::EPSILON;
+
+                forces_x[idx] = delta_t * strength * gradient_y / length * vorticity[idx];
+                forces_y[idx] = -(delta_t * strength * gradient_x / length * vorticity[idx]);
             }
         }
-        Fluid::set_boundaries(orientation, densities, size, cells_type);
+        self.add_forces(&forces_x, &forces_y);
+    }
+
+    /// The curl of the velocities in the centers of the cells, with the distances measured in
+    /// cells. See [`Fluid::vorticity()`].
+    fn curl(&self) -> Vec<f32> {
+        let (width, height) = (
+            i64::from(self.simulation_configs.width),
+            i64::from(self.simulation_configs.height),
+        );
+        let (centered_x, centered_y) = Fluid::centered_velocities(
+            &self.velocities_x,
+            &self.velocities_y,
+            &self.simulation_configs,
+        );
+        let (velocities_x, velocities_y) = (centered_x.as_ref(), centered_y.as_ref());
+        (0..height)
+            .flat_map(|j| {
+                (0..width).map(move |i| {
+                    0.5 * (velocities_y[idx!(i + 1, j, width, height)]
+                        - velocities_y[idx!(i - 1, j, width, height)]
+                        - velocities_x[idx!(i, j + 1, width, height)]
+                        + velocities_x[idx!(i, j - 1, width, height)])
+                })
+            })
+            .collect()
+    }
+
+    /// The vorticity, i.e. the curl of the velocities, in the centers of the cells in 1/s.
+    pub fn vorticity(&self) -> Vec<f32> {
+        let cells_per_meter = self.simulation_configs.cells_per_meter();
+        self.curl()
+            .into_iter()
+            .map(|curl| curl * cells_per_meter)
+            .collect()
+    }
+
+    /// The divergence of the velocities in the inner cells in 1/s, as the projection sees it: the
+    /// central differences on the [`GridLayout::Collocated`] grid and the differences across the
+    /// cells on the [`GridLayout::Staggered`] one. The cells on the edges have no divergence.
+    pub fn divergence(&self) -> Vec<f32> {
+        let (width, height) = (
+            i64::from(self.simulation_configs.width),
+            i64::from(self.simulation_configs.height),
+        );
+        let cells_per_meter = self.simulation_configs.cells_per_meter();
+        let (u, v) = (&self.velocities_x, &self.velocities_y);
+
+        let mut result = vec![0.0; self.cells_type.len()];
+        for j in 1..height - 1 {
+            for i in 1..width - 1 {
+                let difference = match self.simulation_configs.grid_layout {
+                    GridLayout::Collocated => {
+                        0.5 * (u[idx!(i + 1, j, width, height)] - u[idx!(i - 1, j, width, height)]
+                            + v[idx!(i, j + 1, width, height)]
+                            - v[idx!(i, j - 1, width, height)])
+                    }
+                    GridLayout::Staggered => {
+                        u[idx!(i + 1, j, width, height)] - u[idx!(i, j, width, height)]
+                            + v[idx!(i, j + 1, width, height)]
+                            - v[idx!(i, j, width, height)]
+                    }
+                };
+                result[idx!(i, j, width, height)] = difference * cells_per_meter;
+            }
+        }
+        result
+    }
+
+    /// Adds the changes of the velocities caused by the forces in the centers of the cells. On the
+    /// [`GridLayout::Staggered`] grid, every face gets the average of the changes in the two cells
+    /// it separates.
+    fn add_forces(&mut self, forces_x: &[f32], forces_y: &[f32]) {
+        let (forces_x, forces_y) = match self.simulation_configs.grid_layout {
+            GridLayout::Collocated => (Cow::Borrowed(forces_x), Cow::Borrowed(forces_y)),
+            GridLayout::Staggered => (
+                Cow::Owned(Fluid::average_neighbours(
+                    forces_x,
+                    &[(-1, 0), (0, 0)],
+                    &self.simulation_configs,
+                )),
+                Cow::Owned(Fluid::average_neighbours(
+                    forces_y,
+                    &[(0, -1), (0, 0)],
+                    &self.simulation_configs,
+                )),
+            ),
+        };
+
+        for (velocity, force) in self.velocities_x.iter_mut().zip(forces_x.iter()) {
+            *velocity += force;
+        }
+        for (velocity, force) in self.velocities_y.iter_mut().zip(forces_y.iter()) {
+            *velocity += force;
+        }
+    }
+
+    /// The largest magnitude of the velocities in the centers of the cells.
+    pub fn max_velocity(&self) -> f32 {
+        let (velocities_x, velocities_y) = Fluid::centered_velocities(
+            &self.velocities_x,
+            &self.velocities_y,
+            &self.simulation_configs,
+        );
+        velocities_x
+            .iter()
+            .zip(velocities_y.iter())
+            .fold(0.0, |max, (velocity_x, velocity_y)| {
+                max.max(velocity_x.hypot(*velocity_y))
+            })
+    }
+
+    /// Simulates the next frame of the fluid's movement, i.e. [`SimulationConfigs::delta_t`] of
+    /// time, in one or more steps according to [`SimulationConfigs::time_stepping`]. The number of
+    /// the steps is kept in [`Fluid::substeps`].
+    ///
+    /// The adaptive sub-steps split the rest of the frame evenly, so they don't end with a tiny
+    /// one.
+    pub fn advance(&mut self) {
+        let (cfl, max_substeps) = match self.simulation_configs.time_stepping {
+            TimeStepping::Adaptive { cfl, max_substeps } => (cfl, max_substeps),
+            TimeStepping::Fixed => {
+                self.step();
+                self.substeps = 1;
+                return;
+            }
+        };
+
+        let delta_t = self.simulation_configs.delta_t;
+        let cells_per_meter = self.simulation_configs.cells_per_meter();
+        let mut remaining = delta_t;
+        self.substeps = 0;
+        while remaining > 0.0 {
+            // A non-positive (or NaN) `cfl` lets the fluid move nowhere, so all the sub-steps are
+            // taken. This also keeps a fluid at rest from dividing zero by zero.
+            let longest_substep = if cfl > 0.0 {
+                cfl / (self.max_velocity() * cells_per_meter)
+            } else {
+                0.0
+            };
+            let substeps_left = (remaining / longest_substep).ceil().clamp(
+                1.0,
+                max_substeps.saturating_sub(self.substeps).max(1) as f32,
+            );
+            let substep = remaining / substeps_left;
+
+            // The steps take their length from the configurations
+            self.simulation_configs.delta_t = substep;
+            self.step();
+            self.simulation_configs.delta_t = delta_t;
+
+            remaining -= substep;
+            self.substeps += 1;
+        }
     }
 
     /// Simulates the next step of the fluid's movement.
-    /// That includes applying diffusion and advection to the fluid
-    /// and constraining it to not get out of the wall's boundaries
+    /// That includes applying the buoyancy, the vorticity confinement, diffusion and advection to
+    /// the fluid, its temperature and its dyes and constraining it to not get out of the wall's
+    /// boundaries
     pub fn step(&mut self) {
+        if self.fluid_configs.has_temperature {
+            self.apply_buoyancy();
+        }
+        if self.fluid_configs.vorticity_confinement > 0.0 {
+            self.apply_vorticity_confinement();
+        }
+
+        // The buffers of the velocities hold the pressure of the previous projection, which has
+        // other units. The cells on the edges of the container are not updated by the diffusion
+        // and the advection, so they are reset to the velocities first.
+        self.velocities_x0.clone_from(&self.velocities_x);
+        self.velocities_y0.clone_from(&self.velocities_y);
         Fluid::diffuse(
             Orientation::AdjustRow,
             &mut self.velocities_x0,
             &self.velocities_x,
             &self.fluid_configs.viscousity,
-            self.simulation_configs.size,
-            &self.simulation_configs.delta_t,
-            self.simulation_configs.frames,
+            &self.simulation_configs,
             &self.cells_type,
         );
         Fluid::diffuse(
@@ -450,9 +1515,7 @@ impl Fluid {
             &mut self.velocities_y0,
             &self.velocities_y,
             &self.fluid_configs.viscousity,
-            self.simulation_configs.size,
-            &self.simulation_configs.delta_t,
-            self.simulation_configs.frames,
+            &self.simulation_configs,
             &self.cells_type,
         );
 
@@ -461,19 +1524,26 @@ impl Fluid {
             &mut self.velocities_y0,
             &mut self.velocities_x,
             &mut self.velocities_y,
-            self.simulation_configs.size,
-            self.simulation_configs.frames,
+            &self.simulation_configs,
             &self.cells_type,
         );
 
+        let (velocities_y_at_x_faces, velocities_x_at_y_faces) = Fluid::transverse_velocities(
+            &self.velocities_x0,
+            &self.velocities_y0,
+            &self.simulation_configs,
+        );
+
+        self.velocities_x.clone_from(&self.velocities_x0);
+        self.velocities_y.clone_from(&self.velocities_y0);
+
         Fluid::advect(
             Orientation::AdjustRow,
             &mut self.velocities_x,
             &self.velocities_x0,
             &self.velocities_x0,
-            &self.velocities_y0,
-            self.simulation_configs.size,
-            &self.simulation_configs.delta_t,
+            &velocities_y_at_x_faces,
+            &self.simulation_configs,
             &self.cells_type,
         );
 
@@ -481,31 +1551,38 @@ impl Fluid {
             Orientation::AdjustColumn,
             &mut self.velocities_y,
             &self.velocities_y0,
-            &self.velocities_x0,
+            &velocities_x_at_y_faces,
             &self.velocities_y0,
-            self.simulation_configs.size,
-            &self.simulation_configs.delta_t,
+            &self.simulation_configs,
             &self.cells_type,
         );
 
-        Fluid::project(
+        self.pressure_solve = Fluid::project(
             &mut self.velocities_x,
             &mut self.velocities_y,
             &mut self.velocities_x0,
             &mut self.velocities_y0,
-            self.simulation_configs.size,
-            self.simulation_configs.frames,
+            &self.simulation_configs,
             &self.cells_type,
         );
+        // The projection subtracts the gradient of `delta_t` times the kinematic pressure
+        let delta_t = self.simulation_configs.delta_t;
+        self.pressure.clear();
+        self.pressure
+            .extend(self.velocities_x0.iter().map(|pressure| pressure / delta_t));
+
+        let (cell_velocities_x, cell_velocities_y) = Fluid::centered_velocities(
+            &self.velocities_x,
+            &self.velocities_y,
+            &self.simulation_configs,
+        );
 
         Fluid::diffuse(
             Orientation::Passive,
             &mut self.scratch_space,
             &self.density,
             &self.fluid_configs.diffusion,
-            self.simulation_configs.size,
-            &self.simulation_configs.delta_t,
-            self.simulation_configs.frames,
+            &self.simulation_configs,
             &self.cells_type,
         );
 
@@ -513,26 +1590,75 @@ impl Fluid {
             Orientation::Passive,
             &mut self.density,
             &self.scratch_space,
-            &self.velocities_x,
-            &self.velocities_y,
-            self.simulation_configs.size,
-            &self.simulation_configs.delta_t,
+            &cell_velocities_x,
+            &cell_velocities_y,
+            &self.simulation_configs,
             &self.cells_type,
         );
 
         self.scratch_space = self.density.clone();
+
+        if self.fluid_configs.has_temperature {
+            Fluid::diffuse(
+                Orientation::Passive,
+                &mut self.temperature_scratch_space,
+                &self.temperature,
+                &self.fluid_configs.thermal_diffusion,
+                &self.simulation_configs,
+                &self.cells_type,
+            );
+
+            Fluid::advect(
+                Orientation::Passive,
+                &mut self.temperature,
+                &self.temperature_scratch_space,
+                &cell_velocities_x,
+                &cell_velocities_y,
+                &self.simulation_configs,
+                &self.cells_type,
+            );
+
+            self.temperature_scratch_space.clone_from(&self.temperature);
+        }
+
+        for dye in self.dyes.iter_mut() {
+            Fluid::diffuse(
+                Orientation::Passive,
+                &mut dye.scratch_space,
+                &dye.density,
+                &dye.configs.diffusion,
+                &self.simulation_configs,
+                &self.cells_type,
+            );
+
+            Fluid::advect(
+                Orientation::Passive,
+                &mut dye.density,
+                &dye.scratch_space,
+                &cell_velocities_x,
+                &cell_velocities_y,
+                &self.simulation_configs,
+                &self.cells_type,
+            );
+
+            dye.scratch_space.clone_from(&dye.density);
+        }
     }
 
     /// Initializes the density throughout the field with zeroes.
     fn init_density(&mut self) {
-        for j in 0..self.simulation_configs.size {
-            for i in 0..self.simulation_configs.size {
+        let (width, height) = (
+            self.simulation_configs.width,
+            self.simulation_configs.height,
+        );
+        for j in 0..height {
+            for i in 0..width {
                 self.add_density(i, j, 0.0);
             }
         }
 
-        for j in self.simulation_configs.size / 2 - 10..=self.simulation_configs.size / 2 + 10 {
-            for i in self.simulation_configs.size / 2 - 10..=self.simulation_configs.size / 2 + 10 {
+        for j in (height / 2).saturating_sub(10)..=height / 2 + 10 {
+            for i in (width / 2).saturating_sub(10)..=width / 2 + 10 {
                 self.add_density(i, j, 0.9);
             }
         }
@@ -540,32 +1666,38 @@ impl Fluid {
 
     /// Initializes the velocities of the field by making them all point to down right corner.
     fn init_velocities(&mut self) {
-        for j in 0..self.simulation_configs.size {
-            for i in 0..self.simulation_configs.size {
+        for j in 0..self.simulation_configs.height {
+            for i in 0..self.simulation_configs.width {
                 self.add_velocity(i, j, 1.0, 1.0);
             }
         }
     }
 
     /// Initializes the corner walls in the field, i.e. the image's frame, by marking each cell with
-    /// [`ContainerWall::DefaultWall`].
+    /// [`ContainerWall::DefaultWall`]. Only the edges with [`BoundaryCondition::Wall`] are marked.
     fn init_walls(&mut self) {
-        for i in 0..self.simulation_configs.size {
-            self.cells_type[idx!(i, 0, self.simulation_configs.size)] = ContainerWall::DefaultWall;
-            self.cells_type[idx!(
-                i,
-                self.simulation_configs.size - 1,
-                self.simulation_configs.size
-            )] = ContainerWall::DefaultWall;
+        let (width, height) = (
+            self.simulation_configs.width,
+            self.simulation_configs.height,
+        );
+        let boundaries = self.simulation_configs.boundaries;
+
+        for i in 0..width {
+            if boundaries.top == BoundaryCondition::Wall {
+                self.cells_type[idx!(i, 0, width, height)] = ContainerWall::DefaultWall;
+            }
+            if boundaries.bottom == BoundaryCondition::Wall {
+                self.cells_type[idx!(i, height - 1, width, height)] = ContainerWall::DefaultWall;
+            }
         }
 
-        for j in 0..self.simulation_configs.size {
-            self.cells_type[idx!(0, j, self.simulation_configs.size)] = ContainerWall::DefaultWall;
-            self.cells_type[idx!(
-                self.simulation_configs.size - 1,
-                j,
-                self.simulation_configs.size
-            )] = ContainerWall::DefaultWall;
+        for j in 0..height {
+            if boundaries.left == BoundaryCondition::Wall {
+                self.cells_type[idx!(0, j, width, height)] = ContainerWall::DefaultWall;
+            }
+            if boundaries.right == BoundaryCondition::Wall {
+                self.cells_type[idx!(width - 1, j, width, height)] = ContainerWall::DefaultWall;
+            }
         }
     }
 
@@ -573,20 +1705,20 @@ impl Fluid {
     /// more attractively when there's no specific purpose yet of the
     /// simulation yet.
     pub fn add_noise(&mut self) {
-        let perlin = Perlin::new();
+        let perlin = Perlin::new(Perlin::DEFAULT_SEED);
 
         let angle = perlin.get([
             self.simulation_configs.delta_t as f64,
             self.simulation_configs.delta_t as f64,
-        ]) * 6.28
+        ]) * std::f64::consts::TAU
             * 2f64;
-        let rand_x = rand::thread_rng().gen_range(0..self.simulation_configs.size);
-        let rand_y = rand::thread_rng().gen_range(0..self.simulation_configs.size);
+        let rand_x = rand::thread_rng().gen_range(0..self.simulation_configs.width);
+        let rand_y = rand::thread_rng().gen_range(0..self.simulation_configs.height);
 
-        let center_point = point!(x: (self.simulation_configs.size/2) as f32, y: (self.simulation_configs.size/2) as f32);
+        let center_point = point!(x: (self.simulation_configs.width/2) as f32, y: (self.simulation_configs.height/2) as f32);
         let _point_of_rotation = point!(x: rand_x as f32, y: rand_y as f32);
 
-        let ls = line_string![(x: (self.simulation_configs.size/2) as f32, y: (self.simulation_configs.size/2) as f32), (x: rand_x as f32, y: rand_y as f32)];
+        let ls = line_string![(x: (self.simulation_configs.width/2) as f32, y: (self.simulation_configs.height/2) as f32), (x: rand_x as f32, y: rand_y as f32)];
 
         let rotated = ls.rotate_around_point(angle as f32, center_point);
 
@@ -605,15 +1737,27 @@ impl Fluid {
         self.init_walls();
     }
 
-    /// Fills the inner cells of the obstacles with [`ContainerWall::DefaultWall`]
-    /// NB: works as approximation to the real obstacle. By approximating a rectangle.
-    pub fn fill_obstacle(&mut self, obstacle: &mut ObstaclesType) {
-        let points = obstacle.get_approximate_points();
+    /// Removes all the obstacles from the fluid's container, leaving only its walls. Use
+    /// [`Fluid::fill_obstacle()`] to add the obstacles back.
+    pub fn clear_obstacles(&mut self) {
+        self.cells_type.fill(ContainerWall::NoWall);
+        self.init_walls();
+    }
+
+    /// Fills the inner cells of the obstacles with [`ContainerWall::DefaultWall`]. A cell is
+    /// filled if its center lies inside the obstacle. See [`Obstacle`].
+    pub fn fill_obstacle(&mut self, obstacle: &ObstaclesType) {
+        let (width, height) = (
+            i64::from(self.simulation_configs.width),
+            i64::from(self.simulation_configs.height),
+        );
+        let (down_left_point, up_right_point) = obstacle.bounding_box();
 
-        for x in points[0].0..points[1].0 {
-            for y in points[0].1..points[1].1 {
-                self.cells_type[idx!(x, y, i64::from(self.simulation_configs.size))] =
-                    ContainerWall::DefaultWall;
+        for x in down_left_point.0.max(0)..up_right_point.0.min(width) {
+            for y in down_left_point.1.max(0)..up_right_point.1.min(height) {
+                if obstacle.contains((x, y)) {
+                    self.cells_type[idx!(x, y, width, height)] = ContainerWall::DefaultWall;
+                }
             }
         }
     }
@@ -621,16 +1765,547 @@ impl Fluid {
 
 #[cfg(test)]
 mod tests {
-    use crate::simulation::fluid::Fluid;
+    use crate::simulation::configs::{
+        AdvectionScheme, BoundaryCondition, DyeConfigs, FluidConfigs, GridLayout, InletProfile,
+        PressureSolver, SimulationConfigs, SolverStopCondition, TimeStepping,
+    };
+    use crate::simulation::fluid::{ContainerWall, Fluid, Orientation};
+    use crate::simulation::obstacle::{Circle, ObstaclesType};
 
     #[test]
     fn to_coordinate() {
         // ------ Set up coordinates -----
         let coordinate = (3, 4);
-        let size = 10;
+        let (width, height) = (10, 6);
+
+        let idx = idx!(coordinate.0, coordinate.1, width, height);
+
+        assert_eq!(coordinate, Fluid::to_coordinate(idx, width));
+    }
+
+    #[test]
+    fn lin_solve_stops_on_tolerance() {
+        let fluid = Fluid::default();
+        let mut simulation_configs = fluid.simulation_configs;
+        simulation_configs.solver_iterations = 200;
+
+        let mut x = vec![0.0; fluid.density.len()];
+        let fixed_sweeps = Fluid::lin_solve(
+            Orientation::Passive,
+            &mut x,
+            &fluid.density,
+            1.0,
+            5.0,
+            &simulation_configs,
+            &fluid.cells_type,
+        );
+        assert_eq!(fixed_sweeps, simulation_configs.solver_iterations);
+
+        simulation_configs.solver_stop_condition = SolverStopCondition::Tolerance(0.001);
+        let mut x = vec![0.0; fluid.density.len()];
+        let tolerance_sweeps = Fluid::lin_solve(
+            Orientation::Passive,
+            &mut x,
+            &fluid.density,
+            1.0,
+            5.0,
+            &simulation_configs,
+            &fluid.cells_type,
+        );
+
+        assert!(tolerance_sweeps < simulation_configs.solver_iterations);
+        assert!(Fluid::residual(&x, &fluid.density, 1.0, 5.0, &simulation_configs) < 0.001);
+    }
+
+    #[test]
+    fn solver_iterations_do_not_depend_on_frames() {
+        let simulation_configs = SimulationConfigs::new(0.02, 600, 32);
+        assert_eq!(
+            simulation_configs.solver_iterations,
+            SimulationConfigs::default().solver_iterations
+        );
+    }
+
+    #[test]
+    fn velocity_inlet_blows_through_outflow() {
+        let mut simulation_configs = SimulationConfigs::new(0.02, 1, 32);
+        simulation_configs.boundaries.left = BoundaryCondition::VelocityInlet {
+            speed: 2.0,
+            profile: InletProfile::Uniform,
+        };
+        simulation_configs.boundaries.right = BoundaryCondition::Outflow;
+        let mut fluid = Fluid::new(FluidConfigs::default(), simulation_configs);
+        let size = simulation_configs.width;
+
+        assert_eq!(
+            fluid.cells_type[idx!(0, 16, size, size)],
+            ContainerWall::NoWall
+        );
+        assert_eq!(
+            fluid.cells_type[idx!(16, 0, size, size)],
+            ContainerWall::DefaultWall
+        );
+
+        for _ in 0..10 {
+            fluid.step();
+        }
+
+        for j in 1..size - 1 {
+            assert_eq!(fluid.velocities_x[idx!(0, j, size, size)], 2.0);
+            assert_eq!(fluid.velocities_y[idx!(0, j, size, size)], 0.0);
+            assert_eq!(
+                fluid.velocities_x[idx!(size - 1, j, size, size)],
+                fluid.velocities_x[idx!(size - 2, j, size, size)]
+            );
+        }
+        assert!(fluid.velocities_x[idx!(16, 16, size, size)] > 0.0);
+    }
+
+    #[test]
+    fn periodic_edges_wrap_around() {
+        let mut simulation_configs = SimulationConfigs::new(0.02, 1, 32);
+        simulation_configs.boundaries.left = BoundaryCondition::Periodic;
+        simulation_configs.boundaries.right = BoundaryCondition::Periodic;
+        let mut fluid = Fluid::new(FluidConfigs::default(), simulation_configs);
+        let size = simulation_configs.width;
+
+        fluid.step();
+
+        for j in 1..size - 1 {
+            assert_eq!(
+                fluid.density[idx!(0, j, size, size)],
+                fluid.density[idx!(size - 2, j, size, size)]
+            );
+            assert_eq!(
+                fluid.density[idx!(size - 1, j, size, size)],
+                fluid.density[idx!(1, j, size, size)]
+            );
+        }
+    }
+
+    #[test]
+    fn threads_do_not_change_the_result() {
+        let simulate = |threads: u32| {
+            let mut simulation_configs = SimulationConfigs::new(0.02, 1, 48);
+            simulation_configs.threads = threads;
+            simulation_configs.boundaries.top = BoundaryCondition::Periodic;
+            simulation_configs.boundaries.bottom = BoundaryCondition::Periodic;
+            let mut fluid = Fluid::new(FluidConfigs::default(), simulation_configs);
+            for _ in 0..3 {
+                fluid.step();
+            }
+            fluid
+        };
+
+        let expected = simulate(1);
+        for threads in [2, 3, 8] {
+            let result = simulate(threads);
+            assert_eq!(result.density, expected.density);
+            assert_eq!(result.velocities_x, expected.velocities_x);
+            assert_eq!(result.velocities_y, expected.velocities_y);
+        }
+    }
+
+    #[test]
+    fn channel_flow_in_rectangular_container() {
+        let mut simulation_configs = SimulationConfigs::with_dimensions(0.02, 1, 96, 32);
+        simulation_configs.boundaries.left = BoundaryCondition::VelocityInlet {
+            speed: 1.0,
+            profile: InletProfile::Parabolic,
+        };
+        simulation_configs.boundaries.right = BoundaryCondition::Outflow;
+        let mut fluid = Fluid::new(FluidConfigs::default(), simulation_configs);
+        let (width, height) = (simulation_configs.width, simulation_configs.height);
+        assert_eq!(fluid.density.len(), (width * height) as usize);
 
-        let idx = idx!(coordinate.0, coordinate.1, size);
+        for _ in 0..10 {
+            fluid.step();
+        }
+
+        // The inlet spans the whole left edge, which is as long as the height
+        let middle = fluid.velocities_x[idx!(0, height / 2, width, height)];
+        assert!((middle - 1.0).abs() < 0.01);
+        assert!(fluid.velocities_x[idx!(width / 2, height / 2, width, height)] > 0.0);
+        assert_eq!(
+            fluid.cells_type[idx!(width - 2, 0, width, height)],
+            ContainerWall::DefaultWall
+        );
+        assert_eq!(
+            fluid.cells_type[idx!(width - 1, height / 2, width, height)],
+            ContainerWall::NoWall
+        );
+        assert!(fluid.density.iter().all(|density| density.is_finite()));
+    }
+
+    #[test]
+    fn dyes_move_with_the_fluid() {
+        let mut fluid = Fluid::new(FluidConfigs::default(), SimulationConfigs::new(0.02, 1, 32));
+        let diffused = DyeConfigs {
+            diffusion: 0.01,
+            ..DyeConfigs::default()
+        };
+        fluid.set_dyes(&[DyeConfigs::default(), diffused]);
+        for dye in fluid.dyes.iter_mut() {
+            for (idx, density) in fluid.density.iter().enumerate() {
+                dye.add_density(idx, *density);
+            }
+        }
+
+        for _ in 0..3 {
+            fluid.step();
+        }
+
+        // A dye with the same diffusion as the fluid's density is moved exactly the same way
+        assert_eq!(fluid.dyes[0].density, fluid.density);
+        let max = |values: &[f32]| values.iter().fold(0.0_f32, |acc, value| acc.max(*value));
+        assert!(max(&fluid.dyes[1].density) < max(&fluid.density));
+
+        // The densities of the remaining dyes are kept when the dyes change
+        let density = fluid.dyes[0].density.clone();
+        fluid.set_dyes(&[diffused]);
+        assert_eq!(fluid.dyes.len(), 1);
+        assert_eq!(fluid.dyes[0].configs, diffused);
+        assert_eq!(fluid.dyes[0].density, density);
+    }
+
+    #[test]
+    fn warm_fluid_rises() {
+        let configs = FluidConfigs {
+            has_temperature: true,
+            ambient_temperature: 20.0,
+            ..FluidConfigs::default()
+        };
+        let simulation_configs = SimulationConfigs::new(0.02, 1, 32);
+        let mut reference = Fluid::new(FluidConfigs::default(), simulation_configs);
+        let mut ambient = Fluid::new(configs, simulation_configs);
+        let mut heated = Fluid::new(configs, simulation_configs);
+        for j in 12..20 {
+            for i in 12..20 {
+                heated.add_temperature(i, j, 10.0);
+            }
+        }
+
+        for fluid in [&mut reference, &mut ambient, &mut heated] {
+            for _ in 0..3 {
+                fluid.step();
+            }
+        }
+
+        // The fluid with the ambient temperature isn't buoyant
+        assert_eq!(ambient.velocities_x, reference.velocities_x);
+        assert_eq!(ambient.velocities_y, reference.velocities_y);
+        assert!(ambient.temperature.iter().all(|&t| (t - 20.0).abs() < 1e-4));
+
+        // The y axis points down, so the warm fluid gets a smaller y velocity
+        let center = idx!(16, 16, 32, 32);
+        assert!(heated.velocities_y[center] < reference.velocities_y[center]);
+        assert!(heated.temperature[center] > 20.0);
+    }
+
+    #[test]
+    fn vorticity_confinement_keeps_vortices() {
+        let simulation_configs = SimulationConfigs::new(0.02, 1, 32);
+        let confined_configs = FluidConfigs {
+            vorticity_confinement: 10.0,
+            ..FluidConfigs::default()
+        };
+        let mut free = Fluid::new(FluidConfigs::default(), simulation_configs);
+        let mut confined = Fluid::new(confined_configs, simulation_configs);
+        for fluid in [&mut free, &mut confined] {
+            fluid.velocities_x.fill(0.0);
+            fluid.velocities_y.fill(0.0);
+            for j in 10..22 {
+                for i in 10..22 {
+                    let (x, y) = (i as f32 - 15.5, j as f32 - 15.5);
+                    fluid.add_velocity(i, j, -y * 0.1, x * 0.1);
+                }
+            }
+            for _ in 0..5 {
+                fluid.step();
+            }
+        }
+
+        let energy = |fluid: &Fluid| -> f32 {
+            fluid
+                .velocities_x
+                .iter()
+                .zip(&fluid.velocities_y)
+                .map(|(x, y)| x * x + y * y)
+                .sum()
+        };
+        assert!(energy(&confined) > energy(&free));
+    }
+
+    #[test]
+    fn multigrid_solves_the_pressure_more_accurately() {
+        let residual = |pressure_solver: PressureSolver| {
+            let mut simulation_configs = SimulationConfigs::new(0.02, 1, 66);
+            simulation_configs.pressure_solver = pressure_solver;
+            let mut fluid = Fluid::new(FluidConfigs::default(), simulation_configs);
+            fluid.fill_obstacle(&ObstaclesType::Circle(Circle {
+                center: (20, 30),
+                radius: 8,
+            }));
+            for (idx, (velocity_x, velocity_y)) in fluid
+                .velocities_x
+                .iter_mut()
+                .zip(fluid.velocities_y.iter_mut())
+                .enumerate()
+            {
+                let (i, j) = Fluid::to_coordinate(idx as f32, 66.0);
+                *velocity_x = (i * 0.3).sin() * (j * 0.2).cos();
+                *velocity_y = (i * 0.1).cos();
+            }
+
+            fluid.step();
+            assert_eq!(fluid.pressure_solve.iterations, 20);
+            fluid.pressure_solve.residual
+        };
+
+        assert!(
+            residual(PressureSolver::Multigrid) < residual(PressureSolver::GaussSeidel) / 100.0
+        );
+    }
+
+    #[test]
+    fn staggered_projection_removes_the_divergence() {
+        let size = 34;
+        let mut simulation_configs = SimulationConfigs::new(0.02, 1, size);
+        simulation_configs.grid_layout = GridLayout::Staggered;
+        simulation_configs.pressure_solver = PressureSolver::Multigrid;
+        let mut fluid = Fluid::new(FluidConfigs::default(), simulation_configs);
+        fluid.fill_obstacle(&ObstaclesType::Circle(Circle {
+            center: (12, 16),
+            radius: 5,
+        }));
+        for (idx, (velocity_x, velocity_y)) in fluid
+            .velocities_x
+            .iter_mut()
+            .zip(fluid.velocities_y.iter_mut())
+            .enumerate()
+        {
+            let (i, j) = Fluid::to_coordinate(idx as f32, size as f32);
+            *velocity_x = (i * 0.3).sin() * (j * 0.2).cos();
+            *velocity_y = (i * 0.1).cos();
+        }
+
+        fluid.step();
+
+        for j in 1..size - 1 {
+            for i in 1..size - 1 {
+                if fluid.cells_type[idx!(i, j, size, size)] == ContainerWall::DefaultWall {
+                    continue;
+                }
+                let divergence = fluid.velocities_x[idx!(i + 1, j, size, size)]
+                    - fluid.velocities_x[idx!(i, j, size, size)]
+                    + fluid.velocities_y[idx!(i, j + 1, size, size)]
+                    - fluid.velocities_y[idx!(i, j, size, size)];
+                assert!(divergence.abs() < 1e-3, "{} in ({}, {})", divergence, i, j);
+            }
+        }
+    }
+
+    #[test]
+    fn step_keeps_the_pressure_and_removes_the_divergence() {
+        let size = 34;
+        let mut simulation_configs = SimulationConfigs::new(0.02, 1, size);
+        simulation_configs.grid_layout = GridLayout::Staggered;
+        simulation_configs.pressure_solver = PressureSolver::Multigrid;
+        let mut fluid = Fluid::new(FluidConfigs::default(), simulation_configs);
+        fluid.velocities_x.fill(0.0);
+        fluid.velocities_y.fill(0.0);
+        for y in 14..20 {
+            fluid.set_velocity(16, y, 2.0, 0.0);
+        }
+        assert!(fluid
+            .divergence()
+            .iter()
+            .any(|divergence| divergence.abs() > 1.0));
+
+        fluid.step();
+
+        assert!(fluid.pressure.iter().any(|pressure| pressure.abs() > 1e-4));
+        let cells_per_meter = fluid.simulation_configs.cells_per_meter();
+        for divergence in fluid.divergence() {
+            assert!(divergence.abs() < 1e-3 * cells_per_meter, "{}", divergence);
+        }
+    }
+
+    #[test]
+    fn staggered_channel_flow() {
+        let mut simulation_configs = SimulationConfigs::with_dimensions(0.02, 1, 64, 24);
+        simulation_configs.grid_layout = GridLayout::Staggered;
+        simulation_configs.boundaries.left = BoundaryCondition::VelocityInlet {
+            speed: 1.0,
+            profile: InletProfile::Uniform,
+        };
+        simulation_configs.boundaries.right = BoundaryCondition::Outflow;
+        let mut fluid = Fluid::new(FluidConfigs::default(), simulation_configs);
+        let (width, height) = (simulation_configs.width, simulation_configs.height);
+
+        for _ in 0..10 {
+            fluid.step();
+        }
+
+        let (velocities_x, _) = fluid.cell_velocities();
+        for j in 1..height - 1 {
+            assert_eq!(fluid.velocities_x[idx!(1, j, width, height)], 1.0);
+            assert_eq!(
+                fluid.velocities_x[idx!(width - 1, j, width, height)],
+                fluid.velocities_x[idx!(width - 2, j, width, height)]
+            );
+        }
+        // The faces of the walls are closed
+        for i in 0..width {
+            assert_eq!(fluid.velocities_y[idx!(i, 1, width, height)], 0.0);
+        }
+        assert!(velocities_x[idx!(width / 2, height / 2, width, height)] > 0.0);
+        assert!(fluid.density.iter().all(|density| density.is_finite()));
+    }
+
+    #[test]
+    fn cell_velocities_average_the_faces() {
+        let mut simulation_configs = SimulationConfigs::new(0.02, 1, 8);
+        simulation_configs.grid_layout = GridLayout::Staggered;
+        let mut fluid = Fluid::new(FluidConfigs::default(), simulation_configs);
+        fluid.velocities_x.fill(0.0);
+        fluid.velocities_y.fill(0.0);
+
+        fluid.add_velocity(3, 4, 2.0, -4.0);
+        let (velocities_x, velocities_y) = fluid.cell_velocities();
+
+        assert_eq!(fluid.velocities_x[idx!(3, 4, 8, 8)], 1.0);
+        assert_eq!(fluid.velocities_x[idx!(4, 4, 8, 8)], 1.0);
+        assert_eq!(velocities_x[idx!(3, 4, 8, 8)], 1.0);
+        assert_eq!(velocities_x[idx!(2, 4, 8, 8)], 0.5);
+        assert_eq!(velocities_y[idx!(3, 4, 8, 8)], -2.0);
+        assert_eq!(velocities_y[idx!(3, 5, 8, 8)], -1.0);
+    }
+
+    #[test]
+    fn adaptive_time_step_respects_the_cfl_number() {
+        let mut simulation_configs = SimulationConfigs::new(0.1, 1, 34);
+        simulation_configs.time_stepping = TimeStepping::Adaptive {
+            cfl: 1.0,
+            max_substeps: 64,
+        };
+        let mut fluid = Fluid::new(FluidConfigs::default(), simulation_configs);
+
+        // The fluid moves through 3.2 cells in the whole frame
+        assert_eq!(fluid.max_velocity(), 2.0_f32.sqrt());
+        fluid.advance();
+        assert!(fluid.substeps >= 4, "{} sub-steps", fluid.substeps);
+        assert_eq!(fluid.simulation_configs.delta_t, 0.1);
+
+        // A strong kick is split into more sub-steps, but no more than allowed. The kick fades
+        // away during the frame, so the later sub-steps are longer.
+        fluid.add_velocity(16, 16, 500.0, 0.0);
+        fluid.advance();
+        assert!(
+            (16..=64).contains(&fluid.substeps),
+            "{} sub-steps",
+            fluid.substeps
+        );
+
+        fluid.velocities_x.fill(0.0);
+        fluid.velocities_y.fill(0.0);
+        fluid.advance();
+        assert_eq!(fluid.substeps, 1);
+
+        // A zero `cfl` takes all the sub-steps instead of dividing zero by zero
+        fluid.simulation_configs.time_stepping = TimeStepping::Adaptive {
+            cfl: 0.0,
+            max_substeps: 8,
+        };
+        fluid.advance();
+        assert_eq!(fluid.substeps, 8);
+        assert!(fluid.density.iter().all(|density| density.is_finite()));
+
+        simulation_configs.time_stepping = TimeStepping::Fixed;
+        let mut fluid = Fluid::new(FluidConfigs::default(), simulation_configs);
+        fluid.add_velocity(16, 16, 500.0, 0.0);
+        fluid.advance();
+        assert_eq!(fluid.substeps, 1);
+    }
+
+    #[test]
+    fn flows_with_the_same_reynolds_number_are_similar() {
+        let simulate = |scale: f32| {
+            let mut simulation_configs = SimulationConfigs::new(0.02, 1, 32);
+            simulation_configs.domain_length = scale;
+            simulation_configs.characteristic_velocity = scale;
+            let mut fluid_configs = FluidConfigs {
+                diffusion: 0.0001,
+                ..FluidConfigs::default()
+            };
+            fluid_configs.set_reynolds_number(500.0, &simulation_configs);
+            fluid_configs.diffusion *= scale * scale;
+            let mut fluid = Fluid::new(fluid_configs, simulation_configs);
+            for velocity in fluid
+                .velocities_x
+                .iter_mut()
+                .chain(fluid.velocities_y.iter_mut())
+            {
+                *velocity *= scale;
+            }
+            fluid.add_velocity(16, 10, 0.0, 30.0 * scale);
+            for _ in 0..5 {
+                fluid.step();
+            }
+            fluid
+        };
+
+        let (small, large) = (simulate(1.0), simulate(2.0));
+        for (small, large) in small.density.iter().zip(&large.density) {
+            assert!((small - large).abs() < 1e-4);
+        }
+    }
+
+    #[test]
+    fn monotonic_cubic_does_not_overshoot() {
+        for t in [0.0, 0.25, 0.5, 0.75, 1.0] {
+            let step = Fluid::monotonic_cubic([0.0, 0.0, 1.0, 1.0], t);
+            assert!((0.0..=1.0).contains(&step));
+            let line = Fluid::monotonic_cubic([0.0, 1.0, 2.0, 3.0], t);
+            assert!((line - (1.0 + t)).abs() < 1e-6);
+        }
+        assert_eq!(Fluid::monotonic_cubic([5.0, 0.0, 1.0, -5.0], 0.0), 0.0);
+        assert_eq!(Fluid::monotonic_cubic([5.0, 0.0, 1.0, -5.0], 1.0), 1.0);
+    }
+
+    #[test]
+    fn advection_schemes_keep_the_density_bounded() {
+        let mut peaks = Vec::new();
+        for scheme in [
+            AdvectionScheme::SemiLagrangian,
+            AdvectionScheme::MacCormack,
+            AdvectionScheme::Bfecc,
+            AdvectionScheme::MonotonicCubic,
+        ] {
+            let mut simulation_configs = SimulationConfigs::new(0.02, 1, 32);
+            simulation_configs.advection_scheme = scheme;
+            let mut fluid = Fluid::new(FluidConfigs::default(), simulation_configs);
+            for _ in 0..10 {
+                fluid.step();
+            }
+
+            let peak = fluid.density.iter().fold(0.0_f32, |acc, d| acc.max(*d));
+            assert!(fluid.density.iter().all(|d| d.is_finite() && *d >= 0.0));
+            assert!(peak <= 0.9 + 1e-5, "{:?} overshoots: {}", scheme, peak);
+            peaks.push(peak);
+        }
+
+        // The higher-order schemes smear the density out less
+        assert!(peaks[1..].iter().all(|peak| *peak >= peaks[0]));
+    }
+
+    #[test]
+    fn parabolic_inlet_is_largest_in_the_middle() {
+        let size = 34;
+        let middle = Fluid::inlet_profile_factor(InletProfile::Parabolic, 17, size);
+        let end = Fluid::inlet_profile_factor(InletProfile::Parabolic, 1, size);
 
-        assert_eq!(coordinate, Fluid::to_coordinate(idx, size));
+        assert!((middle - 1.0).abs() < 0.01);
+        assert!(end < 0.2);
     }
 }
@@ -8,6 +8,14 @@ pub mod fluid;
 /// (located directly under the project's root)
 pub mod renderer;
 
+/// Runs the kernels of the fluid solver on several threads, every one of which processes a chunk
+/// of the rows of the fluid's container.
+pub mod parallel;
+
+/// Solvers of the pressure equation of the fluid's projection, which take the walls of the
+/// fluid's container into account.
+pub mod pressure;
+
 /// Performs the current simulation only. After the simulation is done, the data is transfered to
 /// the renderer. The renderer is one for the whole program. The current structure is developed for
 /// syncronization purposes, when the simulation of the fluid behaviour should be delegated to
@@ -18,9 +26,29 @@ pub mod renderer_helpers;
 /// as long as the obstacle's points are inside the fluid's container. The fluid will avoid those.
 pub mod obstacle;
 
+/// Emitters add (or remove) density and drive the fluid in round areas of the fluid's container
+/// during a given range of frames.
+pub mod emitter;
+
 /// Contains all configuration types for the project. The renderer and the fluid
 /// use them to define their own behaviour, while the application's widgets store
 /// a copy of those same configs, so they can further manipulate and interact with
 /// the drivers (a.k.a. the [`Renderer`](crate::simulation::renderer::Renderer)
 /// and the [`Fluid`](crate::simulation::fluid::Fluid).
 pub mod configs;
+
+/// Maps the diagnostic fields of the fluid (the velocity magnitude, the vorticity, the pressure,
+/// etc.) onto colormaps, so they can be shown instead of the fluid's colors.
+pub mod visualization;
+
+/// Writes the fields of the fluid (the density, the velocities and the walls) into files in
+/// various formats, e.g. PNG images or NumPy arrays.
+pub mod output;
+
+/// Checkpoints store the state of the fluid after a given frame in a binary file, so a new
+/// simulation can be started from it.
+pub mod checkpoint;
+
+/// Scenario files describe the full setup of a simulation - the fluid, the simulation, the
+/// obstacles, the colors and the output directory - so it can be saved, shared and loaded again.
+pub mod scenario;
@@ -1,31 +1,128 @@
 use crate::simulation::configs::SimulationConfigs;
+use geo::algorithm::bounding_rect::BoundingRect;
+use geo::algorithm::contains::Contains;
+use serde::{Deserialize, Serialize};
+use simplelog::*;
 
-/// Defines every obstacle's behaviour
+/// Defines every obstacle's behaviour.
+///
+/// The points of the obstacles are given in the coordinate system of the fluid's grid: the cell
+/// `(x, y)` spans the square between the points `(x, y)` and `(x + 1, y + 1)`. A cell is covered
+/// by the obstacle if its center lies inside the obstacle's shape.
 pub trait Obstacle {
-    /// Get up left and down right point using which the obstacle is approximated.
-    fn get_approximate_points(&mut self) -> &mut Vec<line_drawing::Point<i64>>;
+    /// Get the down left and the up right point of the box bounding the obstacle. All cells
+    /// covered by the obstacle lie between the down left point (inclusive) and the up right point
+    /// (exclusive).
+    fn bounding_box(&self) -> (line_drawing::Point<i64>, line_drawing::Point<i64>);
+
+    /// Checks if the cell with the given coordinates is covered by the obstacle.
+    fn contains(&self, cell: line_drawing::Point<i64>) -> bool;
+
+    /// Check if all parameters are valid and the obstacle fits inside a fluid container with the
+    /// given width and height.
+    fn are_all_points_valid(&self, fluid_container_width: i64, fluid_container_height: i64)
+        -> bool;
+
+    /// Prepares the obstacle to be put into a fluid container with the given width and height.
+    /// Should be called before the cells covered by the obstacle are requested. Does nothing by
+    /// default.
+    fn fit_into_container(&mut self, _fluid_container_width: u32, _fluid_container_height: u32) {}
+}
+
+/// Returns the center of the given cell. See [`Obstacle`].
+fn cell_center(cell: line_drawing::Point<i64>) -> (f64, f64) {
+    (cell.0 as f64 + 0.5, cell.1 as f64 + 0.5)
+}
+
+/// Checks if the point lies inside a fluid container with the given width and height.
+fn is_point_inside_container(
+    point: line_drawing::Point<i64>,
+    fluid_container_width: i64,
+    fluid_container_height: i64,
+) -> bool {
+    (0..fluid_container_width).contains(&point.0) && (0..fluid_container_height).contains(&point.1)
 }
 
 /// Enum describing the various obstacles' types. This is what unifies all the widgets
 /// and is used fot storing them in collections.
-#[derive(Clone)]
+#[derive(Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type")]
 pub enum ObstaclesType {
     /// Used for describing the [`Rectangle`] type
     Rectangle(Rectangle),
+    /// Used for describing the [`Circle`] type
+    Circle(Circle),
+    /// Used for describing the [`Ellipse`] type
+    Ellipse(Ellipse),
+    /// Used for describing the [`Polygon`] type
+    Polygon(Polygon),
+    /// Used for describing the [`Mask`] type
+    Mask(Mask),
 }
 
-impl Obstacle for ObstaclesType {
-    fn get_approximate_points(&mut self) -> &mut Vec<line_drawing::Point<i64>> {
+impl ObstaclesType {
+    /// The name of the obstacle's type, e.g. "Rectangle"
+    pub fn name(&self) -> &'static str {
+        match self {
+            ObstaclesType::Rectangle(_) => "Rectangle",
+            ObstaclesType::Circle(_) => "Circle",
+            ObstaclesType::Ellipse(_) => "Ellipse",
+            ObstaclesType::Polygon(_) => "Polygon",
+            ObstaclesType::Mask(_) => "Mask",
+        }
+    }
+
+    fn as_obstacle(&self) -> &dyn Obstacle {
         match self {
-            ObstaclesType::Rectangle(rectangle) => &mut rectangle.approximate_points,
+            ObstaclesType::Rectangle(rectangle) => rectangle,
+            ObstaclesType::Circle(circle) => circle,
+            ObstaclesType::Ellipse(ellipse) => ellipse,
+            ObstaclesType::Polygon(polygon) => polygon,
+            ObstaclesType::Mask(mask) => mask,
+        }
+    }
+
+    fn as_obstacle_mut(&mut self) -> &mut dyn Obstacle {
+        match self {
+            ObstaclesType::Rectangle(rectangle) => rectangle,
+            ObstaclesType::Circle(circle) => circle,
+            ObstaclesType::Ellipse(ellipse) => ellipse,
+            ObstaclesType::Polygon(polygon) => polygon,
+            ObstaclesType::Mask(mask) => mask,
         }
     }
 }
 
+impl Obstacle for ObstaclesType {
+    fn bounding_box(&self) -> (line_drawing::Point<i64>, line_drawing::Point<i64>) {
+        self.as_obstacle().bounding_box()
+    }
+
+    fn contains(&self, cell: line_drawing::Point<i64>) -> bool {
+        self.as_obstacle().contains(cell)
+    }
+
+    fn are_all_points_valid(
+        &self,
+        fluid_container_width: i64,
+        fluid_container_height: i64,
+    ) -> bool {
+        self.as_obstacle()
+            .are_all_points_valid(fluid_container_width, fluid_container_height)
+    }
+
+    fn fit_into_container(&mut self, fluid_container_width: u32, fluid_container_height: u32) {
+        self.as_obstacle_mut()
+            .fit_into_container(fluid_container_width, fluid_container_height)
+    }
+}
+
 /// Rectangle obstacle which is fit parallely with respect to the
 /// coordinate system. It is defined by its uppest left vertex point and
 /// the most down right vertex point. **_Note:_** It is currently designed for parallel
 /// obstacles only
+#[derive(PartialEq, Serialize, Deserialize)]
+#[serde(into = "RectanglePoints", from = "RectanglePoints")]
 pub struct Rectangle {
     /// uppest left vertex point. See [`Rectangle`]'s description
     pub down_left_point: line_drawing::Point<i64>,
@@ -44,9 +141,37 @@ impl Clone for Rectangle {
     }
 }
 
+/// The serialized form of the [`Rectangle`]. The points are taken from the approximate points,
+/// since those are the ones that are edited through [`Rectangle::get_approximate_points()`].
+#[derive(Serialize, Deserialize)]
+struct RectanglePoints {
+    down_left_point: line_drawing::Point<i64>,
+    up_right_point: line_drawing::Point<i64>,
+}
+
+impl From<Rectangle> for RectanglePoints {
+    fn from(rectangle: Rectangle) -> Self {
+        Self {
+            down_left_point: rectangle.approximate_points[0],
+            up_right_point: rectangle.approximate_points[1],
+        }
+    }
+}
+
+impl From<RectanglePoints> for Rectangle {
+    fn from(points: RectanglePoints) -> Self {
+        Self {
+            down_left_point: points.down_left_point,
+            up_right_point: points.up_right_point,
+            approximate_points: vec![points.down_left_point, points.up_right_point],
+        }
+    }
+}
+
 impl Default for Rectangle {
     fn default() -> Self {
-        Self::new((80, 80), (110, 110), SimulationConfigs::default().size)
+        let configs = SimulationConfigs::default();
+        Self::new((80, 80), (110, 110), configs.width, configs.height)
     }
 }
 
@@ -55,7 +180,8 @@ impl Rectangle {
     pub fn new(
         down_left_point: line_drawing::Point<i64>,
         up_right_point: line_drawing::Point<i64>,
-        fluid_container_size: u32,
+        fluid_container_width: u32,
+        fluid_container_height: u32,
     ) -> Self {
         let result = Self {
             down_left_point,
@@ -63,39 +189,439 @@ impl Rectangle {
             approximate_points: vec![down_left_point, up_right_point],
         };
 
-        if !result.are_all_points_valid(i64::from(fluid_container_size)) {
+        if !result.are_all_points_valid(
+            i64::from(fluid_container_width),
+            i64::from(fluid_container_height),
+        ) {
             panic!("Invalid input for Rectangle");
         }
 
         result
     }
 
-    /// Check if all parameters are valid
-    pub fn are_all_points_valid(&self, fluid_container_size: i64) -> bool {
-        self.down_left_point.0 != self.up_right_point.0
-            && self.down_left_point.1 != self.up_right_point.1
-            && self.down_left_point.0 < self.up_right_point.0
-            && self.down_left_point.1 < self.up_right_point.1
-            && vec![
-                self.down_left_point.0,
-                self.down_left_point.1,
-                self.up_right_point.0,
-                self.up_right_point.1,
-            ]
-            .iter()
-            .all(|e| *e < fluid_container_size)
+    /// Get down left and up right point using which the obstacle is approximated.
+    pub fn get_approximate_points(&mut self) -> &mut Vec<line_drawing::Point<i64>> {
+        &mut self.approximate_points
     }
 }
 
 impl Obstacle for Rectangle {
-    fn get_approximate_points(&mut self) -> &mut Vec<line_drawing::Point<i64>> {
-        &mut self.approximate_points
+    fn bounding_box(&self) -> (line_drawing::Point<i64>, line_drawing::Point<i64>) {
+        (self.approximate_points[0], self.approximate_points[1])
+    }
+
+    fn contains(&self, cell: line_drawing::Point<i64>) -> bool {
+        let (down_left_point, up_right_point) = self.bounding_box();
+        (down_left_point.0..up_right_point.0).contains(&cell.0)
+            && (down_left_point.1..up_right_point.1).contains(&cell.1)
+    }
+
+    fn are_all_points_valid(
+        &self,
+        fluid_container_width: i64,
+        fluid_container_height: i64,
+    ) -> bool {
+        let (down_left_point, up_right_point) = self.bounding_box();
+        down_left_point.0 != up_right_point.0
+            && down_left_point.1 != up_right_point.1
+            && down_left_point.0 < up_right_point.0
+            && down_left_point.1 < up_right_point.1
+            && [down_left_point.0, up_right_point.0]
+                .iter()
+                .all(|e| *e < fluid_container_width)
+            && [down_left_point.1, up_right_point.1]
+                .iter()
+                .all(|e| *e < fluid_container_height)
+    }
+}
+
+/// Circle obstacle defined by its center and radius.
+#[derive(Clone, PartialEq, Serialize, Deserialize)]
+pub struct Circle {
+    /// The center of the circle
+    pub center: line_drawing::Point<i64>,
+    /// The radius of the circle
+    pub radius: i64,
+}
+
+impl Default for Circle {
+    fn default() -> Self {
+        let configs = SimulationConfigs::default();
+        Self::new((40, 40), 10, configs.width, configs.height)
+    }
+}
+
+impl Circle {
+    /// Create new Circle
+    pub fn new(
+        center: line_drawing::Point<i64>,
+        radius: i64,
+        fluid_container_width: u32,
+        fluid_container_height: u32,
+    ) -> Self {
+        let result = Self { center, radius };
+
+        if !result.are_all_points_valid(
+            i64::from(fluid_container_width),
+            i64::from(fluid_container_height),
+        ) {
+            panic!("Invalid input for Circle");
+        }
+
+        result
+    }
+}
+
+impl Obstacle for Circle {
+    fn bounding_box(&self) -> (line_drawing::Point<i64>, line_drawing::Point<i64>) {
+        (
+            (self.center.0 - self.radius, self.center.1 - self.radius),
+            (self.center.0 + self.radius, self.center.1 + self.radius),
+        )
+    }
+
+    fn contains(&self, cell: line_drawing::Point<i64>) -> bool {
+        let (x, y) = cell_center(cell);
+        let (dx, dy) = (x - self.center.0 as f64, y - self.center.1 as f64);
+        dx * dx + dy * dy <= (self.radius * self.radius) as f64
+    }
+
+    fn are_all_points_valid(
+        &self,
+        fluid_container_width: i64,
+        fluid_container_height: i64,
+    ) -> bool {
+        let (down_left_point, up_right_point) = self.bounding_box();
+        self.radius > 0
+            && is_point_inside_container(
+                down_left_point,
+                fluid_container_width,
+                fluid_container_height,
+            )
+            && is_point_inside_container(
+                up_right_point,
+                fluid_container_width,
+                fluid_container_height,
+            )
+    }
+}
+
+/// Ellipse obstacle defined by its center, its two semi-axes and the angle by which it is rotated
+/// counterclockwise around its center.
+#[derive(Clone, PartialEq, Serialize, Deserialize)]
+pub struct Ellipse {
+    /// The center of the ellipse
+    pub center: line_drawing::Point<i64>,
+    /// The semi-axis parallel to Ox before the rotation
+    pub radius_x: i64,
+    /// The semi-axis parallel to Oy before the rotation
+    pub radius_y: i64,
+    /// The rotation of the ellipse in degrees
+    pub rotation: f32,
+}
+
+impl Default for Ellipse {
+    fn default() -> Self {
+        let configs = SimulationConfigs::default();
+        Self::new((40, 90), 15, 6, 0.0, configs.width, configs.height)
+    }
+}
+
+impl Ellipse {
+    /// Create new Ellipse
+    pub fn new(
+        center: line_drawing::Point<i64>,
+        radius_x: i64,
+        radius_y: i64,
+        rotation: f32,
+        fluid_container_width: u32,
+        fluid_container_height: u32,
+    ) -> Self {
+        let result = Self {
+            center,
+            radius_x,
+            radius_y,
+            rotation,
+        };
+
+        if !result.are_all_points_valid(
+            i64::from(fluid_container_width),
+            i64::from(fluid_container_height),
+        ) {
+            panic!("Invalid input for Ellipse");
+        }
+
+        result
+    }
+}
+
+impl Obstacle for Ellipse {
+    fn bounding_box(&self) -> (line_drawing::Point<i64>, line_drawing::Point<i64>) {
+        let (sin, cos) = (self.rotation as f64).to_radians().sin_cos();
+        let (radius_x, radius_y) = (self.radius_x as f64, self.radius_y as f64);
+
+        let half_width = (radius_x * radius_x * cos * cos + radius_y * radius_y * sin * sin)
+            .sqrt()
+            .ceil() as i64;
+        let half_height = (radius_x * radius_x * sin * sin + radius_y * radius_y * cos * cos)
+            .sqrt()
+            .ceil() as i64;
+
+        (
+            (self.center.0 - half_width, self.center.1 - half_height),
+            (self.center.0 + half_width, self.center.1 + half_height),
+        )
+    }
+
+    fn contains(&self, cell: line_drawing::Point<i64>) -> bool {
+        let (x, y) = cell_center(cell);
+        let (dx, dy) = (x - self.center.0 as f64, y - self.center.1 as f64);
+
+        // Rotate the cell back, so the ellipse's axes are parallel to the coordinate system.
+        let (sin, cos) = (self.rotation as f64).to_radians().sin_cos();
+        let (u, v) = (dx * cos + dy * sin, -dx * sin + dy * cos);
+
+        let (radius_x, radius_y) = (self.radius_x as f64, self.radius_y as f64);
+        (u * u) / (radius_x * radius_x) + (v * v) / (radius_y * radius_y) <= 1.0
+    }
+
+    fn are_all_points_valid(
+        &self,
+        fluid_container_width: i64,
+        fluid_container_height: i64,
+    ) -> bool {
+        let (down_left_point, up_right_point) = self.bounding_box();
+        self.radius_x > 0
+            && self.radius_y > 0
+            && is_point_inside_container(
+                down_left_point,
+                fluid_container_width,
+                fluid_container_height,
+            )
+            && is_point_inside_container(
+                up_right_point,
+                fluid_container_width,
+                fluid_container_height,
+            )
+    }
+}
+
+/// Polygon obstacle defined by its vertices. The polygon is closed automatically, i.e. the last
+/// vertex is connected to the first one.
+#[derive(Clone, PartialEq, Serialize, Deserialize)]
+pub struct Polygon {
+    /// The vertices of the polygon in the order they are connected
+    pub vertices: Vec<line_drawing::Point<i64>>,
+}
+
+impl Default for Polygon {
+    fn default() -> Self {
+        let configs = SimulationConfigs::default();
+        Self::new(
+            vec![(60, 30), (90, 30), (75, 55)],
+            configs.width,
+            configs.height,
+        )
+    }
+}
+
+impl Polygon {
+    /// Create new Polygon
+    pub fn new(
+        vertices: Vec<line_drawing::Point<i64>>,
+        fluid_container_width: u32,
+        fluid_container_height: u32,
+    ) -> Self {
+        let result = Self { vertices };
+
+        if !result.are_all_points_valid(
+            i64::from(fluid_container_width),
+            i64::from(fluid_container_height),
+        ) {
+            panic!("Invalid input for Polygon");
+        }
+
+        result
+    }
+
+    fn to_geo_polygon(&self) -> geo::Polygon<f64> {
+        geo::Polygon::new(
+            geo::LineString::from(
+                self.vertices
+                    .iter()
+                    .map(|vertex| (vertex.0 as f64, vertex.1 as f64))
+                    .collect::<Vec<_>>(),
+            ),
+            vec![],
+        )
+    }
+}
+
+impl Obstacle for Polygon {
+    fn bounding_box(&self) -> (line_drawing::Point<i64>, line_drawing::Point<i64>) {
+        match self.to_geo_polygon().bounding_rect() {
+            Some(rect) => (
+                (rect.min().x.floor() as i64, rect.min().y.floor() as i64),
+                (rect.max().x.ceil() as i64, rect.max().y.ceil() as i64),
+            ),
+            None => ((0, 0), (0, 0)),
+        }
+    }
+
+    fn contains(&self, cell: line_drawing::Point<i64>) -> bool {
+        let (x, y) = cell_center(cell);
+        self.to_geo_polygon().contains(&geo::Point::new(x, y))
+    }
+
+    fn are_all_points_valid(
+        &self,
+        fluid_container_width: i64,
+        fluid_container_height: i64,
+    ) -> bool {
+        self.vertices.len() >= 3
+            && self.vertices.iter().all(|vertex| {
+                is_point_inside_container(*vertex, fluid_container_width, fluid_container_height)
+            })
+    }
+}
+
+/// Obstacle loaded from a bitmap image (e.g. PNG or BMP). The image is resampled to `scale` times
+/// the width and the height of the fluid's container, and then moved by `offset` cells. Every
+/// pixel darker than the `threshold` becomes a wall.
+#[derive(Clone, Serialize, Deserialize)]
+pub struct Mask {
+    /// The path of the image file
+    pub path: String,
+    /// The size of the mask as regards the size of the fluid's container, i.e. `1.0` stretches
+    /// the image over the whole container
+    pub scale: f32,
+    /// The position of the image's up left corner in the fluid's container
+    pub offset: line_drawing::Point<i64>,
+    /// Pixels with luminance below the threshold are obstacle cells
+    pub threshold: u8,
+    /// The image resampled to the current fluid's container. Every element shows if the
+    /// corresponding pixel is dark enough to be a wall. See [`Obstacle::fit_into_container()`].
+    #[serde(skip)]
+    cells: Vec<bool>,
+    /// The width and the height of the resampled image in cells
+    #[serde(skip)]
+    dimensions: (i64, i64),
+}
+
+impl PartialEq for Mask {
+    /// Compares only the parameters of the masks, not the images resampled from them.
+    fn eq(&self, other: &Self) -> bool {
+        self.path == other.path
+            && self.scale == other.scale
+            && self.offset == other.offset
+            && self.threshold == other.threshold
+    }
+}
+
+impl Default for Mask {
+    fn default() -> Self {
+        Self {
+            path: "obstacle_mask.png".to_string(),
+            scale: 1.0,
+            offset: (0, 0),
+            threshold: 128,
+            cells: Vec::new(),
+            dimensions: (0, 0),
+        }
+    }
+}
+
+impl Mask {
+    /// Create new Mask
+    pub fn new(path: &str, scale: f32, offset: line_drawing::Point<i64>, threshold: u8) -> Self {
+        Self {
+            path: path.to_string(),
+            scale,
+            offset,
+            threshold,
+            ..Mask::default()
+        }
+    }
+
+    /// The length in cells of the resampled image's side for a side of the fluid container with
+    /// the given length
+    fn side_for(&self, fluid_container_side: u32) -> i64 {
+        (self.scale * fluid_container_side as f32).round().max(0.0) as i64
+    }
+}
+
+impl Obstacle for Mask {
+    fn bounding_box(&self) -> (line_drawing::Point<i64>, line_drawing::Point<i64>) {
+        (
+            self.offset,
+            (
+                self.offset.0 + self.dimensions.0,
+                self.offset.1 + self.dimensions.1,
+            ),
+        )
+    }
+
+    fn contains(&self, cell: line_drawing::Point<i64>) -> bool {
+        let (x, y) = (cell.0 - self.offset.0, cell.1 - self.offset.1);
+        (0..self.dimensions.0).contains(&x)
+            && (0..self.dimensions.1).contains(&y)
+            && self.cells[(x + y * self.dimensions.0) as usize]
+    }
+
+    fn are_all_points_valid(
+        &self,
+        fluid_container_width: i64,
+        fluid_container_height: i64,
+    ) -> bool {
+        self.scale > 0.0
+            && is_point_inside_container(self.offset, fluid_container_width, fluid_container_height)
+            && image::image_dimensions(&self.path).is_ok()
+    }
+
+    fn fit_into_container(&mut self, fluid_container_width: u32, fluid_container_height: u32) {
+        self.dimensions = (
+            self.side_for(fluid_container_width),
+            self.side_for(fluid_container_height),
+        );
+        self.cells.clear();
+
+        let image = match image::open(&self.path) {
+            Ok(image) => image,
+            Err(error) => {
+                error!("Couldn't load obstacle mask {}: {}", self.path, error);
+                self.dimensions = (0, 0);
+                return;
+            }
+        };
+
+        let (width, height) = (self.dimensions.0 as u32, self.dimensions.1 as u32);
+        let resampled = image
+            .resize_exact(width, height, image::imageops::FilterType::Triangle)
+            .into_luma8();
+
+        self.cells = resampled
+            .pixels()
+            .map(|pixel| pixel.0[0] < self.threshold)
+            .collect();
     }
 }
 
 #[cfg(test)]
 mod tests {
-    use crate::simulation::obstacle::Rectangle;
+    use crate::simulation::obstacle::{Circle, Ellipse, Mask, Obstacle, Polygon, Rectangle};
+
+    fn covered_cells(obstacle: &dyn Obstacle) -> Vec<line_drawing::Point<i64>> {
+        let (down_left_point, up_right_point) = obstacle.bounding_box();
+        let mut result = Vec::new();
+        for x in down_left_point.0..up_right_point.0 {
+            for y in down_left_point.1..up_right_point.1 {
+                if obstacle.contains((x, y)) {
+                    result.push((x, y));
+                }
+            }
+        }
+        result
+    }
 
     #[test]
     #[should_panic]
@@ -103,7 +629,7 @@ mod tests {
     fn rectangle_wrong_parameters_on_creation_panics() {
         let up_left = (50, 120);
         let down_right = (127, 110);
-        Rectangle::new(up_left, down_right, 128);
+        Rectangle::new(up_left, down_right, 128, 128);
     }
 
     #[test]
@@ -111,6 +637,94 @@ mod tests {
     fn rectangle_swapped_parameters_panics() {
         let down_left_point = (10, 10);
         let up_right_point = (12, 12);
-        Rectangle::new(up_right_point, down_left_point, 128);
+        Rectangle::new(up_right_point, down_left_point, 128, 128);
+    }
+
+    #[test]
+    fn rectangle_fits_into_wide_container() {
+        let rectangle = Rectangle::new((300, 10), (400, 100), 512, 128);
+        assert!(rectangle.are_all_points_valid(512, 128));
+        assert!(!rectangle.are_all_points_valid(128, 512));
+        assert!(!rectangle.are_all_points_valid(512, 64));
+    }
+
+    #[test]
+    #[should_panic]
+    fn circle_outside_container_panics() {
+        Circle::new((5, 64), 10, 128, 128);
+    }
+
+    #[test]
+    fn circle_contains() {
+        let circle = Circle::new((64, 64), 10, 128, 128);
+
+        assert!(circle.contains((64, 64)));
+        assert!(circle.contains((54, 64)));
+        assert!(!circle.contains((53, 64)));
+        assert!(!circle.contains((72, 72)));
+
+        // The area of the rasterized circle should be close to the real one.
+        let area = std::f64::consts::PI * 100.0;
+        assert!((covered_cells(&circle).len() as f64 - area).abs() < 0.05 * area);
+    }
+
+    #[test]
+    fn rotated_ellipse_swaps_its_axes() {
+        let ellipse = Ellipse::new((64, 64), 20, 5, 0.0, 128, 128);
+        let rotated = Ellipse::new((64, 64), 20, 5, 90.0, 128, 128);
+
+        assert!(ellipse.contains((80, 64)));
+        assert!(!ellipse.contains((64, 80)));
+        assert!(rotated.contains((64, 80)));
+        assert!(!rotated.contains((80, 64)));
+
+        assert_eq!(covered_cells(&ellipse).len(), covered_cells(&rotated).len());
+    }
+
+    #[test]
+    fn square_polygon_covers_same_cells_as_rectangle() {
+        let rectangle = Rectangle::new((10, 20), (30, 25), 128, 128);
+        let polygon = Polygon::new(vec![(10, 20), (30, 20), (30, 25), (10, 25)], 128, 128);
+
+        assert_eq!(covered_cells(&rectangle), covered_cells(&polygon));
+    }
+
+    #[test]
+    fn polygon_needs_three_vertices() {
+        let polygon = Polygon {
+            vertices: vec![(10, 20), (30, 20)],
+        };
+        assert!(!polygon.are_all_points_valid(128, 128));
+    }
+
+    #[test]
+    fn mask_marks_dark_pixels() {
+        // A 4x4 image with a dark left half
+        let image =
+            image::GrayImage::from_fn(4, 4, |x, _| image::Luma([if x < 2 { 0 } else { 255 }]));
+        let path = std::env::temp_dir().join("equilibrium_mask_marks_dark_pixels.png");
+        image.save(&path).unwrap();
+
+        let mut mask = Mask::new(path.to_str().unwrap(), 0.5, (8, 4), 128);
+        assert!(mask.are_all_points_valid(32, 16));
+
+        mask.fit_into_container(32, 16);
+        let cells = covered_cells(&mask);
+        std::fs::remove_file(&path).unwrap();
+
+        // The image is resampled to 16x8 cells, and the left half of them are walls.
+        assert_eq!(cells.len(), 8 * 8);
+        assert!(cells
+            .iter()
+            .all(|cell| (8..16).contains(&cell.0) && (4..12).contains(&cell.1)));
+    }
+
+    #[test]
+    fn mask_with_missing_file_is_invalid() {
+        let mut mask = Mask::new("there_is_no_such_mask.png", 1.0, (0, 0), 128);
+        assert!(!mask.are_all_points_valid(32, 32));
+
+        mask.fit_into_container(32, 32);
+        assert!(covered_cells(&mask).is_empty());
     }
 }
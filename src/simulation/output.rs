@@ -0,0 +1,445 @@
+use crate::simulation::configs::{AnimationConfigs, AnimationFormat, OutputFields, OutputFormat};
+use crate::simulation::fluid::{ContainerWall, Fluid};
+use std::io::Write;
+
+/// A field of the fluid which can be saved for every frame. See [`OutputFields`].
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum FrameField {
+    /// The density of the fluid
+    Density,
+    /// The x component of the fluid's velocity
+    VelocityX,
+    /// The y component of the fluid's velocity
+    VelocityY,
+    /// `1` for the walls and `0` for the cells accessible for the fluid
+    CellsMask,
+}
+
+impl FrameField {
+    /// All the fields in the order they are saved
+    pub const ALL: [FrameField; 4] = [
+        FrameField::Density,
+        FrameField::VelocityX,
+        FrameField::VelocityY,
+        FrameField::CellsMask,
+    ];
+
+    /// The name of the field. It is used as a prefix of the saved files, e.g. `velocity_x42.npy`.
+    pub fn name(&self) -> &'static str {
+        match self {
+            FrameField::Density => "density",
+            FrameField::VelocityX => "velocity_x",
+            FrameField::VelocityY => "velocity_y",
+            FrameField::CellsMask => "cells_mask",
+        }
+    }
+
+    /// Checks if the field should be saved according to the given configurations.
+    pub fn is_selected(&self, output_fields: &OutputFields) -> bool {
+        match self {
+            FrameField::Density => output_fields.density,
+            FrameField::VelocityX => output_fields.velocity_x,
+            FrameField::VelocityY => output_fields.velocity_y,
+            FrameField::CellsMask => output_fields.cells_mask,
+        }
+    }
+
+    /// The values of the field for every cell of the fluid's container, row by row.
+    pub fn values(&self, fluid: &Fluid) -> Vec<f32> {
+        match self {
+            FrameField::Density => fluid.density.clone(),
+            FrameField::VelocityX => fluid.cell_velocities().0,
+            FrameField::VelocityY => fluid.cell_velocities().1,
+            FrameField::CellsMask => fluid
+                .cells_type
+                .iter()
+                .map(|cell_type| f32::from(u8::from(*cell_type == ContainerWall::DefaultWall)))
+                .collect(),
+        }
+    }
+
+    /// Maps the values of the field into `[0, 1]`, so they can be saved as a grayscale image. The
+    /// density is clamped, since the fluid is shown the same way. The velocities are scaled by the
+    /// largest absolute velocity in the frame, so a still fluid is mid-gray.
+    fn to_unit_range(self, values: &[f32]) -> Vec<f32> {
+        match self {
+            FrameField::Density | FrameField::CellsMask => {
+                values.iter().map(|value| value.clamp(0.0, 1.0)).collect()
+            }
+            FrameField::VelocityX | FrameField::VelocityY => {
+                let max_abs = values
+                    .iter()
+                    .fold(0.0_f32, |acc, value| acc.max(value.abs()));
+                values
+                    .iter()
+                    .map(|value| {
+                        if max_abs == 0.0 {
+                            0.5
+                        } else {
+                            0.5 + 0.5 * value / max_abs
+                        }
+                    })
+                    .collect()
+            }
+        }
+    }
+}
+
+/// Creates the path of the file in which the given field of the given frame is saved, e.g.
+/// `rendered_images/density42.png`.
+///
+/// Example:
+/// ```
+/// use equilibrium::simulation::configs::OutputFormat;
+/// use equilibrium::simulation::output::{frame_path, FrameField};
+///
+/// assert_eq!(
+///     frame_path("results", FrameField::VelocityX, 7, OutputFormat::Npy),
+///     "results/velocity_x7.npy"
+/// );
+/// ```
+pub fn frame_path(
+    save_into_dir: &str,
+    field: FrameField,
+    frame_number: i64,
+    output_format: OutputFormat,
+) -> String {
+    format!(
+        "{}/{}{}.{}",
+        save_into_dir,
+        field.name(),
+        frame_number,
+        output_format.extension()
+    )
+}
+
+/// Saves the given field of the fluid into the file with the given path. The `rendered_image` is
+/// used for the density in the [`OutputFormat::Png`] and [`OutputFormat::Jpeg`] formats, so it
+/// looks the same way as in the application.
+pub fn save_field(
+    path: &str,
+    field: FrameField,
+    fluid: &Fluid,
+    rendered_image: &image::RgbaImage,
+    output_format: OutputFormat,
+) -> std::io::Result<()> {
+    let (width, height) = (
+        fluid.simulation_configs.width,
+        fluid.simulation_configs.height,
+    );
+    let values = field.values(fluid);
+
+    match output_format {
+        OutputFormat::Png if field == FrameField::Density => rendered_image
+            .save(path)
+            .map_err(|e| std::io::Error::new(std::io::ErrorKind::Other, e)),
+        // JPEG has no alpha channel
+        OutputFormat::Jpeg if field == FrameField::Density => {
+            image::DynamicImage::ImageRgba8(rendered_image.clone())
+                .to_rgb8()
+                .save(path)
+                .map_err(|e| std::io::Error::new(std::io::ErrorKind::Other, e))
+        }
+        OutputFormat::Png | OutputFormat::Jpeg => {
+            let pixels = field
+                .to_unit_range(&values)
+                .iter()
+                .map(|value| (value * f32::from(u8::MAX)).round() as u8)
+                .collect();
+            image::GrayImage::from_raw(width, height, pixels)
+                .expect("The fluid has a value for every cell")
+                .save(path)
+                .map_err(|e| std::io::Error::new(std::io::ErrorKind::Other, e))
+        }
+        OutputFormat::Gray16Png => {
+            let pixels = field
+                .to_unit_range(&values)
+                .iter()
+                .map(|value| (value * f32::from(u16::MAX)).round() as u16)
+                .collect();
+            image::ImageBuffer::<image::Luma<u16>, Vec<u16>>::from_raw(width, height, pixels)
+                .expect("The fluid has a value for every cell")
+                .save(path)
+                .map_err(|e| std::io::Error::new(std::io::ErrorKind::Other, e))
+        }
+        OutputFormat::Npy => write_npy(&mut std::fs::File::create(path)?, &values, width, height),
+        OutputFormat::Csv => write_csv(&mut std::fs::File::create(path)?, &values, width),
+    }
+}
+
+/// Writes the values as a NumPy array (format version 1.0) of little-endian 32-bit floats with
+/// the shape `(height, width)`. The first index of the array is the row, i.e. the `y` coordinate.
+pub fn write_npy(
+    writer: &mut impl Write,
+    values: &[f32],
+    width: u32,
+    height: u32,
+) -> std::io::Result<()> {
+    const MAGIC: &[u8] = b"\x93NUMPY\x01\x00";
+
+    let mut header = format!(
+        "{{'descr': '<f4', 'fortran_order': False, 'shape': ({}, {}), }}",
+        height, width
+    );
+    // The header is padded with spaces and ends with a new line, so the data is 64-byte aligned.
+    let unpadded_length = MAGIC.len() + 2 + header.len() + 1;
+    header.push_str(&" ".repeat((64 - unpadded_length % 64) % 64));
+    header.push('\n');
+
+    writer.write_all(MAGIC)?;
+    writer.write_all(&(header.len() as u16).to_le_bytes())?;
+    writer.write_all(header.as_bytes())?;
+    for value in values {
+        writer.write_all(&value.to_le_bytes())?;
+    }
+    Ok(())
+}
+
+/// Writes the values as comma-separated values, one row of the grid with `width` cells per line.
+pub fn write_csv(writer: &mut impl Write, values: &[f32], width: u32) -> std::io::Result<()> {
+    for row in values.chunks(width as usize) {
+        let line: Vec<String> = row.iter().map(|value| value.to_string()).collect();
+        writeln!(writer, "{}", line.join(","))?;
+    }
+    Ok(())
+}
+
+/// Writes the frames as a single animation according to the given configurations. See
+/// [`write_gif()`] and [`write_apng()`].
+pub fn write_animation(
+    writer: &mut impl Write,
+    frames: &[image::RgbaImage],
+    animation_configs: &AnimationConfigs,
+) -> std::io::Result<()> {
+    if frames.is_empty() {
+        return Err(std::io::Error::new(
+            std::io::ErrorKind::InvalidInput,
+            "there are no frames to be exported",
+        ));
+    }
+
+    match animation_configs.format {
+        AnimationFormat::Gif => write_gif(writer, frames, animation_configs),
+        AnimationFormat::Apng => write_apng(writer, frames, animation_configs),
+    }
+}
+
+/// Writes the frames as an animated GIF.
+pub fn write_gif(
+    writer: &mut impl Write,
+    frames: &[image::RgbaImage],
+    animation_configs: &AnimationConfigs,
+) -> std::io::Result<()> {
+    let delay = image::Delay::from_numer_denom_ms(1000, animation_configs.frame_rate.max(1));
+    // The GIF's loop count is the number of repetitions after the first play.
+    let repeat = match animation_configs.plays {
+        0 => image::codecs::gif::Repeat::Infinite,
+        plays => image::codecs::gif::Repeat::Finite(plays - 1),
+    };
+
+    let mut encoder = image::codecs::gif::GifEncoder::new(writer);
+    encoder
+        .set_repeat(repeat)
+        .map_err(|e| std::io::Error::new(std::io::ErrorKind::Other, e))?;
+    encoder
+        .encode_frames(
+            frames
+                .iter()
+                .map(|frame| image::Frame::from_parts(frame.clone(), 0, 0, delay)),
+        )
+        .map_err(|e| std::io::Error::new(std::io::ErrorKind::Other, e))
+}
+
+/// Writes the frames as an animated PNG. Every frame is encoded as a regular PNG image, whose
+/// image data is then put into the animation's frame chunks. All the frames should have the same
+/// dimensions.
+pub fn write_apng(
+    writer: &mut impl Write,
+    frames: &[image::RgbaImage],
+    animation_configs: &AnimationConfigs,
+) -> std::io::Result<()> {
+    const SIGNATURE: &[u8] = b"\x89PNG\r\n\x1a\n";
+
+    writer.write_all(SIGNATURE)?;
+
+    // Every fcTL and fdAT chunk has its own sequence number.
+    let mut sequence_number: u32 = 0;
+    for (i, frame) in frames.iter().enumerate() {
+        let mut png = Vec::new();
+        image::codecs::png::PngEncoder::new(&mut png)
+            .encode(
+                frame.as_raw(),
+                frame.width(),
+                frame.height(),
+                image::ColorType::Rgba8,
+            )
+            .map_err(|e| std::io::Error::new(std::io::ErrorKind::Other, e))?;
+
+        let chunks = png_chunks(&png[SIGNATURE.len()..])?;
+
+        if i == 0 {
+            for (chunk_type, data) in chunks
+                .iter()
+                .filter(|(chunk_type, _)| chunk_type == b"IHDR")
+            {
+                write_png_chunk(writer, chunk_type, data)?;
+            }
+
+            let mut animation_control = Vec::new();
+            animation_control.extend_from_slice(&(frames.len() as u32).to_be_bytes());
+            animation_control.extend_from_slice(&u32::from(animation_configs.plays).to_be_bytes());
+            write_png_chunk(writer, b"acTL", &animation_control)?;
+        }
+
+        let mut frame_control = Vec::new();
+        frame_control.extend_from_slice(&sequence_number.to_be_bytes());
+        frame_control.extend_from_slice(&frame.width().to_be_bytes());
+        frame_control.extend_from_slice(&frame.height().to_be_bytes());
+        // The offsets of the frame
+        frame_control.extend_from_slice(&[0; 8]);
+        // The delay of the frame is 1 / frame_rate seconds
+        frame_control.extend_from_slice(&1_u16.to_be_bytes());
+        let frame_rate = u16::try_from(animation_configs.frame_rate.max(1)).unwrap_or(u16::MAX);
+        frame_control.extend_from_slice(&frame_rate.to_be_bytes());
+        // Neither dispose nor blend the frames, since every frame covers the whole image
+        frame_control.extend_from_slice(&[0, 0]);
+        write_png_chunk(writer, b"fcTL", &frame_control)?;
+        sequence_number += 1;
+
+        for (_, data) in chunks
+            .iter()
+            .filter(|(chunk_type, _)| chunk_type == b"IDAT")
+        {
+            if i == 0 {
+                write_png_chunk(writer, b"IDAT", data)?;
+            } else {
+                let mut frame_data = sequence_number.to_be_bytes().to_vec();
+                frame_data.extend_from_slice(data);
+                write_png_chunk(writer, b"fdAT", &frame_data)?;
+                sequence_number += 1;
+            }
+        }
+    }
+
+    write_png_chunk(writer, b"IEND", &[])
+}
+
+/// Splits the PNG data (without its signature) into chunks: pairs of chunk types and data.
+fn png_chunks(mut png: &[u8]) -> std::io::Result<Vec<([u8; 4], &[u8])>> {
+    let invalid_png = || std::io::Error::new(std::io::ErrorKind::InvalidData, "invalid PNG chunk");
+
+    let mut result = Vec::new();
+    while !png.is_empty() {
+        if png.len() < 12 {
+            return Err(invalid_png());
+        }
+        let length = u32::from_be_bytes([png[0], png[1], png[2], png[3]]) as usize;
+        let chunk_type = [png[4], png[5], png[6], png[7]];
+        // The chunk consists of its length, type, data and CRC.
+        let data = png.get(8..8 + length).ok_or_else(invalid_png)?;
+        result.push((chunk_type, data));
+        png = png.get(12 + length..).ok_or_else(invalid_png)?;
+    }
+    Ok(result)
+}
+
+/// Writes a single PNG chunk, including its length and CRC.
+fn write_png_chunk(
+    writer: &mut impl Write,
+    chunk_type: &[u8; 4],
+    data: &[u8],
+) -> std::io::Result<()> {
+    let mut hasher = crc32fast::Hasher::new();
+    hasher.update(chunk_type);
+    hasher.update(data);
+
+    writer.write_all(&(data.len() as u32).to_be_bytes())?;
+    writer.write_all(chunk_type)?;
+    writer.write_all(data)?;
+    writer.write_all(&hasher.finalize().to_be_bytes())
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::simulation::configs::{AnimationConfigs, AnimationFormat};
+    use crate::simulation::output::{
+        png_chunks, write_animation, write_csv, write_npy, FrameField,
+    };
+    use image::AnimationDecoder;
+
+    fn test_frames() -> Vec<image::RgbaImage> {
+        (0..3)
+            .map(|i| image::RgbaImage::from_pixel(4, 4, image::Rgba([i * 100, 0, 0, 255])))
+            .collect()
+    }
+
+    #[test]
+    fn npy_header_is_aligned() {
+        let mut buffer = Vec::new();
+        write_npy(&mut buffer, &[0.0, 1.0, 2.0, 4.0, 5.0, 3.5], 3, 2).unwrap();
+
+        let header_length = u16::from_le_bytes([buffer[8], buffer[9]]) as usize;
+        assert_eq!((10 + header_length) % 64, 0);
+        assert_eq!(buffer[10 + header_length - 1], b'\n');
+        assert!(String::from_utf8_lossy(&buffer[10..10 + header_length]).contains("(2, 3)"));
+        assert_eq!(buffer.len(), 10 + header_length + 6 * 4);
+        assert_eq!(&buffer[buffer.len() - 4..], &3.5_f32.to_le_bytes());
+    }
+
+    #[test]
+    fn csv_has_a_line_per_row() {
+        let mut buffer = Vec::new();
+        write_csv(&mut buffer, &[0.0, 1.0, 2.0, 3.5, 4.0, 5.0], 3).unwrap();
+
+        assert_eq!(String::from_utf8(buffer).unwrap(), "0,1,2\n3.5,4,5\n");
+    }
+
+    #[test]
+    fn still_fluid_is_mid_gray() {
+        let values = FrameField::VelocityX.to_unit_range(&[0.0, 2.0, -1.0]);
+        assert_eq!(values, vec![0.5, 1.0, 0.25]);
+        assert_eq!(FrameField::VelocityY.to_unit_range(&[0.0]), vec![0.5]);
+    }
+
+    #[test]
+    fn gif_has_all_frames() {
+        let mut buffer = Vec::new();
+        write_animation(&mut buffer, &test_frames(), &AnimationConfigs::default()).unwrap();
+
+        let decoder = image::codecs::gif::GifDecoder::new(buffer.as_slice()).unwrap();
+        let frames = decoder.into_frames().collect_frames().unwrap();
+        assert_eq!(frames.len(), 3);
+        assert_eq!(frames[2].buffer().get_pixel(1, 1)[0], 200);
+    }
+
+    #[test]
+    fn apng_has_all_frames() {
+        let animation_configs = AnimationConfigs {
+            format: AnimationFormat::Apng,
+            frame_rate: 10,
+            plays: 2,
+        };
+        let mut buffer = Vec::new();
+        write_animation(&mut buffer, &test_frames(), &animation_configs).unwrap();
+
+        let chunk_types: Vec<_> = png_chunks(&buffer[8..])
+            .unwrap()
+            .iter()
+            .map(|(chunk_type, _)| String::from_utf8_lossy(chunk_type).to_string())
+            .collect();
+        assert_eq!(
+            chunk_types,
+            ["IHDR", "acTL", "fcTL", "IDAT", "fcTL", "fdAT", "fcTL", "fdAT", "IEND"]
+        );
+
+        // Decoders which don't support animations show the first frame.
+        let first_frame = image::load_from_memory(&buffer).unwrap().into_rgba8();
+        assert_eq!(first_frame, test_frames()[0]);
+    }
+
+    #[test]
+    fn animation_needs_frames() {
+        let mut buffer = Vec::new();
+        assert!(write_animation(&mut buffer, &[], &AnimationConfigs::default()).is_err());
+    }
+}
@@ -0,0 +1,265 @@
+/// A contiguous range of the inner rows of a field, handed over to a single thread by
+/// [`Workers::for_each_rows_chunk()`]. Besides its own rows, the chunk can read the rows right above and
+/// below it, as they were when the threads started.
+pub struct RowsChunk<'a> {
+    width: u32,
+    first_row: u32,
+    rows: &'a mut [f32],
+    above: &'a [f32],
+    below: &'a [f32],
+}
+
+impl RowsChunk<'_> {
+    /// The rows owned by the chunk
+    pub fn rows(&self) -> std::ops::Range<u32> {
+        self.first_row..self.first_row + self.rows.len() as u32 / self.width
+    }
+
+    /// The value of the cell. The cell must be in the chunk's rows or in the rows right above and
+    /// below them.
+    pub fn get(&self, i: u32, j: u32) -> f32 {
+        let rows = self.rows();
+        if j + 1 == rows.start {
+            self.above[i as usize]
+        } else if j == rows.end {
+            self.below[i as usize]
+        } else {
+            self.rows[((j - rows.start) * self.width + i) as usize]
+        }
+    }
+
+    /// Sets the value of the cell. The cell must be in the chunk's rows.
+    pub fn set(&mut self, i: u32, j: u32, value: f32) {
+        self.rows[((j - self.first_row) * self.width + i) as usize] = value;
+    }
+}
+
+/// Threads shared by all the passes of a solve, e.g. by the sweeps of an iterative solver. The
+/// threads are spawned once, when the workers are created, and every pass hands its chunks over to
+/// them. With a single thread, the kernels are run on the current thread.
+pub struct Workers {
+    threads: u32,
+    pool: Option<scoped_threadpool::Pool>,
+    /// The copy of the values made by [`Workers::for_each_chunk_with_snapshot()`], kept around so
+    /// it isn't allocated for every pass
+    snapshot: Vec<f32>,
+}
+
+impl Workers {
+    /// Spawns the given number of threads, unless there's just one of them.
+    pub fn new(threads: u32) -> Self {
+        let threads = threads.max(1);
+        Self {
+            threads,
+            pool: (threads > 1).then(|| scoped_threadpool::Pool::new(threads)),
+            snapshot: Vec::new(),
+        }
+    }
+
+    /// Splits the inner rows of the field with `width` cells in each of its `height` rows (all the
+    /// rows but the first and the last one) into chunks of about the same size, one for every
+    /// thread, and runs the kernel on every chunk in a separate thread.
+    ///
+    /// The kernel must not depend on the order in which the chunks are processed, e.g. it should
+    /// only read the cells of the field which no chunk writes. This way the result doesn't depend
+    /// on the number of threads.
+    pub fn for_each_rows_chunk<F>(&mut self, values: &mut [f32], width: u32, height: u32, kernel: F)
+    where
+        F: Fn(RowsChunk<'_>) + Sync,
+    {
+        let inner_rows = height.saturating_sub(2);
+        let threads = self.threads.clamp(1, inner_rows.max(1));
+        let pool = match &mut self.pool {
+            Some(pool) if threads > 1 => pool,
+            _ => {
+                let (above, rest) = values.split_at_mut(width as usize);
+                let (rows, below) = rest.split_at_mut((inner_rows * width) as usize);
+                kernel(RowsChunk {
+                    width,
+                    first_row: 1,
+                    rows,
+                    above,
+                    below,
+                });
+                return;
+            }
+        };
+
+        // The rows around every chunk are copied, since they may belong to other chunks.
+        let mut chunks_bounds = Vec::with_capacity(threads as usize);
+        let mut halos = Vec::with_capacity(threads as usize);
+        let mut first_row = 1;
+        for chunk in 0..threads {
+            let rows_count = inner_rows / threads + u32::from(chunk < inner_rows % threads);
+            let row = |j: u32| values[(j * width) as usize..((j + 1) * width) as usize].to_vec();
+            halos.push((row(first_row - 1), row(first_row + rows_count)));
+            chunks_bounds.push((first_row, rows_count));
+            first_row += rows_count;
+        }
+
+        let kernel = &kernel;
+        pool.scoped(|scope| {
+            let mut rest = &mut values[width as usize..];
+            for ((first_row, rows_count), (above, below)) in
+                chunks_bounds.into_iter().zip(halos.iter())
+            {
+                let (rows, next) =
+                    std::mem::take(&mut rest).split_at_mut((rows_count * width) as usize);
+                rest = next;
+                scope.execute(move || {
+                    kernel(RowsChunk {
+                        width,
+                        first_row,
+                        rows,
+                        above,
+                        below,
+                    })
+                });
+            }
+        });
+    }
+
+    /// Splits the values into contiguous chunks of about the same size, one for every thread, and
+    /// runs the kernel on every chunk in a separate thread. The kernel gets the index of the first
+    /// value of the chunk and the chunk itself.
+    ///
+    /// Just as for [`Workers::for_each_rows_chunk()`], the kernel must not depend on the order in
+    /// which the chunks are processed.
+    pub fn for_each_chunk<F>(&mut self, values: &mut [f32], kernel: F)
+    where
+        F: Fn(usize, &mut [f32]) + Sync,
+    {
+        let threads = (self.threads as usize).clamp(1, values.len().max(1));
+        let pool = match &mut self.pool {
+            Some(pool) if threads > 1 => pool,
+            _ => {
+                kernel(0, values);
+                return;
+            }
+        };
+
+        let chunk_size = (values.len() + threads - 1) / threads;
+        let kernel = &kernel;
+        pool.scoped(|scope| {
+            for (k, chunk) in values.chunks_mut(chunk_size).enumerate() {
+                scope.execute(move || kernel(k * chunk_size, chunk));
+            }
+        });
+    }
+
+    /// Same as [`Workers::for_each_chunk()`], but the kernel also gets all the values as they were
+    /// before the pass, so it can read the values written by the other chunks.
+    pub fn for_each_chunk_with_snapshot<F>(&mut self, values: &mut [f32], kernel: F)
+    where
+        F: Fn(&[f32], usize, &mut [f32]) + Sync,
+    {
+        let mut snapshot = std::mem::take(&mut self.snapshot);
+        snapshot.clear();
+        snapshot.extend_from_slice(values);
+        self.for_each_chunk(values, |offset, chunk| kernel(&snapshot, offset, chunk));
+        self.snapshot = snapshot;
+    }
+}
+
+/// Runs the kernel on the chunks of the inner rows of the field with [`Workers`] spawned just for
+/// this pass. See [`Workers::for_each_rows_chunk()`].
+pub fn for_each_rows_chunk<F>(values: &mut [f32], width: u32, height: u32, threads: u32, kernel: F)
+where
+    F: Fn(RowsChunk<'_>) + Sync,
+{
+    Workers::new(threads).for_each_rows_chunk(values, width, height, kernel);
+}
+
+/// Runs the kernel on the chunks of the values with [`Workers`] spawned just for this pass. See
+/// [`Workers::for_each_chunk()`].
+pub fn for_each_chunk<F>(values: &mut [f32], threads: u32, kernel: F)
+where
+    F: Fn(usize, &mut [f32]) + Sync,
+{
+    Workers::new(threads).for_each_chunk(values, kernel);
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::simulation::parallel::{for_each_chunk, for_each_rows_chunk, Workers};
+
+    /// Sets every cell with even `i + j` to the sum of its neighbours, which are all odd.
+    fn sum_odd_neighbours(values: &mut [f32], width: u32, height: u32, threads: u32) {
+        for_each_rows_chunk(values, width, height, threads, |mut chunk| {
+            for j in chunk.rows() {
+                for i in (2 - j % 2..width - 1).step_by(2) {
+                    let value = chunk.get(i - 1, j)
+                        + chunk.get(i + 1, j)
+                        + chunk.get(i, j - 1)
+                        + chunk.get(i, j + 1);
+                    chunk.set(i, j, value);
+                }
+            }
+        });
+    }
+
+    #[test]
+    fn result_does_not_depend_on_threads() {
+        let (width, height) = (7, 9);
+        let values: Vec<f32> = (0..width * height)
+            .map(|idx| (idx * idx % 13) as f32)
+            .collect();
+
+        let mut expected = values.clone();
+        for j in 1..height - 1 {
+            for i in 1..width - 1 {
+                if (i + j) % 2 == 0 {
+                    let idx = |i: u32, j: u32| (j * width + i) as usize;
+                    expected[idx(i, j)] = values[idx(i - 1, j)]
+                        + values[idx(i + 1, j)]
+                        + values[idx(i, j - 1)]
+                        + values[idx(i, j + 1)];
+                }
+            }
+        }
+
+        // More threads than rows are fine as well
+        for threads in 1..=10 {
+            let mut result = values.clone();
+            sum_odd_neighbours(&mut result, width, height, threads);
+            assert_eq!(result, expected, "{} threads", threads);
+        }
+    }
+
+    #[test]
+    fn chunks_cover_all_values() {
+        for threads in 1..=10 {
+            let mut values = vec![0.0; 7];
+            for_each_chunk(&mut values, threads, |offset, chunk| {
+                for (k, value) in chunk.iter_mut().enumerate() {
+                    *value += (offset + k) as f32;
+                }
+            });
+            assert_eq!(values, [0.0, 1.0, 2.0, 3.0, 4.0, 5.0, 6.0]);
+        }
+    }
+
+    #[test]
+    fn snapshot_keeps_the_values_before_every_pass() {
+        // Every pass sets the values to the sums of their neighbours before the pass
+        let sweep = |threads: u32| {
+            let mut workers = Workers::new(threads);
+            let mut values: Vec<f32> = (0..11).map(|value| value as f32).collect();
+            for _ in 0..3 {
+                workers.for_each_chunk_with_snapshot(&mut values, |previous, offset, chunk| {
+                    for (k, value) in chunk.iter_mut().enumerate() {
+                        let idx = offset + k;
+                        *value = previous[idx.saturating_sub(1)]
+                            + previous[(idx + 1).min(previous.len() - 1)];
+                    }
+                });
+            }
+            values
+        };
+
+        let expected = sweep(1);
+        for threads in 2..=5 {
+            assert_eq!(sweep(threads), expected, "{} threads", threads);
+        }
+    }
+}
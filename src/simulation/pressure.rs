@@ -0,0 +1,676 @@
+use crate::simulation::configs::{
+    BoundaryCondition, PressureSolver, SimulationConfigs, SolverStopCondition,
+};
+use crate::simulation::fluid::ContainerWall;
+use crate::simulation::parallel::Workers;
+
+/// The weight of the Jacobi iterations. Without damping, the checkerboard pattern of the error
+/// flips its sign on every iteration instead of fading away, since no edge has a fixed pressure.
+const JACOBI_WEIGHT: f32 = 2.0 / 3.0;
+
+/// The number of red-black Gauss-Seidel sweeps done on every level of the multigrid before and
+/// after the correction from the coarser level
+const SMOOTHING_SWEEPS: u32 = 2;
+
+/// The factor of the corrections brought from the coarser levels of the multigrid. Copying a
+/// coarse cell into all the cells it covers makes the correction too smooth, so it falls short of
+/// the solution about twice.
+const OVER_CORRECTION: f32 = 2.0;
+
+/// The number of red-black Gauss-Seidel sweeps which solve the equation on the coarsest level of
+/// the multigrid
+const COARSEST_SWEEPS: u32 = 32;
+
+/// The outcome of a solve of the pressure equation. See [`Fluid::pressure_solve`].
+///
+/// [`Fluid::pressure_solve`]: crate::simulation::fluid::Fluid::pressure_solve
+#[derive(Copy, Clone, PartialEq, Debug, Default)]
+pub struct PressureSolve {
+    /// The number of performed iterations
+    pub iterations: u32,
+    /// The largest absolute residual of the equation in the fluid's cells after the last
+    /// iteration
+    pub residual: f32,
+}
+
+/// The inner cells of the fluid's container (or a coarser version of them for the multigrid)
+/// together with the discrete Laplace operator on their fluid cells. The neighbours of a cell
+/// which are walls or lie beyond a closed edge are skipped, i.e. the pressure gradient across
+/// them is zero. Beyond a periodic edge, the neighbour is the cell on the opposite edge.
+///
+/// Every neighbour has a weight, which is `1.0` for the cells of the container. The operator
+/// applied on the values `x` in a cell is `diagonal * x - sum(weight * x_neighbour)`.
+struct Grid {
+    width: usize,
+    height: usize,
+    is_periodic_x: bool,
+    is_periodic_y: bool,
+    is_fluid: Vec<bool>,
+    /// The fluid neighbours of every cell. Only the first `neighbours_count` of them are used.
+    neighbours: Vec<[usize; 4]>,
+    weights: Vec<[f32; 4]>,
+    neighbours_count: Vec<u8>,
+    /// The sum of the weights of every cell's neighbours
+    diagonal: Vec<f32>,
+}
+
+impl Grid {
+    fn new(
+        width: usize,
+        height: usize,
+        is_fluid: Vec<bool>,
+        is_periodic_x: bool,
+        is_periodic_y: bool,
+    ) -> Self {
+        let mut result = Self {
+            width,
+            height,
+            is_periodic_x,
+            is_periodic_y,
+            is_fluid,
+            neighbours: vec![[0; 4]; width * height],
+            weights: vec![[0.0; 4]; width * height],
+            neighbours_count: vec![0; width * height],
+            diagonal: vec![0.0; width * height],
+        };
+
+        for y in 0..height {
+            for x in 0..width {
+                let idx = x + y * width;
+                if !result.is_fluid[idx] {
+                    continue;
+                }
+
+                let candidates = [
+                    Grid::neighbour(x, -1, width, is_periodic_x).map(|x| x + y * width),
+                    Grid::neighbour(x, 1, width, is_periodic_x).map(|x| x + y * width),
+                    Grid::neighbour(y, -1, height, is_periodic_y).map(|y| x + y * width),
+                    Grid::neighbour(y, 1, height, is_periodic_y).map(|y| x + y * width),
+                ];
+                for candidate in candidates.into_iter().flatten() {
+                    if result.is_fluid[candidate] {
+                        result.connect(idx, candidate, 1.0);
+                    }
+                }
+            }
+        }
+        result
+    }
+
+    /// Adds the weight to the connection from the cell to its neighbour. A cell isn't connected to
+    /// itself.
+    fn connect(&mut self, idx: usize, neighbour: usize, weight: f32) {
+        if idx == neighbour {
+            return;
+        }
+
+        let count = self.neighbours_count[idx] as usize;
+        match self.neighbours[idx][..count]
+            .iter()
+            .position(|&other| other == neighbour)
+        {
+            Some(k) => self.weights[idx][k] += weight,
+            None => {
+                self.neighbours[idx][count] = neighbour;
+                self.weights[idx][count] = weight;
+                self.neighbours_count[idx] += 1;
+            }
+        }
+        self.diagonal[idx] += weight;
+    }
+
+    /// The coordinate next to the given one along an axis with the given length, if any.
+    fn neighbour(coordinate: usize, step: i64, length: usize, is_periodic: bool) -> Option<usize> {
+        let next = coordinate as i64 + step;
+        if is_periodic {
+            Some(next.rem_euclid(length as i64) as usize)
+        } else if (0..length as i64).contains(&next) {
+            Some(next as usize)
+        } else {
+            None
+        }
+    }
+
+    /// Checks if the equation of the cell has to be solved, i.e. the cell is fluid and it isn't
+    /// cut off from all its neighbours.
+    fn is_solved(&self, idx: usize) -> bool {
+        self.is_fluid[idx] && self.neighbours_count[idx] > 0
+    }
+
+    /// The weighted sum of the values of the cell's neighbours
+    fn neighbours_sum(&self, values: &[f32], idx: usize) -> f32 {
+        let count = self.neighbours_count[idx] as usize;
+        self.neighbours[idx][..count]
+            .iter()
+            .zip(&self.weights[idx][..count])
+            .map(|(&neighbour, weight)| weight * values[neighbour])
+            .sum()
+    }
+
+    /// The Laplace operator applied on the values in the given cell
+    fn apply(&self, values: &[f32], idx: usize) -> f32 {
+        self.diagonal[idx] * values[idx] - self.neighbours_sum(values, idx)
+    }
+
+    /// Sets the residuals `b - A x` of every solved cell. The rest of them are zero.
+    fn residuals(&self, x: &[f32], b: &[f32], workers: &mut Workers) -> Vec<f32> {
+        let mut result = vec![0.0; x.len()];
+        workers.for_each_chunk(&mut result, |offset, chunk| {
+            for (k, residual) in chunk.iter_mut().enumerate() {
+                let idx = offset + k;
+                if self.is_solved(idx) {
+                    *residual = b[idx] - self.apply(x, idx);
+                }
+            }
+        });
+        result
+    }
+
+    /// The largest absolute residual of the solved cells
+    fn max_residual(&self, x: &[f32], b: &[f32], workers: &mut Workers) -> f32 {
+        self.residuals(x, b, workers)
+            .iter()
+            .fold(0.0, |acc, residual| acc.max(residual.abs()))
+    }
+
+    /// A weighted Jacobi iteration. Every cell is updated from the values of the previous
+    /// iteration. See [`JACOBI_WEIGHT`].
+    fn jacobi(&self, x: &mut [f32], b: &[f32], workers: &mut Workers) {
+        workers.for_each_chunk_with_snapshot(x, |previous, offset, chunk| {
+            for (k, value) in chunk.iter_mut().enumerate() {
+                let idx = offset + k;
+                if self.is_solved(idx) {
+                    let solution =
+                        (b[idx] + self.neighbours_sum(previous, idx)) / self.diagonal[idx];
+                    *value = (1.0 - JACOBI_WEIGHT) * previous[idx] + JACOBI_WEIGHT * solution;
+                }
+            }
+        });
+    }
+
+    /// A red-black sweep of successive over-relaxation, i.e. of Gauss-Seidel when `omega` is
+    /// `1.0`. The cells with even `x + y` are updated first, and then the ones with odd `x + y`.
+    /// Every half of the sweep reads the values as they were before it, so the result doesn't
+    /// depend on the number of threads.
+    fn relax(&self, x: &mut [f32], b: &[f32], omega: f32, workers: &mut Workers) {
+        for parity in 0..2 {
+            workers.for_each_chunk_with_snapshot(x, |previous, offset, chunk| {
+                for (k, value) in chunk.iter_mut().enumerate() {
+                    let idx = offset + k;
+                    if (idx % self.width + idx / self.width) % 2 != parity || !self.is_solved(idx) {
+                        continue;
+                    }
+                    let solution =
+                        (b[idx] + self.neighbours_sum(previous, idx)) / self.diagonal[idx];
+                    *value = (1.0 - omega) * previous[idx] + omega * solution;
+                }
+            });
+        }
+    }
+
+    /// Shifts the values of the solved cells, so they sum up to zero.
+    fn remove_mean(&self, values: &mut [f32]) {
+        let solved_count = (0..values.len()).filter(|&idx| self.is_solved(idx)).count();
+        if solved_count == 0 {
+            return;
+        }
+
+        let mean = (0..values.len())
+            .filter(|&idx| self.is_solved(idx))
+            .map(|idx| f64::from(values[idx]))
+            .sum::<f64>()
+            / solved_count as f64;
+        for (idx, value) in values.iter_mut().enumerate() {
+            if self.is_solved(idx) {
+                *value -= mean as f32;
+            }
+        }
+    }
+
+    /// Checks if the grid is small enough to be the coarsest level of the multigrid.
+    fn is_coarsest(&self) -> bool {
+        self.width <= 2 || self.height <= 2
+    }
+
+    /// The grid in which every cell covers 2x2 cells of this one. A cell is fluid if any of the
+    /// cells it covers is fluid. The operator of the coarse grid is the Galerkin one, i.e. the
+    /// operator of this grid restricted and brought back the same way as the corrections of the
+    /// multigrid: the weights of all the connections between the cells covered by two coarse
+    /// cells are summed up. This way the walls and the periodic edges are carried over to the
+    /// coarse grid as they are, even if the grid has an odd number of cells.
+    fn coarsen(&self) -> Grid {
+        let (width, height) = ((self.width + 1) / 2, (self.height + 1) / 2);
+        let mut result = Grid {
+            width,
+            height,
+            is_periodic_x: self.is_periodic_x,
+            is_periodic_y: self.is_periodic_y,
+            is_fluid: vec![false; width * height],
+            neighbours: vec![[0; 4]; width * height],
+            weights: vec![[0.0; 4]; width * height],
+            neighbours_count: vec![0; width * height],
+            diagonal: vec![0.0; width * height],
+        };
+
+        for idx in 0..self.is_fluid.len() {
+            if !self.is_fluid[idx] {
+                continue;
+            }
+            let parent = self.parent(idx, width);
+            result.is_fluid[parent] = true;
+
+            let count = self.neighbours_count[idx] as usize;
+            for (&neighbour, &weight) in self.neighbours[idx][..count]
+                .iter()
+                .zip(&self.weights[idx][..count])
+            {
+                result.connect(parent, self.parent(neighbour, width), weight);
+            }
+        }
+        result
+    }
+
+    /// The index of the cell of the coarser grid with the given width which covers the cell.
+    fn parent(&self, idx: usize, coarse_width: usize) -> usize {
+        (idx % self.width) / 2 + (idx / self.width) / 2 * coarse_width
+    }
+}
+
+/// Solves the pressure equation of the projection of the fluid for its inner cells with the given
+/// solver. [`PressureSolver::GaussSeidel`] is solved as [`PressureSolver::RedBlackSor`] with an
+/// `omega` of `1.0` here, i.e. taking the walls into account. The pressure `p` is used as the
+/// initial guess and `div` is the divergence of the velocities. The walls get the average of
+/// their fluid neighbours, so the pressure gradient next to them is close to zero. The edges of
+/// the container are left untouched.
+///
+/// No edge has a fixed pressure, so the equation has a solution only if the divergence sums up to
+/// zero. That's why the divergence is shifted by its mean before solving.
+pub fn solve(
+    solver: PressureSolver,
+    p: &mut [f32],
+    div: &[f32],
+    simulation_configs: &SimulationConfigs,
+    cells_type: &[ContainerWall],
+) -> PressureSolve {
+    let (full_width, full_height) = (
+        simulation_configs.width as usize,
+        simulation_configs.height as usize,
+    );
+    let (width, height) = (full_width - 2, full_height - 2);
+    let full_idx = |idx: usize| (idx % width + 1) + (idx / width + 1) * full_width;
+
+    let grid = Grid::new(
+        width,
+        height,
+        (0..width * height)
+            .map(|idx| cells_type[full_idx(idx)] == ContainerWall::NoWall)
+            .collect(),
+        simulation_configs.boundaries.left == BoundaryCondition::Periodic,
+        simulation_configs.boundaries.top == BoundaryCondition::Periodic,
+    );
+
+    let mut x: Vec<f32> = (0..width * height).map(|idx| p[full_idx(idx)]).collect();
+    let mut b: Vec<f32> = (0..width * height).map(|idx| div[full_idx(idx)]).collect();
+    grid.remove_mean(&mut b);
+
+    let result = match solver {
+        PressureSolver::GaussSeidel => iterate(
+            &grid,
+            &mut x,
+            &b,
+            simulation_configs,
+            |grid, x, b, workers| grid.relax(x, b, 1.0, workers),
+        ),
+        PressureSolver::RedBlackSor { omega } => {
+            let omega = omega.clamp(0.0, 2.0);
+            iterate(
+                &grid,
+                &mut x,
+                &b,
+                simulation_configs,
+                |grid, x, b, workers| grid.relax(x, b, omega, workers),
+            )
+        }
+        PressureSolver::Jacobi => iterate(
+            &grid,
+            &mut x,
+            &b,
+            simulation_configs,
+            |grid, x, b, workers| grid.jacobi(x, b, workers),
+        ),
+        PressureSolver::ConjugateGradient => {
+            conjugate_gradient(&grid, &mut x, &b, simulation_configs)
+        }
+        PressureSolver::Multigrid => {
+            let mut coarser_levels: Vec<Grid> = Vec::new();
+            while !coarser_levels.last().unwrap_or(&grid).is_coarsest() {
+                coarser_levels.push(coarser_levels.last().unwrap_or(&grid).coarsen());
+            }
+            iterate(
+                &grid,
+                &mut x,
+                &b,
+                simulation_configs,
+                |grid, x, b, workers| v_cycle(grid, &coarser_levels, x, b, workers),
+            )
+        }
+    };
+
+    write_back(&grid, &x, p, full_idx);
+    result
+}
+
+/// Performs the iterations of the solver bound by [`SimulationConfigs::solver_iterations`] and
+/// [`SimulationConfigs::solver_stop_condition`].
+fn iterate<F>(
+    grid: &Grid,
+    x: &mut [f32],
+    b: &[f32],
+    simulation_configs: &SimulationConfigs,
+    iteration: F,
+) -> PressureSolve
+where
+    F: Fn(&Grid, &mut [f32], &[f32], &mut Workers),
+{
+    let mut workers = Workers::new(simulation_configs.threads);
+    for k in 0..simulation_configs.solver_iterations {
+        iteration(grid, x, b, &mut workers);
+
+        if let SolverStopCondition::Tolerance(tolerance) = simulation_configs.solver_stop_condition
+        {
+            let residual = grid.max_residual(x, b, &mut workers);
+            if residual < tolerance {
+                return PressureSolve {
+                    iterations: k + 1,
+                    residual,
+                };
+            }
+        }
+    }
+
+    PressureSolve {
+        iterations: simulation_configs.solver_iterations,
+        residual: grid.max_residual(x, b, &mut workers),
+    }
+}
+
+/// Conjugate gradient preconditioned by the diagonal of the Laplace operator.
+fn conjugate_gradient(
+    grid: &Grid,
+    x: &mut [f32],
+    b: &[f32],
+    simulation_configs: &SimulationConfigs,
+) -> PressureSolve {
+    let mut workers = Workers::new(simulation_configs.threads);
+    let dot = |u: &[f32], v: &[f32]| -> f64 {
+        u.iter()
+            .zip(v)
+            .map(|(u, v)| f64::from(*u) * f64::from(*v))
+            .sum()
+    };
+    // The preconditioned residual is kept free of constants, which the Laplace operator maps to
+    // zero. Otherwise the rounding errors pile up along them and the iterations break down.
+    let precondition = |r: &[f32]| -> Vec<f32> {
+        let mut z: Vec<f32> = r
+            .iter()
+            .enumerate()
+            .map(|(idx, r)| match grid.is_solved(idx) {
+                true => r / grid.diagonal[idx],
+                false => 0.0,
+            })
+            .collect();
+        grid.remove_mean(&mut z);
+        z
+    };
+
+    let mut r = grid.residuals(x, b, &mut workers);
+    let mut z = precondition(&r);
+    let mut direction = z.clone();
+    let mut rz = dot(&r, &z);
+    let mut q = vec![0.0; x.len()];
+
+    for k in 0..simulation_configs.solver_iterations {
+        workers.for_each_chunk(&mut q, |offset, chunk| {
+            for (i, value) in chunk.iter_mut().enumerate() {
+                let idx = offset + i;
+                if grid.is_solved(idx) {
+                    *value = grid.apply(&direction, idx);
+                }
+            }
+        });
+        let direction_q = dot(&direction, &q);
+        if direction_q <= 0.0 {
+            // The residual is already zero
+            return PressureSolve {
+                iterations: k,
+                residual: grid.max_residual(x, b, &mut workers),
+            };
+        }
+
+        let alpha = (rz / direction_q) as f32;
+        for idx in 0..x.len() {
+            x[idx] += alpha * direction[idx];
+            r[idx] -= alpha * q[idx];
+        }
+
+        if let SolverStopCondition::Tolerance(tolerance) = simulation_configs.solver_stop_condition
+        {
+            let residual = grid.max_residual(x, b, &mut workers);
+            if residual < tolerance {
+                return PressureSolve {
+                    iterations: k + 1,
+                    residual,
+                };
+            }
+        }
+
+        z = precondition(&r);
+        let next_rz = dot(&r, &z);
+        let beta = (next_rz / rz) as f32;
+        rz = next_rz;
+        for (direction, z) in direction.iter_mut().zip(&z) {
+            *direction = z + beta * *direction;
+        }
+    }
+
+    PressureSolve {
+        iterations: simulation_configs.solver_iterations,
+        residual: grid.max_residual(x, b, &mut workers),
+    }
+}
+
+/// A V-cycle of the multigrid. The equation is smoothed on every level from the given grid to the
+/// coarsest one, where it's solved, and the solutions are brought back to the finer levels
+/// as corrections. The residuals are restricted to the coarser level by summing up the cells
+/// covered by every coarse cell, and the corrections are brought back by copying the coarse cell
+/// into all the cells it covers. See [`OVER_CORRECTION`].
+fn v_cycle(grid: &Grid, coarser_levels: &[Grid], x: &mut [f32], b: &[f32], workers: &mut Workers) {
+    let (coarse, coarser_levels) = match coarser_levels.split_first() {
+        Some(levels) => levels,
+        None => {
+            for _ in 0..COARSEST_SWEEPS {
+                grid.relax(x, b, 1.0, workers);
+            }
+            return;
+        }
+    };
+
+    for _ in 0..SMOOTHING_SWEEPS {
+        grid.relax(x, b, 1.0, workers);
+    }
+
+    let mut coarse_b = vec![0.0; coarse.width * coarse.height];
+    for (idx, residual) in grid.residuals(x, b, workers).into_iter().enumerate() {
+        coarse_b[grid.parent(idx, coarse.width)] += residual;
+    }
+    let mut coarse_x = vec![0.0; coarse_b.len()];
+    v_cycle(coarse, coarser_levels, &mut coarse_x, &coarse_b, workers);
+    for (idx, value) in x.iter_mut().enumerate() {
+        if grid.is_solved(idx) {
+            *value += OVER_CORRECTION * coarse_x[grid.parent(idx, coarse.width)];
+        }
+    }
+
+    for _ in 0..SMOOTHING_SWEEPS {
+        grid.relax(x, b, 1.0, workers);
+    }
+}
+
+/// Writes the solution back into the pressure of the whole container. The walls get the average
+/// of their fluid neighbours.
+fn write_back(grid: &Grid, x: &[f32], p: &mut [f32], full_idx: impl Fn(usize) -> usize) {
+    for idx in 0..x.len() {
+        p[full_idx(idx)] = if grid.is_fluid[idx] {
+            x[idx]
+        } else {
+            let (width, height) = (grid.width, grid.height);
+            let (i, j) = (idx % width, idx / width);
+            let neighbours: Vec<usize> = [
+                Grid::neighbour(i, -1, width, grid.is_periodic_x).map(|i| i + j * width),
+                Grid::neighbour(i, 1, width, grid.is_periodic_x).map(|i| i + j * width),
+                Grid::neighbour(j, -1, height, grid.is_periodic_y).map(|j| i + j * width),
+                Grid::neighbour(j, 1, height, grid.is_periodic_y).map(|j| i + j * width),
+            ]
+            .into_iter()
+            .flatten()
+            .filter(|&neighbour| grid.is_fluid[neighbour])
+            .collect();
+            match neighbours.len() {
+                0 => 0.0,
+                count => neighbours.iter().map(|&idx| x[idx]).sum::<f32>() / count as f32,
+            }
+        };
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::simulation::configs::{
+        BoundaryCondition, BoundaryConditions, PressureSolver, SimulationConfigs,
+        SolverStopCondition,
+    };
+    use crate::simulation::fluid::ContainerWall;
+    use crate::simulation::pressure::solve;
+
+    /// A container with a square obstacle in it and a divergence which is set in the walls as well
+    fn container_with_obstacle(size: u32) -> (SimulationConfigs, Vec<ContainerWall>, Vec<f32>) {
+        let simulation_configs = SimulationConfigs::new(0.02, 1, size);
+        let cells_count = simulation_configs.cells_count();
+        let mut cells_type = vec![ContainerWall::NoWall; cells_count];
+        for j in size / 3..size / 2 {
+            for i in size / 4..size / 2 {
+                cells_type[(j * size + i) as usize] = ContainerWall::DefaultWall;
+            }
+        }
+        let div = (0..cells_count)
+            .map(|idx| ((idx * 7919) % 101) as f32 / 101.0 - 0.5)
+            .collect();
+        (simulation_configs, cells_type, div)
+    }
+
+    #[test]
+    fn solvers_converge() {
+        let (mut simulation_configs, cells_type, div) = container_with_obstacle(34);
+        for (solver, iterations, tolerance) in [
+            (PressureSolver::GaussSeidel, 2000, 1e-3),
+            (PressureSolver::Jacobi, 4000, 1e-3),
+            (PressureSolver::RedBlackSor { omega: 1.8 }, 500, 1e-3),
+            (PressureSolver::ConjugateGradient, 300, 1e-4),
+            (PressureSolver::Multigrid, 10, 1e-4),
+        ] {
+            simulation_configs.solver_iterations = iterations;
+            let mut p = vec![0.0; div.len()];
+            let result = solve(solver, &mut p, &div, &simulation_configs, &cells_type);
+
+            assert!(result.iterations <= iterations);
+            assert!(
+                result.residual < tolerance,
+                "{:?} hasn't converged: {}",
+                solver,
+                result.residual
+            );
+        }
+    }
+
+    #[test]
+    fn multigrid_converges_with_periodic_edges() {
+        // The inner cells don't split evenly into the coarser levels
+        let (mut simulation_configs, cells_type, div) = container_with_obstacle(47);
+        simulation_configs.boundaries = BoundaryConditions {
+            left: BoundaryCondition::Periodic,
+            right: BoundaryCondition::Periodic,
+            ..BoundaryConditions::default()
+        };
+        simulation_configs.solver_iterations = 10;
+
+        let mut p = vec![0.0; div.len()];
+        let result = solve(
+            PressureSolver::Multigrid,
+            &mut p,
+            &div,
+            &simulation_configs,
+            &cells_type,
+        );
+
+        assert!(result.residual < 1e-4, "{}", result.residual);
+    }
+
+    #[test]
+    fn solvers_stop_on_tolerance() {
+        let (mut simulation_configs, cells_type, div) = container_with_obstacle(34);
+        simulation_configs.solver_iterations = 100;
+        simulation_configs.solver_stop_condition = SolverStopCondition::Tolerance(1e-3);
+
+        let mut p = vec![0.0; div.len()];
+        let result = solve(
+            PressureSolver::Multigrid,
+            &mut p,
+            &div,
+            &simulation_configs,
+            &cells_type,
+        );
+
+        assert!(result.iterations < 100);
+        assert!(result.residual < 1e-3);
+    }
+
+    #[test]
+    fn walls_do_not_affect_the_fluid() {
+        let (mut simulation_configs, cells_type, div) = container_with_obstacle(34);
+        let fluid_div: Vec<f32> = div
+            .iter()
+            .zip(&cells_type)
+            .map(|(div, cell)| match cell {
+                ContainerWall::NoWall => *div,
+                ContainerWall::DefaultWall => 0.0,
+            })
+            .collect();
+
+        for solver in [PressureSolver::ConjugateGradient, PressureSolver::Multigrid] {
+            let mut p = vec![0.0; div.len()];
+            solve(solver, &mut p, &div, &simulation_configs, &cells_type);
+            let mut fluid_p = vec![0.0; div.len()];
+            solve(
+                solver,
+                &mut fluid_p,
+                &fluid_div,
+                &simulation_configs,
+                &cells_type,
+            );
+            assert_eq!(p, fluid_p);
+
+            // The result doesn't depend on the number of threads either
+            simulation_configs.threads = 3;
+            let mut threaded_p = vec![0.0; div.len()];
+            solve(
+                solver,
+                &mut threaded_p,
+                &div,
+                &simulation_configs,
+                &cells_type,
+            );
+            assert_eq!(p, threaded_p);
+            simulation_configs.threads = 1;
+        }
+    }
+}
@@ -1,24 +1,38 @@
+use super::emitter::Emitter;
 use super::obstacle::ObstaclesType;
-use super::renderer_helpers::{CurrentSimulation, FluidStep, RenderingListener};
+use super::renderer_helpers::{
+    BrushStroke, CurrentSimulation, FluidStep, RenderedFrame, RenderingListener, SimulationChange,
+    SimulationControl,
+};
+#[cfg(feature = "gui")]
 use crate::app::widgets::widgets_menu::SettingType;
-use crate::simulation::configs::{FluidConfigs, SimulationConfigs};
+use crate::simulation::checkpoint::Checkpoint;
+use crate::simulation::configs::{
+    DyeConfigs, FluidConfigs, LiveChanges, SimulationConfigs, ViewportConfigs,
+};
 use crate::simulation::fluid::Fluid;
-use eframe::egui::Color32;
+use crate::simulation::scenario::Scenario;
+use epaint::Color32;
 use std::sync::mpsc;
 use std::sync::mpsc::{Receiver, Sender};
 use std::thread::JoinHandle;
 
+/// The threads of a run started by [`Renderer::render()`]: one simulates the fluid and another one
+/// renders it. Joining them returns the error which has stopped the rendering, if any.
+pub type RenderingThreads = Vec<JoinHandle<std::io::Result<()>>>;
+
 /// Utility for visualization and interaction with the fluid simulation.
 ///
-/// In future implementations it is planned to have two ways to modify the simulation’s parameters
-/// while the simulation is running: to rerun the simulation from the beginning, or to continue it
-/// with the newly added parameters. Currently the configurations that are changed, will be applied
-/// in the next run of the simulation. Internally, there are parameters of the type `next_*_configs`
-/// that are used to buffer the future configurations. In the first case, they will be updating the
-/// current state of the renderer live during the simulation (the current state is stored in the
-/// struct’s member [`Renderer::current_simulation`]), or, in the other, they will update it at the
-/// **beginning** of the new simulation (i.e. frame 0). In order to update the next configurations,
-/// use [`Renderer::update_configs()`].
+/// The simulation’s parameters can be modified while the simulation is running in several ways,
+/// chosen by [`Renderer::live_changes`]: to apply them in the next run of the simulation, to rerun
+/// the simulation from the beginning, or to continue it with the newly added parameters.
+/// Internally, there are parameters of the type `next_*_configs` that are used to buffer the
+/// future configurations. They update the state of the simulation at the **beginning** of the new
+/// simulation (i.e. frame 0). When the simulation continues with the new parameters, the changes
+/// of the fluid configurations, the dyes and the obstacles are also sent to the running
+/// simulation, and take effect before its next step, while the color of the obstacles is sent to
+/// the running [`RenderingListener`]. In order to update the next configurations, use
+/// [`Renderer::update_configs()`].
 pub struct Renderer {
     /// Buffered fluid configurations for the next run. The configurations of the fluid are not
     /// changed while the fluid is being simulated.
@@ -28,41 +42,77 @@ pub struct Renderer {
     /// not changed while the fluid is being simulated.
     next_simulation_configs: SimulationConfigs,
 
-    /// Buffered name for saving directory for the next run. Use when you don't want to change the
-    /// directory in the middle of the previous simulation. Use with [`Renderer::update_configs()`]
-    next_save_into_dir: String,
+    /// Buffered viewport configurations for the next run, e.g. the saving directory and the
+    /// output format. Use when you don't want to change them in the middle of the previous
+    /// simulation. Use with [`Renderer::update_configs()`]
+    next_viewport_configs: ViewportConfigs,
 
-    /// Buffered color for the obstacles. The configurations of the fluid are not changed while
-    /// the simulation is running.
-    next_obstacles_color: eframe::egui::Color32,
+    /// Buffered color for the obstacles. It is sent to the running [`RenderingListener`] only if
+    /// the simulation continues with the new parameters.
+    next_obstacles_color: Color32,
 
     /// Buffered obstacles for the next run. The configurations of the fluid are not changed while
     /// the fluid is being simulated.
     next_obstacles: Vec<ObstaclesType>,
 
+    /// Buffered emitters for the next run. The configurations of the fluid are not changed while
+    /// the fluid is being simulated.
+    next_emitters: Vec<Emitter>,
+
+    /// Buffered dyes for the next run. The configurations of the fluid are not changed while the
+    /// fluid is being simulated.
+    next_dyes: Vec<DyeConfigs>,
+
     /// Contains the state of the current simulation step.
     pub current_simulation: CurrentSimulation,
 
     /// Listens for the current simulation signal, in order to render the simulation into an image
     pub rendering_listener: RenderingListener,
+
+    /// Sends the brush strokes to the running simulation. See [`Renderer::paint()`].
+    brush_strokes_tx: Option<Sender<BrushStroke>>,
+
+    /// Pauses, resumes and stops the running simulation. Every run gets a new control.
+    simulation_control: SimulationControl,
+
+    /// Sends the changes of the configurations to the running simulation. See
+    /// [`LiveChanges::Continue`].
+    changes_tx: Option<Sender<SimulationChange>>,
+
+    /// Sends the changed color of the obstacles to the running [`RenderingListener`]. See
+    /// [`LiveChanges::Continue`].
+    obstacles_colors_tx: Option<Sender<Color32>>,
+
+    /// How the changed configurations are applied while the simulation is running
+    pub live_changes: LiveChanges,
+
+    /// The checkpoint from which the next runs start. See [`Renderer::start_from_checkpoint()`].
+    next_checkpoint: Option<Checkpoint>,
 }
 
 impl Default for Renderer {
     /// Add default Renderer, containing an obstacle and default [`FluidConfigs`] and [`SimulationConfigs`]
     fn default() -> Self {
         let fluid = Fluid::default();
-        let default_dir = RenderingListener::make_save_into_dir("rendered_images");
 
         Self {
-            next_fluid_configs: fluid.fluid_configs.clone(),
-            next_simulation_configs: fluid.simulation_configs.clone(),
+            next_fluid_configs: fluid.fluid_configs,
+            next_simulation_configs: fluid.simulation_configs,
             next_obstacles_color: Color32::RED,
             next_obstacles: vec![ObstaclesType::Rectangle(
                 crate::simulation::obstacle::Rectangle::default(),
             )],
-            next_save_into_dir: default_dir.clone(),
+            next_emitters: Vec::new(),
+            next_dyes: Vec::new(),
+            next_viewport_configs: ViewportConfigs::default(),
             current_simulation: CurrentSimulation::default(),
             rendering_listener: RenderingListener::default(),
+            brush_strokes_tx: None,
+            simulation_control: SimulationControl::default(),
+            changes_tx: None,
+            obstacles_colors_tx: None,
+            live_changes: LiveChanges::default(),
+            next_checkpoint: None,
         }
     }
 }
@@ -70,98 +120,311 @@ impl Default for Renderer {
 impl Renderer {
     /// Creates new Renderer
     pub fn new(fluid: Fluid, obstacles_color: Color32, images_dir: String) -> Renderer {
-        let save_into_dir = RenderingListener::make_save_into_dir(&images_dir);
         Renderer {
-            next_fluid_configs: fluid.fluid_configs.clone(),
-            next_simulation_configs: fluid.simulation_configs.clone(),
+            next_fluid_configs: fluid.fluid_configs,
+            next_simulation_configs: fluid.simulation_configs,
             next_obstacles_color: obstacles_color,
             next_obstacles: Vec::new(),
-            next_save_into_dir: save_into_dir,
+            next_emitters: Vec::new(),
+            next_dyes: Vec::new(),
+            next_viewport_configs: ViewportConfigs {
+                save_into_dir: images_dir,
+                ..ViewportConfigs::default()
+            },
+            current_simulation: CurrentSimulation::default(),
+            rendering_listener: RenderingListener::default(),
+            brush_strokes_tx: None,
+            simulation_control: SimulationControl::default(),
+            changes_tx: None,
+            obstacles_colors_tx: None,
+            live_changes: LiveChanges::default(),
+            next_checkpoint: None,
+        }
+    }
+
+    /// Creates new Renderer that will simulate the given scenario on its next run
+    pub fn from_scenario(scenario: &Scenario) -> Renderer {
+        Renderer {
+            next_fluid_configs: scenario.fluid,
+            next_simulation_configs: scenario.simulation,
+            next_obstacles_color: scenario.obstacles_color,
+            next_obstacles: scenario.obstacles.clone(),
+            next_emitters: scenario.emitters.clone(),
+            next_dyes: scenario.dyes.clone(),
+            next_viewport_configs: scenario.viewport.clone(),
             current_simulation: CurrentSimulation::default(),
             rendering_listener: RenderingListener::default(),
+            brush_strokes_tx: None,
+            simulation_control: SimulationControl::default(),
+            changes_tx: None,
+            obstacles_colors_tx: None,
+            live_changes: LiveChanges::default(),
+            next_checkpoint: None,
         }
     }
 
     /// Updates the configurations for the next simulation. As an example,
     /// during rendering, the configurations new configurations may be saved in
-    /// the renderer, but will only be applied after the beginning of the next simulation
-    pub fn update_configs(&mut self, settings_menu: &[SettingType]) {
+    /// the renderer, but will only be applied after the beginning of the next simulation.
+    ///
+    /// If [`Renderer::live_changes`] is [`LiveChanges::Continue`], the changed fluid
+    /// configurations, dyes, obstacles and their color are also sent to the running simulation.
+    /// Returns whether any of the configurations of the simulated fluid has changed, so the caller
+    /// could restart the simulation if [`Renderer::live_changes`] is [`LiveChanges::Restart`]. The
+    /// viewport configurations and the color of the obstacles don't change the fluid, so they are
+    /// not reported.
+    #[cfg(feature = "gui")]
+    pub fn update_configs(&mut self, settings_menu: &[SettingType]) -> bool {
+        let mut has_changed = false;
+
         for setting in settings_menu.iter() {
             match setting {
                 SettingType::Fluid(fluid_widget) => {
-                    self.next_fluid_configs = fluid_widget.fluid_configs;
+                    if self.next_fluid_configs != fluid_widget.fluid_configs {
+                        self.next_fluid_configs = fluid_widget.fluid_configs;
+                        self.send_change(SimulationChange::Fluid(self.next_fluid_configs));
+                        has_changed = true;
+                    }
                 }
                 SettingType::Simulation(simulation_widget) => {
-                    self.next_simulation_configs = simulation_widget.simulation_configs;
+                    if self.next_simulation_configs != simulation_widget.simulation_configs {
+                        self.next_simulation_configs = simulation_widget.simulation_configs;
+                        has_changed = true;
+                    }
                 }
                 SettingType::Obstacle(obstacle_widget) => {
-                    self.next_obstacles = obstacle_widget
+                    let obstacles: Vec<ObstaclesType> = obstacle_widget
                         .obstacles
                         .iter()
                         .cloned()
                         .map(|el| el.obstacle)
                         .collect();
-                    self.next_obstacles_color = obstacle_widget.color;
+                    if self.next_obstacles != obstacles {
+                        self.next_obstacles = obstacles;
+                        self.send_change(SimulationChange::Obstacles(self.next_obstacles.clone()));
+                        has_changed = true;
+                    }
+
+                    if self.next_obstacles_color != obstacle_widget.color {
+                        self.next_obstacles_color = obstacle_widget.color;
+                        self.send_obstacles_color();
+                    }
+                }
+                SettingType::Emitter(emitter_widget) => {
+                    if self.next_emitters != emitter_widget.emitters {
+                        self.next_emitters = emitter_widget.emitters.clone();
+                        has_changed = true;
+                    }
+                }
+                SettingType::Dye(dye_widget) => {
+                    if self.next_dyes != dye_widget.dyes {
+                        self.next_dyes = dye_widget.dyes.clone();
+                        self.send_change(SimulationChange::Dyes(self.next_dyes.clone()));
+                        has_changed = true;
+                    }
                 }
                 SettingType::Viewport(viewport_widget) => {
-                    self.next_save_into_dir = RenderingListener::make_save_into_dir(
-                        &viewport_widget.save_into_dir.clone(),
-                    );
+                    if self.next_viewport_configs != viewport_widget.viewport_configs {
+                        self.next_viewport_configs = viewport_widget.viewport_configs.clone();
+                    }
                 }
             }
         }
+
+        has_changed
+    }
+
+    /// Removes the dye with the given index from the configurations of the next run. If the
+    /// simulation continues with the new parameters, the dye is also removed from the running
+    /// simulation, and its emitters follow the removal. See [`SimulationChange::RemoveDye`].
+    #[cfg(feature = "gui")]
+    pub fn remove_dye(&mut self, index: usize) {
+        if index < self.next_dyes.len() {
+            self.next_dyes.remove(index);
+            self.send_change(SimulationChange::RemoveDye(index));
+        }
+    }
+
+    /// Sends the change to the running simulation if it should continue with the new parameters.
+    /// See [`LiveChanges::Continue`].
+    #[cfg(feature = "gui")]
+    fn send_change(&self, change: SimulationChange) {
+        if self.live_changes != LiveChanges::Continue {
+            return;
+        }
+
+        if let Some(changes_tx) = &self.changes_tx {
+            // The simulation may have already finished, so there is nobody to apply the change.
+            let _ = changes_tx.send(change);
+        }
+    }
+
+    /// Sends the color of the obstacles to the running [`RenderingListener`] if the simulation
+    /// should continue with the new parameters. See [`LiveChanges::Continue`].
+    #[cfg(feature = "gui")]
+    fn send_obstacles_color(&self) {
+        if self.live_changes != LiveChanges::Continue {
+            return;
+        }
+
+        if let Some(obstacles_colors_tx) = &self.obstacles_colors_tx {
+            // The rendering may have already finished
+            let _ = obstacles_colors_tx.send(self.next_obstacles_color);
+        }
     }
 
     /// Runs the simulation, and then renders the result. Internally, it fires several more
     /// threads: one to simulate the fluid, and another one to render the result.
-    /// As a result a [`std::sync::mpsc::Receiver<i64>`] is returned, by which a signal for every new
-    /// render will be sent over.
-    pub fn render(&mut self) -> (Receiver<i64>, Vec<JoinHandle<()>>) {
+    /// As a result a [`std::sync::mpsc::Receiver<RenderedFrame>`] is returned, by which every newly
+    /// rendered frame will be sent over.
+    ///
+    /// The previous simulation, if it is still running, is stopped. The threads finish as soon as
+    /// the simulation is stopped (see [`Renderer::stop()`]) or the returned receiver is dropped.
+    /// Joining the threads returns the error which has stopped the rendering, e.g. when a frame
+    /// couldn't be saved. See [`RenderingListener::listen()`].
+    ///
+    /// If the next run can't start from the checkpoint (see [`Renderer::start_from_checkpoint()`]),
+    /// the error is returned and the previous simulation keeps running.
+    pub fn render(&mut self) -> std::io::Result<(Receiver<RenderedFrame>, RenderingThreads)> {
         let (simulation_tx, simulation_rx): (Sender<FluidStep>, Receiver<FluidStep>) =
             mpsc::channel();
 
-        self.prepare_simulation();
+        self.prepare_simulation()?;
+        self.stop();
+
+        let (brush_strokes_tx, brush_strokes_rx) = mpsc::channel();
+        self.brush_strokes_tx = Some(brush_strokes_tx);
+
+        let (changes_tx, changes_rx) = mpsc::channel();
+        self.changes_tx = Some(changes_tx);
+
+        self.simulation_control = SimulationControl::default();
+        let simulation_control = self.simulation_control.clone();
 
         let mut current_simulation = self.current_simulation.clone();
         let simulation_handler = std::thread::spawn(move || {
-            current_simulation.simulate(simulation_tx);
+            current_simulation.simulate(
+                simulation_tx,
+                brush_strokes_rx,
+                changes_rx,
+                simulation_control,
+            );
+            Ok(())
         });
 
-        let rendering_listener = self.rendering_listener.clone();
+        let (obstacles_colors_tx, obstacles_colors_rx) = mpsc::channel();
+        self.obstacles_colors_tx = Some(obstacles_colors_tx);
+
+        let mut rendering_listener = self.rendering_listener.clone();
         let max_frames = self.current_simulation.fluid.simulation_configs.frames;
 
-        let (rendering_tx, rendering_rx): (Sender<i64>, Receiver<i64>) = mpsc::channel();
+        let (rendering_tx, rendering_rx): (Sender<RenderedFrame>, Receiver<RenderedFrame>) =
+            mpsc::channel();
 
+        let simulation_control = self.simulation_control.clone();
         let rendering_handler = std::thread::spawn(move || {
-            rendering_listener.listen(max_frames, simulation_rx, rendering_tx);
+            rendering_listener.listen(
+                max_frames,
+                simulation_rx,
+                obstacles_colors_rx,
+                rendering_tx,
+                simulation_control,
+            )
         });
 
         let result_joinhandles = vec![simulation_handler, rendering_handler];
 
-        (rendering_rx, result_joinhandles)
+        Ok((rendering_rx, result_joinhandles))
+    }
+
+    /// Paints the given brush stroke into the running simulation. It is applied before the next
+    /// step of the fluid. Returns `false` if there is no simulation running.
+    pub fn paint(&self, brush_stroke: BrushStroke) -> bool {
+        self.brush_strokes_tx
+            .as_ref()
+            .map_or(false, |tx| tx.send(brush_stroke).is_ok())
     }
 
-    /// Prepares the next simulation by creating new instances of all the needed components
-    fn prepare_simulation(&mut self) {
+    /// Pauses the running simulation. The frames simulated so far are still rendered.
+    pub fn pause(&self) {
+        self.simulation_control.pause();
+    }
+
+    /// Resumes the paused simulation.
+    pub fn resume(&self) {
+        self.simulation_control.resume();
+    }
+
+    /// Stops the running simulation for good. It can't be resumed afterwards.
+    pub fn stop(&mut self) {
+        self.simulation_control.cancel();
+        self.brush_strokes_tx = None;
+        self.changes_tx = None;
+        self.obstacles_colors_tx = None;
+    }
+
+    /// Checks if the running simulation is paused.
+    pub fn is_paused(&self) -> bool {
+        self.simulation_control.is_paused()
+    }
+
+    /// Makes the next runs start from the state of the fluid in the given checkpoint, instead of
+    /// the initial one. The rest of the configurations, e.g. the viscosity or the obstacles, are
+    /// still taken from the next configurations, so several experiments could branch from the
+    /// same checkpoint. Pass `None` to start from the initial state again. See
+    /// [`Checkpoint::restore_into()`]. The checkpoint is checked against the next configurations
+    /// when the run starts, see [`Renderer::render()`].
+    pub fn start_from_checkpoint(&mut self, checkpoint: Option<Checkpoint>) {
+        self.next_checkpoint = checkpoint;
+    }
+
+    /// The checkpoint from which the next runs start, if any
+    pub fn next_checkpoint(&self) -> Option<&Checkpoint> {
+        self.next_checkpoint.as_ref()
+    }
+
+    /// Prepares the next simulation by creating new instances of all the needed components. Fails
+    /// without changing anything if the fluid can't be restored from the next checkpoint.
+    fn prepare_simulation(&mut self) -> std::io::Result<()> {
+        let mut fluid = Fluid::new(self.next_fluid_configs, self.next_simulation_configs);
+        fluid.set_dyes(&self.next_dyes);
+        if let Some(checkpoint) = &self.next_checkpoint {
+            checkpoint.restore_into(&mut fluid)?;
+        }
+
         self.current_simulation = CurrentSimulation {
-            fluid: Fluid::new(self.next_fluid_configs, self.next_simulation_configs),
+            fluid,
             obstacles: self.next_obstacles.clone(),
+            emitters: self.next_emitters.clone(),
         };
 
-        self.rendering_listener = RenderingListener {
-            save_into_dir: self.next_save_into_dir.clone(),
-            obstacles_color: self.next_obstacles_color,
-        };
+        self.rendering_listener =
+            RenderingListener::new(&self.next_viewport_configs, self.next_obstacles_color);
+        Ok(())
     }
 }
 
-#[cfg(test)]
+#[cfg(all(test, feature = "gui"))]
 mod tests {
     use crate::app::widgets::fluid_widget::FluidWidget;
+    use crate::app::widgets::viewport_widget::ViewportWidget;
     use crate::app::widgets::widgets_menu::{SettingType, SettingsMenu};
-    use crate::simulation::configs::{FluidConfigs, SimulationConfigs};
+    use crate::simulation::checkpoint::Checkpoint;
+    use crate::simulation::configs::{FluidConfigs, SimulationConfigs, VisualizedField};
     use crate::simulation::{fluid::Fluid, renderer::Renderer};
-    use eframe::egui::Color32;
+    use epaint::Color32;
+
+    #[test]
+    fn render_fails_for_an_incompatible_checkpoint() {
+        let mut renderer = Renderer::default();
+        let fluid = Fluid::new(FluidConfigs::default(), SimulationConfigs::new(0.02, 1, 16));
+        renderer.start_from_checkpoint(Some(Checkpoint {
+            frame_number: 0,
+            fluid,
+        }));
+
+        assert!(renderer.render().is_err());
+    }
 
     #[test]
     fn update_configs() {
@@ -190,8 +453,24 @@ mod tests {
             SettingsMenu::from_settings(vec![SettingType::Fluid(fluid_ui_setting)], false);
 
         //---------- Test's purpouse -----------
-        renderer.update_configs(&settings_menu.settings_menu);
+        assert!(renderer.update_configs(&settings_menu.settings_menu));
 
         assert_eq!(renderer.next_fluid_configs.diffusion, 0.4212312);
+
+        // Nothing has changed since the last update
+        assert!(!renderer.update_configs(&settings_menu.settings_menu));
+    }
+
+    #[test]
+    fn viewport_changes_dont_change_the_fluid() {
+        let mut renderer = Renderer::default();
+        let mut viewport_widget = ViewportWidget::default();
+        viewport_widget.viewport_configs.image_resize_factor = 42;
+        viewport_widget.viewport_configs.visualization.field = VisualizedField::Vorticity;
+        let settings_menu =
+            SettingsMenu::from_settings(vec![SettingType::Viewport(viewport_widget)], false);
+
+        assert!(!renderer.update_configs(&settings_menu.settings_menu));
+        assert_eq!(renderer.next_viewport_configs.image_resize_factor, 42);
     }
 }
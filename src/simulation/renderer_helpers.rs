@@ -1,19 +1,20 @@
+use crate::simulation::checkpoint::Checkpoint;
+use crate::simulation::configs::{
+    AnimationConfigs, DyeConfigs, FluidConfigs, OutputFields, OutputFormat, TimeStepping,
+    ViewportConfigs, VisualizationConfigs, VisualizedField,
+};
+use crate::simulation::emitter::Emitter;
 use crate::simulation::fluid::ContainerWall;
 use crate::simulation::fluid::Fluid;
-use crate::simulation::obstacle::ObstaclesType;
+use crate::simulation::obstacle::{Obstacle, ObstaclesType};
+use crate::simulation::output::{self, FrameField};
+use crate::simulation::pressure::PressureSolve;
+use crate::simulation::visualization;
 use simplelog::*;
 use std::fs;
+use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::mpsc::{Receiver, Sender};
-
-/// Creates a name of the a rendered density file based on the frame number and
-/// a given directory
-macro_rules! density_img_path {
-    ($save_into_dir:expr, $frame_number:expr) => {
-        &($save_into_dir.clone().to_owned() + "/density" + &$frame_number.to_string() + ".jpg")
-    };
-}
-
-pub(crate) use density_img_path;
+use std::sync::Arc;
 
 /// Saves the state of the current step of the fluid. The purpose of this structure is to be sent
 /// over from [`CurrentSimulation`](crate::simulation::renderer_helpers::CurrentSimulation) to
@@ -23,6 +24,125 @@ pub struct FluidStep {
     frame_number: i64,
 }
 
+/// A frame of the simulation rendered into an image. It is sent over from the
+/// [`RenderingListener`] to whoever displays the simulation, e.g. the
+/// [`App`](crate::app::app::App), so the frames can be shown without reading them back from the
+/// disk.
+pub struct RenderedFrame {
+    /// The number of the frame in the simulation
+    pub frame_number: i64,
+    /// The rendered image. Every pixel corresponds to a single cell of the fluid's container.
+    pub image: image::RgbaImage,
+    /// The outcome of the last solve of the pressure equation in the frame
+    pub pressure_solve: PressureSolve,
+    /// The number of steps the frame has been simulated in. See [`Fluid::substeps`].
+    pub substeps: u32,
+}
+
+/// Density and velocity painted by the user into the running simulation, e.g. by dragging the
+/// mouse over the viewport. It is sent over from the [`App`](crate::app::app::App) to the
+/// [`CurrentSimulation`] through [`Renderer::paint()`](crate::simulation::renderer::Renderer::paint()).
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub struct BrushStroke {
+    /// The cell in the center of the stroke
+    pub center: (u32, u32),
+    /// The radius of the stroke in cells
+    pub radius: u32,
+    /// The density added to every cell of the stroke
+    pub density: f32,
+    /// The velocity added to every cell of the stroke
+    pub velocity: (f32, f32),
+}
+
+impl BrushStroke {
+    /// Adds the stroke's density and velocity to all the cells in its radius which are not walls.
+    pub fn apply(&self, fluid: &mut Fluid) {
+        let width = i64::from(fluid.simulation_configs.width);
+        let height = i64::from(fluid.simulation_configs.height);
+        let radius = self.radius as i64;
+        let (center_x, center_y) = (self.center.0 as i64, self.center.1 as i64);
+
+        for y in (center_y - radius).max(0)..=(center_y + radius).min(height - 1) {
+            for x in (center_x - radius).max(0)..=(center_x + radius).min(width - 1) {
+                let is_inside = (x - center_x).pow(2) + (y - center_y).pow(2) <= radius.pow(2);
+                if !is_inside
+                    || fluid.cells_type[idx!(x, y, width, height)] != ContainerWall::NoWall
+                {
+                    continue;
+                }
+
+                fluid.add_density(x as u32, y as u32, self.density);
+                fluid.add_velocity(x as u32, y as u32, self.velocity.0, self.velocity.1);
+            }
+        }
+    }
+}
+
+/// A change of the configurations sent to the running [`CurrentSimulation`]. It takes effect
+/// before the next step of the fluid. See
+/// [`LiveChanges::Continue`](crate::simulation::configs::LiveChanges::Continue).
+#[derive(Clone)]
+pub enum SimulationChange {
+    /// New fluid configurations, e.g. viscosity, diffusion, colors or noise
+    Fluid(FluidConfigs),
+    /// New collection of all the obstacles
+    Obstacles(Vec<ObstaclesType>),
+    /// New configurations of the dyes. See [`Fluid::set_dyes()`].
+    Dyes(Vec<DyeConfigs>),
+    /// Removal of the dye with the given index. See [`Fluid::remove_dye()`] and
+    /// [`Emitter::remove_dye()`].
+    RemoveDye(usize),
+}
+
+/// Pauses, resumes and cancels a running [`CurrentSimulation`] from another thread. All the clones
+/// of the control share the same state, so the one kept by the
+/// [`Renderer`](crate::simulation::renderer::Renderer) controls the one moved into the simulation
+/// thread.
+#[derive(Clone, Default, Debug)]
+pub struct SimulationControl {
+    is_paused: Arc<AtomicBool>,
+    is_cancelled: Arc<AtomicBool>,
+}
+
+impl SimulationControl {
+    /// How often a paused simulation checks if it has been resumed or cancelled
+    const PAUSE_CHECK_INTERVAL: std::time::Duration = std::time::Duration::from_millis(20);
+
+    /// Pauses the simulation before its next step.
+    pub fn pause(&self) {
+        self.is_paused.store(true, Ordering::SeqCst);
+    }
+
+    /// Resumes the paused simulation.
+    pub fn resume(&self) {
+        self.is_paused.store(false, Ordering::SeqCst);
+    }
+
+    /// Stops the simulation before its next step. A cancelled simulation can't be resumed.
+    pub fn cancel(&self) {
+        self.is_cancelled.store(true, Ordering::SeqCst);
+    }
+
+    /// Checks if the simulation is paused.
+    pub fn is_paused(&self) -> bool {
+        self.is_paused.load(Ordering::SeqCst)
+    }
+
+    /// Checks if the simulation is cancelled.
+    pub fn is_cancelled(&self) -> bool {
+        self.is_cancelled.load(Ordering::SeqCst)
+    }
+
+    /// Blocks while the simulation is paused. Returns `false` if the simulation has been cancelled
+    /// and should not continue.
+    fn wait_while_paused(&self) -> bool {
+        while self.is_paused() && !self.is_cancelled() {
+            std::thread::sleep(Self::PAUSE_CHECK_INTERVAL);
+        }
+        !self.is_cancelled()
+    }
+}
+
 /// Performs the simulation of the fluid. It sends a
 /// [`FluidStep`](crate::simulation::renderer_helpers::FluidStep) over a [`std::sync::mpsc`]
 /// channel directly to the [`crate::simulation::renderer::Renderer`].
@@ -34,6 +154,10 @@ pub struct CurrentSimulation {
     /// Collection of all the obstacles. To update the fluid's behaviour to correspond to the
     /// obstacles, use [`crate::simulation::renderer::Renderer::update_configs()`].
     pub obstacles: Vec<ObstaclesType>,
+
+    /// Collection of all the emitters. They are applied to the fluid before every step of the
+    /// simulation. See [`Emitter`].
+    pub emitters: Vec<Emitter>,
 }
 
 impl Default for CurrentSimulation {
@@ -43,26 +167,67 @@ impl Default for CurrentSimulation {
             obstacles: vec![ObstaclesType::Rectangle(
                 crate::simulation::obstacle::Rectangle::default(),
             )],
+            emitters: Vec::new(),
         }
     }
 }
 
 impl CurrentSimulation {
-    /// Runs the fluid simulation
-    pub fn simulate(&mut self, tx: Sender<FluidStep>) {
+    /// Runs the fluid simulation. The brush strokes and the changes of the configurations received
+    /// before each step are applied to the fluid right away. See [`BrushStroke`] and
+    /// [`SimulationChange`].
+    ///
+    /// The simulation checks the `control` before every step, so it can be paused or stopped
+    /// early. It also stops if nobody listens for the steps anymore.
+    pub fn simulate(
+        &mut self,
+        tx: Sender<FluidStep>,
+        brush_strokes: Receiver<BrushStroke>,
+        changes: Receiver<SimulationChange>,
+        control: SimulationControl,
+    ) {
         self.mark_fluid_obstacles();
         for i in 0..self.fluid.simulation_configs.frames {
+            if !control.wait_while_paused() {
+                simplelog::debug!("CurrentSimulation: cancelled before frame {}", i);
+                return;
+            }
+
+            for change in changes.try_iter() {
+                self.apply_change(change);
+            }
+
             if self.fluid.fluid_configs.has_perlin_noise {
                 self.fluid.add_noise();
             }
 
-            self.fluid.step();
+            for emitter in self.emitters.iter().filter(|emitter| emitter.is_active(i)) {
+                emitter.emit(&mut self.fluid);
+            }
 
-            tx.send(FluidStep {
-                fluid: self.fluid.clone(),
-                frame_number: i,
-            })
-            .unwrap();
+            for brush_stroke in brush_strokes.try_iter() {
+                brush_stroke.apply(&mut self.fluid);
+            }
+
+            self.fluid.advance();
+            if let TimeStepping::Adaptive { .. } = self.fluid.simulation_configs.time_stepping {
+                simplelog::info!(
+                    "CurrentSimulation: frame {} is simulated in {} sub-steps",
+                    i,
+                    self.fluid.substeps
+                );
+            }
+
+            if tx
+                .send(FluidStep {
+                    fluid: self.fluid.clone(),
+                    frame_number: i,
+                })
+                .is_err()
+            {
+                simplelog::debug!("CurrentSimulation: the listener has been disconnected");
+                return;
+            }
 
             simplelog::debug!(
                 "CurrentSimulation: sent a signal that a frame {} is ready",
@@ -71,10 +236,31 @@ impl CurrentSimulation {
         }
     }
 
+    /// Applies the change of the configurations to the fluid.
+    fn apply_change(&mut self, change: SimulationChange) {
+        match change {
+            SimulationChange::Fluid(fluid_configs) => self.fluid.fluid_configs = fluid_configs,
+            SimulationChange::Obstacles(obstacles) => {
+                self.obstacles = obstacles;
+                self.fluid.clear_obstacles();
+                self.mark_fluid_obstacles();
+            }
+            SimulationChange::Dyes(dyes) => self.fluid.set_dyes(&dyes),
+            SimulationChange::RemoveDye(index) => {
+                self.fluid.remove_dye(index);
+                Emitter::remove_dye(&mut self.emitters, index);
+            }
+        }
+    }
+
     /// After altering the obstacles list. Refresh the fluid's configuration regarding its
     /// obstacles.
     fn mark_fluid_obstacles(&mut self) {
         for obstacle in self.obstacles.iter_mut() {
+            obstacle.fit_into_container(
+                self.fluid.simulation_configs.width,
+                self.fluid.simulation_configs.height,
+            );
             self.fluid.fill_obstacle(obstacle);
         }
     }
@@ -88,122 +274,325 @@ pub struct RenderingListener {
     pub save_into_dir: String,
 
     /// The color of all obstacles. Obstacles cannot be set individual colors.
-    pub obstacles_color: eframe::egui::Color32,
+    pub obstacles_color: epaint::Color32,
+
+    /// If set, every rendered frame is also saved into the [`RenderingListener::save_into_dir`].
+    /// Otherwise the frames are only kept in memory.
+    pub save_images: bool,
+
+    /// The format of the saved frames
+    pub output_format: OutputFormat,
+
+    /// The fields of the fluid which are saved for every frame
+    pub output_fields: OutputFields,
+
+    /// A [`Checkpoint`] is saved into the [`RenderingListener::save_into_dir`] every that many
+    /// frames. `0` means no checkpoints are saved.
+    pub checkpoint_interval: u32,
+
+    /// The field of the fluid shown in the rendered frames and its colormap
+    pub visualization: VisualizationConfigs,
 }
 
 impl Default for RenderingListener {
     fn default() -> Self {
         Self {
             save_into_dir: RenderingListener::make_save_into_dir("rendered_images"),
-            obstacles_color: eframe::egui::Color32::RED,
+            obstacles_color: epaint::Color32::RED,
+            save_images: true,
+            output_format: OutputFormat::Png,
+            output_fields: OutputFields::default(),
+            checkpoint_interval: 0,
+            visualization: VisualizationConfigs::default(),
         }
     }
 }
 
 impl RenderingListener {
-    /// Creates the default directory in which the result images will be saved.
+    /// Creates new RenderingListener which saves the frames according to the given viewport
+    /// configurations.
+    pub fn new(viewport_configs: &ViewportConfigs, obstacles_color: epaint::Color32) -> Self {
+        Self {
+            save_into_dir: RenderingListener::make_save_into_dir(&viewport_configs.save_into_dir),
+            obstacles_color,
+            save_images: viewport_configs.save_images,
+            output_format: viewport_configs.output_format,
+            output_fields: viewport_configs.output_fields,
+            checkpoint_interval: viewport_configs.checkpoint_interval,
+            visualization: viewport_configs.visualization,
+        }
+    }
+
+    /// Creates the default directory in which the result images will be saved. Relative paths are
+    /// resolved against the project's root, or against the current directory when the program is
+    /// not run from within the project (e.g. the headless binary on a build server). Absolute
+    /// paths are kept as they are.
     pub fn make_save_into_dir(dir_name: &str) -> String {
+        if std::path::Path::new(dir_name).is_absolute() {
+            return dir_name.to_string();
+        }
+
         let project_root = project_root::get_project_root()
+            .or_else(|_| std::env::current_dir())
             .unwrap()
             .to_str()
             .unwrap()
             .to_string();
-        project_root + "/" + &dir_name
+        project_root + "/" + dir_name
     }
 
-    /// Creates the file where the result image is rendered.
-    fn render_image(&self, fluid_step: FluidStep) {
-        simplelog::debug!(
-            "RenderingListener: Received a signal that a frame is ready! Starting to render; Saving into: {}",
-            density_img_path!(self.save_into_dir, fluid_step.frame_number)
-        );
-        let fluid = fluid_step.fluid;
+    /// Renders the state of the fluid into an image. Every pixel corresponds to a single cell of
+    /// the fluid's container. With [`VisualizedField::Colors`] the world's color fades into the
+    /// fluid's color as the density grows to `1.0`, and the dyes are blended over them one after
+    /// another (see [`RenderingListener::blend_dye()`]). The other fields are mapped onto their
+    /// colormap, see [`visualization::render_field()`]. The image is opaque: the alpha channel of
+    /// the fluid's colors is ignored.
+    pub fn render_image(&self, fluid: &Fluid) -> image::RgbaImage {
+        if self.visualization.field != VisualizedField::Colors {
+            return visualization::render_field(&self.visualization, fluid, self.obstacles_color);
+        }
 
         let world_rgba = [
             fluid.fluid_configs.world_color.r(),
             fluid.fluid_configs.world_color.g(),
             fluid.fluid_configs.world_color.b(),
-            fluid.fluid_configs.world_color.a(),
         ];
 
         let fluid_rgba = [
             fluid.fluid_configs.fluid_color.r(),
             fluid.fluid_configs.fluid_color.g(),
             fluid.fluid_configs.fluid_color.b(),
-            fluid.fluid_configs.fluid_color.a(),
         ];
 
         let obstacles_rgba = [
             self.obstacles_color.r(),
             self.obstacles_color.g(),
             self.obstacles_color.b(),
-            self.obstacles_color.a(),
         ];
 
-        let mut imgbuf =
-            image::ImageBuffer::new(fluid.simulation_configs.size, fluid.simulation_configs.size);
+        let (width, height) = (
+            fluid.simulation_configs.width,
+            fluid.simulation_configs.height,
+        );
+        let mut imgbuf = image::ImageBuffer::new(width, height);
 
         for (x, y, pixel) in imgbuf.enumerate_pixels_mut() {
-            let density = fluid.density[idx!(x, y, fluid.simulation_configs.size)];
-            let cell_type = fluid.cells_type[idx!(x, y, fluid.simulation_configs.size)];
+            let density = fluid.density[idx!(x, y, width, height)];
+            let cell_type = fluid.cells_type[idx!(x, y, width, height)];
 
             if cell_type == ContainerWall::DefaultWall {
                 *pixel = image::Rgba([
                     obstacles_rgba[0],
                     obstacles_rgba[1],
                     obstacles_rgba[2],
-                    obstacles_rgba[3],
-                ]);
-            } else if density != 0.0 && cell_type == ContainerWall::NoWall {
-                *pixel = image::Rgba([
-                    (density * fluid_rgba[0] as f32) as u8,
-                    fluid_rgba[1],
-                    density as u8,
-                    1,
+                    u8::MAX,
                 ]);
             } else {
-                *pixel = image::Rgba(world_rgba);
+                let mut rgb = world_rgba;
+                if cell_type == ContainerWall::NoWall {
+                    let amount = density.clamp(0.0, 1.0);
+                    for (channel, (world, fluid)) in
+                        rgb.iter_mut().zip(world_rgba.iter().zip(fluid_rgba))
+                    {
+                        let (world, fluid) = (f32::from(*world), f32::from(fluid));
+                        *channel = (world + amount * (fluid - world)).round() as u8;
+                    }
+                }
+
+                for dye in fluid.dyes.iter() {
+                    rgb =
+                        Self::blend_dye(rgb, dye.density[idx!(x, y, width, height)], &dye.configs);
+                }
+
+                *pixel = image::Rgba([rgb[0], rgb[1], rgb[2], u8::MAX]);
             }
         }
 
-        if !std::path::Path::new(&self.save_into_dir).exists() {
-            fs::create_dir(&self.save_into_dir)
-                .expect("Error while creating a directory to store the simulation results.");
+        imgbuf
+    }
+
+    /// Blends the color of the dye over the given color with the "over" operator. The opacity of
+    /// the dye is the alpha of its color scaled by its density, which is clamped to `[0, 1]`.
+    fn blend_dye(rgb: [u8; 3], density: f32, dye: &DyeConfigs) -> [u8; 3] {
+        // The colors are premultiplied by their alpha
+        let coverage = density.clamp(0.0, 1.0);
+        let transparency = 1.0 - coverage * f32::from(dye.color.a()) / f32::from(u8::MAX);
+        let dye_rgb = [dye.color.r(), dye.color.g(), dye.color.b()];
+
+        let mut result = [0; 3];
+        for (channel, (base, dye)) in result.iter_mut().zip(rgb.iter().zip(dye_rgb)) {
+            *channel = (coverage * f32::from(dye) + transparency * f32::from(*base))
+                .round()
+                .min(f32::from(u8::MAX)) as u8;
         }
+        result
+    }
 
-        imgbuf
-            .save(density_img_path!(
-                self.save_into_dir,
-                fluid_step.frame_number
-            ))
-            .expect("Coulnt't save density image");
+    /// Saves the selected fields of the given frame into the [`RenderingListener::save_into_dir`],
+    /// a file per field. See [`output::save_field()`].
+    fn save_frame(&self, fluid_step: &FluidStep, image: &image::RgbaImage) -> std::io::Result<()> {
+        simplelog::debug!(
+            "RenderingListener: Saving frame {} into: {}",
+            fluid_step.frame_number,
+            self.save_into_dir
+        );
+
+        fs::create_dir_all(&self.save_into_dir).map_err(|e| {
+            std::io::Error::new(
+                e.kind(),
+                format!("couldn't create directory {}: {}", self.save_into_dir, e),
+            )
+        })?;
+
+        for field in FrameField::ALL
+            .iter()
+            .filter(|field| field.is_selected(&self.output_fields))
+        {
+            let path = output::frame_path(
+                &self.save_into_dir,
+                *field,
+                fluid_step.frame_number,
+                self.output_format,
+            );
+
+            output::save_field(&path, *field, &fluid_step.fluid, image, self.output_format)
+                .map_err(|e| {
+                    std::io::Error::new(e.kind(), format!("couldn't save {}: {}", path, e))
+                })?;
+        }
+        Ok(())
+    }
+
+    /// Saves the state of the fluid after the given frame as a [`Checkpoint`] into the
+    /// [`RenderingListener::save_into_dir`].
+    fn save_checkpoint(&self, fluid_step: &FluidStep) -> std::io::Result<()> {
+        let path = Checkpoint::path(&self.save_into_dir, fluid_step.frame_number);
+        let checkpoint = Checkpoint {
+            frame_number: fluid_step.frame_number,
+            fluid: fluid_step.fluid.clone(),
+        };
+
+        fs::create_dir_all(&self.save_into_dir)
+            .and_then(|_| checkpoint.save(&path))
+            .map_err(|e| {
+                std::io::Error::new(
+                    e.kind(),
+                    format!("couldn't save checkpoint {}: {}", path, e),
+                )
+            })
+    }
+
+    /// Saves the frame if [`RenderingListener::save_images`] is set, and a checkpoint every
+    /// [`RenderingListener::checkpoint_interval`] frames.
+    fn save_results(
+        &self,
+        fluid_step: &FluidStep,
+        image: &image::RgbaImage,
+    ) -> std::io::Result<()> {
+        if self.save_images {
+            self.save_frame(fluid_step, image)?;
+        }
+
+        if self.checkpoint_interval > 0
+            && (fluid_step.frame_number + 1) % i64::from(self.checkpoint_interval) == 0
+        {
+            self.save_checkpoint(fluid_step)?;
+        }
+        Ok(())
+    }
+
+    /// Exports the rendered frames as a single animation into the
+    /// [`RenderingListener::save_into_dir`]. Returns the path of the animation file. See
+    /// [`output::write_animation()`].
+    pub fn export_animation(
+        &self,
+        frames: &[image::RgbaImage],
+        animation_configs: &AnimationConfigs,
+    ) -> std::io::Result<String> {
+        fs::create_dir_all(&self.save_into_dir)?;
+        let path = format!(
+            "{}/animation.{}",
+            self.save_into_dir,
+            animation_configs.format.extension()
+        );
+
+        let mut writer = std::io::BufWriter::new(fs::File::create(&path)?);
+        output::write_animation(&mut writer, frames, animation_configs)?;
+        std::io::Write::flush(&mut writer)?;
+
+        simplelog::info!("RenderingListener: animation exported into {}", path);
+        Ok(path)
     }
 
     /// Listens for a signal from [`CurrentSimulation`] that a frame is ready, and then renders it.
+    /// The rendered frames are sent over through the `rendering_tx`, and also saved into the
+    /// [`RenderingListener::save_into_dir`] if [`RenderingListener::save_images`] is set.
+    ///
+    /// The colors received through the `obstacles_colors_rx` replace the
+    /// [`RenderingListener::obstacles_color`] from the next rendered frame on.
+    ///
+    /// The listening stops early if the simulation has been stopped through the
+    /// `simulation_control`, or nobody receives the rendered frames anymore. The frames still
+    /// waiting in the `simulation_rx` are not rendered after the simulation is stopped.
+    ///
+    /// If a frame or a checkpoint can't be saved, the simulation is stopped and the error is
+    /// returned. The frames rendered before it have already been sent.
     pub fn listen(
-        &self,
+        &mut self,
         max_frames: i64,
         simulation_rx: Receiver<FluidStep>,
-        rendering_tx: Sender<i64>,
-    ) {
-        for i in 0..max_frames {
-            self.render_image(simulation_rx.recv().expect(
-                "Simulation sender has been disconnected. Cannot listen through RenderingListener",
-            ));
+        obstacles_colors_rx: Receiver<epaint::Color32>,
+        rendering_tx: Sender<RenderedFrame>,
+        simulation_control: SimulationControl,
+    ) -> std::io::Result<()> {
+        for (i, fluid_step) in simulation_rx.iter().take(max_frames as usize).enumerate() {
+            if simulation_control.is_cancelled() {
+                simplelog::debug!("RenderingListener: the simulation has been stopped");
+                return Ok(());
+            }
 
-            rendering_tx
-                .send(i)
-                .expect("Could not properly send current frame number through rendering_tx");
+            if let Some(obstacles_color) = obstacles_colors_rx.try_iter().last() {
+                self.obstacles_color = obstacles_color;
+            }
+
+            let image = self.render_image(&fluid_step.fluid);
+
+            if let Err(error) = self.save_results(&fluid_step, &image) {
+                error!("RenderingListener: {}", error);
+                simulation_control.cancel();
+                return Err(error);
+            }
+
+            if rendering_tx
+                .send(RenderedFrame {
+                    frame_number: fluid_step.frame_number,
+                    image,
+                    pressure_solve: fluid_step.fluid.pressure_solve,
+                    substeps: fluid_step.fluid.substeps,
+                })
+                .is_err()
+            {
+                simplelog::debug!("RenderingListener: the receiver has been disconnected");
+                return Ok(());
+            }
 
             simplelog::debug!("RenderingListener: frame {} is rendered!", i);
         }
+        Ok(())
     }
 }
 
 #[cfg(test)]
 mod tests {
-    use crate::simulation::obstacle::Obstacle;
+    use crate::simulation::configs::{DyeConfigs, FluidConfigs, OutputFormat, SimulationConfigs};
+    use crate::simulation::emitter::Emitter;
+    use crate::simulation::obstacle::{Obstacle, ObstaclesType, Rectangle};
+    use crate::simulation::output::{frame_path, FrameField};
+    use crate::simulation::renderer_helpers::{
+        BrushStroke, FluidStep, RenderingListener, SimulationChange, SimulationControl,
+    };
     use crate::simulation::{fluid::ContainerWall, renderer_helpers::CurrentSimulation};
+    use crate::simulation::{fluid::Fluid, renderer::Renderer};
 
     fn calc_height<T>(a: (T, T), b: (T, T)) -> T::Output
     where
@@ -219,6 +608,14 @@ mod tests {
         b.0 - a.0
     }
 
+    #[test]
+    fn density_img_path_str_works() {
+        assert_eq!(
+            frame_path("holiday_dir", FrameField::Density, 0, OutputFormat::Jpeg),
+            "holiday_dir/density0.jpg"
+        );
+    }
+
     #[test]
     fn mark_fluid_obstacles_default_current_simulation() {
         let mut current_simulation = CurrentSimulation::default();
@@ -230,18 +627,14 @@ mod tests {
             .filter(|&el| el == &ContainerWall::DefaultWall)
             .count();
 
-        let size = current_simulation.fluid.simulation_configs.size;
-        let image_parameter = 2 * (size + (size - 2));
+        let width = current_simulation.fluid.simulation_configs.width;
+        let height = current_simulation.fluid.simulation_configs.height;
+        let image_parameter = 2 * (width + (height - 2));
 
         // Sums up the area of all obstacles. NB: Assumes all obstacles are rectangles
         let obstacles_area = current_simulation.obstacles.iter().fold(0, |acc, x| {
-            acc + calc_width(
-                x.clone().get_approximate_points()[0],
-                x.clone().get_approximate_points()[1],
-            ) * calc_height(
-                x.clone().get_approximate_points()[0],
-                x.clone().get_approximate_points()[1],
-            )
+            acc + calc_width(x.bounding_box().0, x.bounding_box().1)
+                * calc_height(x.bounding_box().0, x.bounding_box().1)
         });
 
         // NB: We explicitly know how that the obstacle does not overlap with the perimeter.
@@ -250,4 +643,381 @@ mod tests {
         // Safety note: Default wall size is way smaller than usize.
         assert_eq!(default_walls_real_count, expected_count as usize);
     }
+
+    #[test]
+    fn brush_stroke_skips_walls() {
+        let mut fluid = Fluid::default();
+        let size = fluid.simulation_configs.width;
+        let density_before = fluid.density.clone();
+        let velocity_before = fluid.velocities_x[idx!(1, 11, size, size)];
+
+        BrushStroke {
+            center: (1, 10),
+            radius: 1,
+            density: 0.25,
+            velocity: (2.0, 0.0),
+        }
+        .apply(&mut fluid);
+
+        // The left edge of the container is a wall
+        assert_eq!(
+            fluid.density[idx!(0, 10, size, size)],
+            density_before[idx!(0, 10, size, size)]
+        );
+        assert_eq!(
+            fluid.density[idx!(1, 10, size, size)],
+            density_before[idx!(1, 10, size, size)] + 0.25
+        );
+        assert_eq!(
+            fluid.density[idx!(2, 10, size, size)],
+            density_before[idx!(2, 10, size, size)] + 0.25
+        );
+        assert_eq!(
+            fluid.density[idx!(2, 11, size, size)],
+            density_before[idx!(2, 11, size, size)]
+        );
+        assert_eq!(
+            fluid.velocities_x[idx!(1, 11, size, size)],
+            velocity_before + 2.0
+        );
+    }
+
+    #[test]
+    fn paint_without_running_simulation() {
+        let renderer = Renderer::default();
+        assert!(!renderer.paint(BrushStroke {
+            center: (10, 10),
+            radius: 1,
+            density: 1.0,
+            velocity: (0.0, 0.0),
+        }));
+    }
+
+    #[test]
+    fn listen_keeps_frames_in_memory() {
+        let save_into_dir = std::env::temp_dir()
+            .join("equilibrium_listen_keeps_frames_in_memory")
+            .to_str()
+            .unwrap()
+            .to_string();
+        let mut rendering_listener = RenderingListener {
+            save_into_dir: save_into_dir.clone(),
+            save_images: false,
+            ..RenderingListener::default()
+        };
+
+        let (simulation_tx, simulation_rx) = std::sync::mpsc::channel();
+        let (rendering_tx, rendering_rx) = std::sync::mpsc::channel();
+        for frame_number in 0..2 {
+            simulation_tx
+                .send(FluidStep {
+                    fluid: Fluid::default(),
+                    frame_number,
+                })
+                .unwrap();
+        }
+
+        let (obstacles_colors_tx, obstacles_colors_rx) = std::sync::mpsc::channel();
+        obstacles_colors_tx.send(epaint::Color32::GREEN).unwrap();
+        rendering_listener
+            .listen(
+                2,
+                simulation_rx,
+                obstacles_colors_rx,
+                rendering_tx,
+                SimulationControl::default(),
+            )
+            .unwrap();
+
+        let rendered_frames: Vec<_> = rendering_rx.try_iter().collect();
+        assert_eq!(rendered_frames.len(), 2);
+        assert_eq!(rendered_frames[1].frame_number, 1);
+        assert_eq!(rendered_frames[1].image.dimensions(), (128, 128));
+        // The walls have the color sent while listening
+        assert_eq!(rendered_frames[0].image.get_pixel(0, 0).0, [0, 255, 0, 255]);
+        assert!(!std::path::Path::new(&save_into_dir).exists());
+    }
+
+    fn small_simulation(frames: i64) -> CurrentSimulation {
+        CurrentSimulation {
+            fluid: Fluid::new(
+                FluidConfigs::default(),
+                SimulationConfigs::new(0.5, frames, 32),
+            ),
+            obstacles: Vec::new(),
+            emitters: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn listen_fails_when_frames_cannot_be_saved() {
+        // The directory can't be created inside of a file
+        let file =
+            std::env::temp_dir().join("equilibrium_listen_fails_when_frames_cannot_be_saved");
+        std::fs::write(&file, b"").unwrap();
+        let mut rendering_listener = RenderingListener {
+            save_into_dir: file.join("frames").to_str().unwrap().to_string(),
+            ..RenderingListener::default()
+        };
+
+        let (simulation_tx, simulation_rx) = std::sync::mpsc::channel();
+        let (rendering_tx, rendering_rx) = std::sync::mpsc::channel();
+        for frame_number in 0..2 {
+            simulation_tx
+                .send(FluidStep {
+                    fluid: Fluid::default(),
+                    frame_number,
+                })
+                .unwrap();
+        }
+        let simulation_control = SimulationControl::default();
+
+        assert!(rendering_listener
+            .listen(
+                2,
+                simulation_rx,
+                std::sync::mpsc::channel().1,
+                rendering_tx,
+                simulation_control.clone(),
+            )
+            .is_err());
+        // The simulation is stopped, and the frame isn't sent
+        assert!(simulation_control.is_cancelled());
+        assert_eq!(rendering_rx.try_iter().count(), 0);
+    }
+
+    #[test]
+    fn cancelled_simulation_stops() {
+        let (simulation_tx, simulation_rx) = std::sync::mpsc::channel();
+        let (_brush_strokes_tx, brush_strokes_rx) = std::sync::mpsc::channel();
+        let (_changes_tx, changes_rx) = std::sync::mpsc::channel();
+        let control = SimulationControl::default();
+        control.pause();
+        control.cancel();
+
+        small_simulation(5).simulate(simulation_tx, brush_strokes_rx, changes_rx, control);
+
+        assert_eq!(simulation_rx.try_iter().count(), 0);
+    }
+
+    #[test]
+    fn paused_simulation_waits_for_resume() {
+        let (simulation_tx, simulation_rx) = std::sync::mpsc::channel();
+        let (_brush_strokes_tx, brush_strokes_rx) = std::sync::mpsc::channel();
+        let (_changes_tx, changes_rx) = std::sync::mpsc::channel();
+        let control = SimulationControl::default();
+        control.pause();
+
+        let simulation_control = control.clone();
+        let simulation_handle = std::thread::spawn(move || {
+            small_simulation(3).simulate(
+                simulation_tx,
+                brush_strokes_rx,
+                changes_rx,
+                simulation_control,
+            );
+        });
+
+        std::thread::sleep(std::time::Duration::from_millis(100));
+        assert!(simulation_rx.try_recv().is_err());
+
+        control.resume();
+        simulation_handle.join().unwrap();
+        assert_eq!(simulation_rx.try_iter().count(), 3);
+    }
+
+    #[test]
+    fn disconnected_channels_stop_the_threads() {
+        // Nobody listens for the simulation's steps
+        let (simulation_tx, simulation_rx) = std::sync::mpsc::channel();
+        drop(simulation_rx);
+        let (_brush_strokes_tx, brush_strokes_rx) = std::sync::mpsc::channel();
+        let (_changes_tx, changes_rx) = std::sync::mpsc::channel();
+        small_simulation(3).simulate(
+            simulation_tx,
+            brush_strokes_rx,
+            changes_rx,
+            SimulationControl::default(),
+        );
+
+        // The simulation stops before all the frames are sent
+        let (simulation_tx, simulation_rx) = std::sync::mpsc::channel();
+        let (rendering_tx, rendering_rx) = std::sync::mpsc::channel();
+        simulation_tx
+            .send(FluidStep {
+                fluid: Fluid::default(),
+                frame_number: 0,
+            })
+            .unwrap();
+        drop(simulation_tx);
+        let mut rendering_listener = RenderingListener {
+            save_images: false,
+            ..RenderingListener::default()
+        };
+        rendering_listener
+            .listen(
+                10,
+                simulation_rx,
+                std::sync::mpsc::channel().1,
+                rendering_tx,
+                SimulationControl::default(),
+            )
+            .unwrap();
+        assert_eq!(rendering_rx.try_iter().count(), 1);
+    }
+
+    #[test]
+    fn listen_stops_with_the_simulation() {
+        let (simulation_tx, simulation_rx) = std::sync::mpsc::channel();
+        let (rendering_tx, rendering_rx) = std::sync::mpsc::channel();
+        for frame_number in 0..3 {
+            simulation_tx
+                .send(FluidStep {
+                    fluid: Fluid::default(),
+                    frame_number,
+                })
+                .unwrap();
+        }
+        let simulation_control = SimulationControl::default();
+        simulation_control.cancel();
+
+        // The queued frames are neither rendered nor saved
+        let mut rendering_listener = RenderingListener {
+            save_images: false,
+            ..RenderingListener::default()
+        };
+        rendering_listener
+            .listen(
+                3,
+                simulation_rx,
+                std::sync::mpsc::channel().1,
+                rendering_tx,
+                simulation_control,
+            )
+            .unwrap();
+        assert_eq!(rendering_rx.try_iter().count(), 0);
+    }
+
+    #[test]
+    fn changes_take_effect_in_running_simulation() {
+        let (simulation_tx, simulation_rx) = std::sync::mpsc::channel();
+        let (_brush_strokes_tx, brush_strokes_rx) = std::sync::mpsc::channel();
+        let (changes_tx, changes_rx) = std::sync::mpsc::channel();
+        let mut current_simulation = small_simulation(1);
+        current_simulation.obstacles = vec![ObstaclesType::Rectangle(Rectangle::new(
+            (10, 10),
+            (20, 20),
+            32,
+            32,
+        ))];
+
+        let fluid_configs = FluidConfigs {
+            viscousity: 0.5,
+            has_perlin_noise: false,
+            ..FluidConfigs::default()
+        };
+        changes_tx
+            .send(SimulationChange::Fluid(fluid_configs))
+            .unwrap();
+        changes_tx
+            .send(SimulationChange::Obstacles(Vec::new()))
+            .unwrap();
+        changes_tx
+            .send(SimulationChange::Dyes(vec![DyeConfigs::default()]))
+            .unwrap();
+
+        current_simulation.simulate(
+            simulation_tx,
+            brush_strokes_rx,
+            changes_rx,
+            SimulationControl::default(),
+        );
+
+        let fluid = &current_simulation.fluid;
+        assert!(fluid.fluid_configs == fluid_configs);
+        assert_eq!(
+            fluid.cells_type[idx!(15, 15, 32, 32)],
+            ContainerWall::NoWall
+        );
+        // The walls of the container are kept
+        assert_eq!(
+            fluid.cells_type[idx!(0, 15, 32, 32)],
+            ContainerWall::DefaultWall
+        );
+        assert_eq!(fluid.dyes.len(), 1);
+        assert_eq!(simulation_rx.try_iter().count(), 1);
+    }
+
+    #[test]
+    fn removed_dye_keeps_the_other_dyes() {
+        let mut current_simulation = small_simulation(1);
+        let dyes: Vec<_> = (1..=3)
+            .map(|i| DyeConfigs {
+                diffusion: i as f32,
+                ..DyeConfigs::default()
+            })
+            .collect();
+        current_simulation.fluid.set_dyes(&dyes);
+        for (i, dye) in current_simulation.fluid.dyes.iter_mut().enumerate() {
+            dye.density.fill(i as f32);
+        }
+        current_simulation.emitters = vec![
+            Emitter {
+                dye: Some(1),
+                ..Emitter::default()
+            },
+            Emitter {
+                dye: Some(2),
+                ..Emitter::default()
+            },
+        ];
+
+        current_simulation.apply_change(SimulationChange::RemoveDye(1));
+
+        let fluid = &current_simulation.fluid;
+        assert_eq!(fluid.dyes.len(), 2);
+        assert_eq!(fluid.dyes[1].configs, dyes[2]);
+        assert_eq!(fluid.dyes[1].density[0], 2.0);
+        assert_eq!(current_simulation.emitters.len(), 1);
+        assert_eq!(current_simulation.emitters[0].dye, Some(1));
+    }
+
+    #[test]
+    fn dyes_are_blended_over_the_fluid() {
+        let mut fluid = Fluid::new(FluidConfigs::default(), SimulationConfigs::new(0.5, 1, 32));
+        fluid.density.fill(0.0);
+        let opaque = DyeConfigs {
+            color: epaint::Color32::from_rgb(0, 0, 200),
+            ..DyeConfigs::default()
+        };
+        let transparent = DyeConfigs {
+            color: epaint::Color32::from_rgba_premultiplied(0, 100, 0, 128),
+            ..DyeConfigs::default()
+        };
+        fluid.set_dyes(&[opaque, transparent]);
+        fluid.dyes[0].add_density(idx!(5, 5, 32, 32), 1.0);
+        fluid.dyes[0].add_density(idx!(6, 5, 32, 32), 0.5);
+        fluid.dyes[1].add_density(idx!(6, 5, 32, 32), 2.0);
+
+        let image = RenderingListener::default().render_image(&fluid);
+        let world_color = fluid.fluid_configs.world_color;
+
+        assert_eq!(image.get_pixel(5, 5).0, [0, 0, 200, u8::MAX]);
+        // Half of the opaque dye over the world, and then the transparent dye over both of them
+        let half_blue = |channel: u8| (0.5 * f32::from(channel)).round();
+        let expected = [
+            (0.498 * half_blue(world_color.r())).round() as u8,
+            (100.0 + 0.498 * half_blue(world_color.g())).round() as u8,
+            (0.498 * (100.0 + half_blue(world_color.b()))).round() as u8,
+            u8::MAX,
+        ];
+        let pixel = image.get_pixel(6, 5).0;
+        for channel in 0..4 {
+            assert!((i16::from(pixel[channel]) - i16::from(expected[channel])).abs() <= 1);
+        }
+        assert_eq!(
+            image.get_pixel(7, 5).0,
+            [world_color.r(), world_color.g(), world_color.b(), u8::MAX]
+        );
+    }
 }
@@ -0,0 +1,444 @@
+use crate::simulation::configs::{
+    AnimationConfigs, DyeConfigs, FluidConfigs, SimulationConfigs, ViewportConfigs,
+};
+use crate::simulation::emitter::Emitter;
+use crate::simulation::obstacle::{Obstacle, ObstaclesType, Rectangle};
+use epaint::Color32;
+use serde::{Deserialize, Serialize};
+
+/// The version of the scenario format written by the current build. Older versions can still be
+/// opened, while newer ones are rejected with [`ScenarioError::UnsupportedVersion`].
+pub const SCENARIO_VERSION: u32 = 2;
+
+/// Describes what went wrong while saving or opening a [`Scenario`].
+#[derive(Debug)]
+pub enum ScenarioError {
+    /// The scenario file couldn't be read or written.
+    Io(std::io::Error),
+    /// The scenario file is not a valid TOML document or some of its values are invalid.
+    Parse(String),
+    /// The scenario has been written by a newer version of the application.
+    UnsupportedVersion(u32),
+    /// The width or the height of the fluid's container is below [`SimulationConfigs::MIN_SIZE`].
+    ContainerTooSmall,
+    /// The obstacle with the given name and index doesn't fit in the fluid's container.
+    InvalidObstacle(&'static str, usize),
+    /// A periodic edge of the container is opposite to a non-periodic one.
+    UnpairedPeriodicBoundary,
+    /// The emitter with the given index is outside the fluid's container or its parameters are
+    /// invalid, e.g. it emits a dye which doesn't exist.
+    InvalidEmitter(usize),
+    /// The domain length or the characteristic velocity of the simulation is not positive.
+    InvalidPhysicalUnits,
+    /// The adaptive time stepping has a non-positive CFL number or no sub-steps, see
+    /// [`TimeStepping::is_valid()`](crate::simulation::configs::TimeStepping::is_valid()).
+    InvalidTimeStepping,
+    /// The frame rate of the animation is outside of the
+    /// [`AnimationConfigs::FRAME_RATES`](crate::simulation::configs::AnimationConfigs::FRAME_RATES).
+    InvalidAnimation,
+}
+
+impl std::fmt::Display for ScenarioError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ScenarioError::Io(error) => write!(f, "{}", error),
+            ScenarioError::Parse(message) => write!(f, "invalid scenario: {}", message),
+            ScenarioError::UnsupportedVersion(version) => write!(
+                f,
+                "scenario version {} is newer than the supported version {}",
+                version, SCENARIO_VERSION
+            ),
+            ScenarioError::ContainerTooSmall => write!(
+                f,
+                "the width and the height of the fluid's container must be at least {}",
+                SimulationConfigs::MIN_SIZE
+            ),
+            ScenarioError::InvalidObstacle(name, index) => write!(
+                f,
+                "obstacle #{} ({}) doesn't fit in the fluid's container",
+                index, name
+            ),
+            ScenarioError::UnpairedPeriodicBoundary => write!(
+                f,
+                "a periodic edge of the container must be opposite to a periodic edge"
+            ),
+            ScenarioError::InvalidEmitter(index) => write!(
+                f,
+                "emitter #{} is outside the fluid's container or its parameters are invalid",
+                index
+            ),
+            ScenarioError::InvalidPhysicalUnits => write!(
+                f,
+                "the domain length and the characteristic velocity must be positive"
+            ),
+            ScenarioError::InvalidTimeStepping => write!(
+                f,
+                "the CFL number of the adaptive time stepping must be positive and it must allow \
+                 at least one sub-step"
+            ),
+            ScenarioError::InvalidAnimation => write!(
+                f,
+                "the frame rate of the animation must be between {} and {}",
+                AnimationConfigs::FRAME_RATES.start(),
+                AnimationConfigs::FRAME_RATES.end()
+            ),
+        }
+    }
+}
+
+impl std::error::Error for ScenarioError {}
+
+impl From<std::io::Error> for ScenarioError {
+    fn from(error: std::io::Error) -> Self {
+        ScenarioError::Io(error)
+    }
+}
+
+/// The full setup of a simulation. It is stored as a TOML file, which can be written by
+/// [`Scenario::save()`] and read back by [`Scenario::open()`]. Every value but the `version` is
+/// optional and falls back to its default.
+///
+/// Example:
+/// ```
+/// use equilibrium::simulation::scenario::Scenario;
+///
+/// let scenario = Scenario::from_toml(
+///     r#"
+///     version = 1
+///
+///     [simulation]
+///     frames = 600
+///
+///     [[obstacles]]
+///     type = "Rectangle"
+///     down_left_point = [10, 10]
+///     up_right_point = [20, 20]
+///     "#,
+/// )
+/// .unwrap();
+///
+/// assert_eq!(scenario.simulation.frames, 600);
+/// assert_eq!(scenario.obstacles.len(), 1);
+/// ```
+#[derive(Clone, Serialize, Deserialize)]
+pub struct Scenario {
+    /// The version of the format the scenario is written in. See [`SCENARIO_VERSION`].
+    pub version: u32,
+    /// See [`FluidConfigs`]
+    #[serde(default)]
+    pub fluid: FluidConfigs,
+    /// See [`SimulationConfigs`]
+    #[serde(default)]
+    pub simulation: SimulationConfigs,
+    /// See [`ViewportConfigs`]
+    #[serde(default)]
+    pub viewport: ViewportConfigs,
+    /// The color of all obstacles
+    #[serde(default = "Scenario::default_obstacles_color")]
+    pub obstacles_color: Color32,
+    /// Collection of all the obstacles in the fluid's container
+    #[serde(default)]
+    pub obstacles: Vec<ObstaclesType>,
+    /// Collection of all the emitters in the fluid's container
+    #[serde(default)]
+    pub emitters: Vec<Emitter>,
+    /// The passive dyes carried by the fluid besides its density
+    #[serde(default)]
+    pub dyes: Vec<DyeConfigs>,
+}
+
+impl Default for Scenario {
+    fn default() -> Self {
+        Self {
+            version: SCENARIO_VERSION,
+            fluid: FluidConfigs::default(),
+            simulation: SimulationConfigs::default(),
+            viewport: ViewportConfigs::default(),
+            obstacles_color: Self::default_obstacles_color(),
+            obstacles: vec![ObstaclesType::Rectangle(Rectangle::default())],
+            emitters: Vec::new(),
+            dyes: Vec::new(),
+        }
+    }
+}
+
+impl Scenario {
+    fn default_obstacles_color() -> Color32 {
+        Color32::RED
+    }
+
+    /// Reads a scenario from the given TOML document, and validates it.
+    pub fn from_toml(content: &str) -> Result<Self, ScenarioError> {
+        let result: Self =
+            toml::from_str(content).map_err(|e| ScenarioError::Parse(e.to_string()))?;
+        result.validate()?;
+        Ok(result)
+    }
+
+    /// Writes the scenario as a TOML document. It goes through a [`toml::Value`] first, which puts
+    /// the plain values of every table before its nested tables, as TOML requires.
+    pub fn to_toml(&self) -> Result<String, ScenarioError> {
+        toml::Value::try_from(self)
+            .and_then(|value| toml::to_string(&value))
+            .map_err(|e| ScenarioError::Parse(e.to_string()))
+    }
+
+    /// Opens the scenario file with the given path.
+    pub fn open(path: &str) -> Result<Self, ScenarioError> {
+        Self::from_toml(&std::fs::read_to_string(path)?)
+    }
+
+    /// Saves the scenario into a file with the given path. The file is overwritten if it exists.
+    pub fn save(&self, path: &str) -> Result<(), ScenarioError> {
+        std::fs::write(path, self.to_toml()?)?;
+        Ok(())
+    }
+
+    /// Checks if the scenario can be simulated by the current version of the application.
+    pub fn validate(&self) -> Result<(), ScenarioError> {
+        if self.version > SCENARIO_VERSION {
+            return Err(ScenarioError::UnsupportedVersion(self.version));
+        }
+
+        if self.simulation.width < SimulationConfigs::MIN_SIZE
+            || self.simulation.height < SimulationConfigs::MIN_SIZE
+        {
+            return Err(ScenarioError::ContainerTooSmall);
+        }
+
+        if !self.simulation.boundaries.are_valid() {
+            return Err(ScenarioError::UnpairedPeriodicBoundary);
+        }
+
+        if !self.simulation.are_units_valid() {
+            return Err(ScenarioError::InvalidPhysicalUnits);
+        }
+
+        if !self.simulation.time_stepping.is_valid() {
+            return Err(ScenarioError::InvalidTimeStepping);
+        }
+
+        if !self.viewport.animation.is_valid() {
+            return Err(ScenarioError::InvalidAnimation);
+        }
+
+        let (width, height) = (
+            i64::from(self.simulation.width),
+            i64::from(self.simulation.height),
+        );
+        for (index, obstacle) in self.obstacles.iter().enumerate() {
+            if !obstacle.are_all_points_valid(width, height) {
+                return Err(ScenarioError::InvalidObstacle(obstacle.name(), index));
+            }
+        }
+
+        for (index, emitter) in self.emitters.iter().enumerate() {
+            if !emitter.is_valid(width, height, self.dyes.len()) {
+                return Err(ScenarioError::InvalidEmitter(index));
+            }
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::simulation::configs::DyeConfigs;
+    use crate::simulation::emitter::Emitter;
+    use crate::simulation::obstacle::{Obstacle, ObstaclesType, Rectangle};
+    use crate::simulation::scenario::{Scenario, ScenarioError, SCENARIO_VERSION};
+    use epaint::Color32;
+
+    #[test]
+    fn scenario_round_trip() {
+        let mut scenario = Scenario::default();
+        scenario.fluid.fluid_color = Color32::from_rgb(1, 2, 3);
+        scenario.simulation.frames = 321;
+        scenario.simulation.width = 256;
+        scenario.simulation.height = 160;
+        scenario.viewport.save_into_dir = "somewhere_else".to_string();
+        scenario.obstacles_color = Color32::GREEN;
+        scenario
+            .obstacles
+            .push(ObstaclesType::Rectangle(Rectangle::new(
+                (1, 2),
+                (3, 4),
+                256,
+                160,
+            )));
+        scenario.dyes.push(DyeConfigs {
+            diffusion: 0.25,
+            color: Color32::BLUE,
+        });
+        scenario.emitters.push(Emitter {
+            end_frame: Some(100),
+            dye: Some(0),
+            ..Emitter::default()
+        });
+
+        let result = Scenario::from_toml(&scenario.to_toml().unwrap()).unwrap();
+
+        assert_eq!(result.version, SCENARIO_VERSION);
+        assert_eq!(result.fluid.fluid_color, Color32::from_rgb(1, 2, 3));
+        assert_eq!(result.simulation.frames, 321);
+        assert_eq!(result.simulation.width, 256);
+        assert_eq!(result.simulation.height, 160);
+        assert_eq!(result.viewport.save_into_dir, "somewhere_else");
+        assert_eq!(result.obstacles_color, Color32::GREEN);
+        assert_eq!(result.obstacles.len(), 2);
+        assert_eq!(result.obstacles[1].bounding_box(), ((1, 2), (3, 4)));
+        assert_eq!(result.emitters, scenario.emitters);
+        assert_eq!(result.dyes, scenario.dyes);
+    }
+
+    #[test]
+    fn newer_version_is_rejected() {
+        let content = format!("version = {}", SCENARIO_VERSION + 1);
+        assert!(matches!(
+            Scenario::from_toml(&content),
+            Err(ScenarioError::UnsupportedVersion(_))
+        ));
+    }
+
+    #[test]
+    fn obstacle_outside_container_is_rejected() {
+        let content = r#"
+            version = 1
+
+            [simulation]
+            size = 64
+
+            [[obstacles]]
+            type = "Rectangle"
+            down_left_point = [10, 10]
+            up_right_point = [100, 100]
+        "#;
+        assert!(matches!(
+            Scenario::from_toml(content),
+            Err(ScenarioError::InvalidObstacle("Rectangle", 0))
+        ));
+    }
+
+    #[test]
+    fn size_of_version_1_sets_width_and_height() {
+        let content = r#"
+            version = 1
+
+            [simulation]
+            size = 64
+        "#;
+        let scenario = Scenario::from_toml(content).unwrap();
+        assert_eq!(scenario.simulation.width, 64);
+        assert_eq!(scenario.simulation.height, 64);
+    }
+
+    #[test]
+    fn obstacle_below_wide_container_is_rejected() {
+        let content = r#"
+            version = 2
+
+            [simulation]
+            width = 512
+            height = 128
+
+            [[obstacles]]
+            type = "Rectangle"
+            down_left_point = [300, 100]
+            up_right_point = [400, 200]
+        "#;
+        assert!(matches!(
+            Scenario::from_toml(content),
+            Err(ScenarioError::InvalidObstacle("Rectangle", 0))
+        ));
+    }
+
+    #[test]
+    fn emitter_of_missing_dye_is_rejected() {
+        let content = r#"
+            version = 2
+
+            [[dyes]]
+            color = [0, 0, 255, 255]
+
+            [[emitters]]
+            dye = 1
+        "#;
+        assert!(matches!(
+            Scenario::from_toml(content),
+            Err(ScenarioError::InvalidEmitter(0))
+        ));
+    }
+
+    #[test]
+    fn unpaired_periodic_boundary_is_rejected() {
+        let content = r#"
+            version = 1
+
+            [simulation.boundaries]
+            left = "Periodic"
+            right = { VelocityInlet = { speed = 1.0, profile = "Parabolic" } }
+        "#;
+        assert!(matches!(
+            Scenario::from_toml(content),
+            Err(ScenarioError::UnpairedPeriodicBoundary)
+        ));
+    }
+
+    #[test]
+    fn too_small_container_is_rejected() {
+        let content = r#"
+            version = 2
+
+            [simulation]
+            width = 64
+            height = 2
+        "#;
+        assert!(matches!(
+            Scenario::from_toml(content),
+            Err(ScenarioError::ContainerTooSmall)
+        ));
+    }
+
+    #[test]
+    fn non_positive_domain_length_is_rejected() {
+        let content = r#"
+            version = 2
+
+            [simulation]
+            domain_length = 0.0
+        "#;
+        assert!(matches!(
+            Scenario::from_toml(content),
+            Err(ScenarioError::InvalidPhysicalUnits)
+        ));
+    }
+
+    #[test]
+    fn zero_cfl_is_rejected() {
+        let content = r#"
+            version = 2
+
+            [simulation]
+            time_stepping = { Adaptive = { cfl = 0.0, max_substeps = 16 } }
+        "#;
+        assert!(matches!(
+            Scenario::from_toml(content),
+            Err(ScenarioError::InvalidTimeStepping)
+        ));
+    }
+
+    #[test]
+    fn too_high_frame_rate_is_rejected() {
+        let content = r#"
+            version = 1
+
+            [viewport.animation]
+            frame_rate = 70000
+        "#;
+        assert!(matches!(
+            Scenario::from_toml(content),
+            Err(ScenarioError::InvalidAnimation)
+        ));
+    }
+}
@@ -0,0 +1,235 @@
+use crate::simulation::configs::{Colormap, ValueRange, VisualizationConfigs, VisualizedField};
+use crate::simulation::fluid::{ContainerWall, Fluid};
+
+/// The colors of matplotlib's viridis at `0.0, 0.125, ..., 1.0`
+const VIRIDIS: [[u8; 3]; 9] = [
+    [68, 1, 84],
+    [71, 44, 122],
+    [59, 81, 139],
+    [44, 113, 142],
+    [33, 144, 141],
+    [39, 173, 129],
+    [92, 200, 99],
+    [170, 220, 50],
+    [253, 231, 37],
+];
+
+/// The colors of matplotlib's inferno at `0.0, 0.125, ..., 1.0`
+const INFERNO: [[u8; 3]; 9] = [
+    [0, 0, 4],
+    [31, 12, 72],
+    [85, 15, 109],
+    [136, 34, 106],
+    [186, 54, 85],
+    [227, 89, 51],
+    [249, 140, 10],
+    [249, 201, 50],
+    [252, 255, 164],
+];
+
+/// The colors of the coolwarm colormap at `0.0, 0.125, ..., 1.0`
+const COOLWARM: [[u8; 3]; 9] = [
+    [59, 76, 192],
+    [98, 130, 234],
+    [141, 176, 254],
+    [184, 208, 249],
+    [221, 221, 221],
+    [245, 196, 173],
+    [244, 154, 123],
+    [222, 96, 77],
+    [180, 4, 38],
+];
+
+impl VisualizedField {
+    /// The values of the field for every cell of the fluid's container, row by row. Nothing is
+    /// shown with the [`VisualizedField::Colors`], so it has no values.
+    pub fn values(self, fluid: &Fluid) -> Vec<f32> {
+        match self {
+            VisualizedField::Colors => Vec::new(),
+            VisualizedField::Density => fluid.density.clone(),
+            VisualizedField::VelocityMagnitude => {
+                let (velocities_x, velocities_y) = fluid.cell_velocities();
+                velocities_x
+                    .iter()
+                    .zip(&velocities_y)
+                    .map(|(velocity_x, velocity_y)| velocity_x.hypot(*velocity_y))
+                    .collect()
+            }
+            VisualizedField::Vorticity => fluid.vorticity(),
+            VisualizedField::Pressure => fluid.pressure.clone(),
+            VisualizedField::Divergence => fluid.divergence(),
+        }
+    }
+
+    /// Checks if the sign of the field matters more than its magnitude, so its automatic range is
+    /// symmetric around zero.
+    pub fn is_signed(self) -> bool {
+        matches!(
+            self,
+            VisualizedField::Vorticity | VisualizedField::Divergence
+        )
+    }
+}
+
+impl Colormap {
+    /// The color at the given position between `0.0` and `1.0` of the colormap. The positions
+    /// outside of them are clamped.
+    pub fn color(self, position: f32) -> [u8; 3] {
+        let colors = match self {
+            Colormap::Viridis => &VIRIDIS,
+            Colormap::Inferno => &INFERNO,
+            Colormap::Coolwarm => &COOLWARM,
+        };
+
+        // `max` turns NaN into the first color
+        let position = position.clamp(0.0, 1.0).max(0.0) * (colors.len() - 1) as f32;
+        let lower = (position.floor() as usize).min(colors.len() - 2);
+        let t = position - lower as f32;
+
+        let mut result = [0; 3];
+        for (channel, value) in result.iter_mut().enumerate() {
+            let (from, to) = (
+                f32::from(colors[lower][channel]),
+                f32::from(colors[lower + 1][channel]),
+            );
+            *value = (from + t * (to - from)).round() as u8;
+        }
+        result
+    }
+}
+
+/// The range of the values mapped onto the whole colormap. The automatic range spans the values
+/// in the fluid's cells, i.e. the walls are left out.
+pub fn value_range(
+    configs: &VisualizationConfigs,
+    values: &[f32],
+    cells_type: &[ContainerWall],
+) -> (f32, f32) {
+    if let ValueRange::Fixed { min, max } = configs.range {
+        return (min, max);
+    }
+
+    let (min, max) = values
+        .iter()
+        .zip(cells_type)
+        .filter(|(value, cell_type)| **cell_type == ContainerWall::NoWall && value.is_finite())
+        .fold(
+            (f32::INFINITY, f32::NEG_INFINITY),
+            |(min, max), (value, _)| (min.min(*value), max.max(*value)),
+        );
+    if min > max {
+        return (0.0, 0.0);
+    }
+
+    if configs.field.is_signed() {
+        let max_abs = min.abs().max(max.abs());
+        (-max_abs, max_abs)
+    } else {
+        (min, max)
+    }
+}
+
+/// Renders the field of the fluid selected by the configurations with their colormap. Every pixel
+/// corresponds to a single cell of the fluid's container, and the walls have the color of the
+/// obstacles.
+pub fn render_field(
+    configs: &VisualizationConfigs,
+    fluid: &Fluid,
+    obstacles_color: epaint::Color32,
+) -> image::RgbaImage {
+    let (width, height) = (
+        fluid.simulation_configs.width,
+        fluid.simulation_configs.height,
+    );
+    let values = configs.field.values(fluid);
+    let (min, max) = value_range(configs, &values, &fluid.cells_type);
+
+    let mut imgbuf = image::ImageBuffer::new(width, height);
+    for (x, y, pixel) in imgbuf.enumerate_pixels_mut() {
+        let idx = idx!(x, y, width, height);
+        let rgb = if fluid.cells_type[idx] == ContainerWall::DefaultWall {
+            [
+                obstacles_color.r(),
+                obstacles_color.g(),
+                obstacles_color.b(),
+            ]
+        } else if max > min {
+            configs.colormap.color((values[idx] - min) / (max - min))
+        } else {
+            configs.colormap.color(0.5)
+        };
+        *pixel = image::Rgba([rgb[0], rgb[1], rgb[2], u8::MAX]);
+    }
+    imgbuf
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::simulation::configs::{
+        Colormap, FluidConfigs, SimulationConfigs, ValueRange, VisualizationConfigs,
+        VisualizedField,
+    };
+    use crate::simulation::fluid::{ContainerWall, Fluid};
+    use crate::simulation::visualization::{render_field, value_range};
+
+    #[test]
+    fn colormaps_span_their_colors() {
+        assert_eq!(Colormap::Viridis.color(0.0), [68, 1, 84]);
+        assert_eq!(Colormap::Viridis.color(1.0), [253, 231, 37]);
+        assert_eq!(Colormap::Inferno.color(-3.0), [0, 0, 4]);
+        assert_eq!(Colormap::Coolwarm.color(0.5), [221, 221, 221]);
+        assert_eq!(Colormap::Coolwarm.color(f32::NAN), [59, 76, 192]);
+        // Halfway between the first two colors
+        assert_eq!(Colormap::Inferno.color(0.0625), [16, 6, 38]);
+    }
+
+    #[test]
+    fn automatic_range_of_signed_fields_is_symmetric() {
+        let values = [-1.0, 0.5, 3.0, 100.0];
+        let cells_type = [
+            ContainerWall::NoWall,
+            ContainerWall::NoWall,
+            ContainerWall::NoWall,
+            ContainerWall::DefaultWall,
+        ];
+        let mut configs = VisualizationConfigs {
+            field: VisualizedField::Density,
+            ..VisualizationConfigs::default()
+        };
+        assert_eq!(value_range(&configs, &values, &cells_type), (-1.0, 3.0));
+
+        configs.field = VisualizedField::Vorticity;
+        assert_eq!(value_range(&configs, &values, &cells_type), (-3.0, 3.0));
+
+        configs.range = ValueRange::Fixed { min: 0.0, max: 2.0 };
+        assert_eq!(value_range(&configs, &values, &cells_type), (0.0, 2.0));
+    }
+
+    #[test]
+    fn vortex_is_shown_with_the_ends_of_the_colormap() {
+        let size = 32;
+        let mut fluid = Fluid::new(
+            FluidConfigs::default(),
+            SimulationConfigs::new(0.02, 1, size),
+        );
+        fluid.velocities_x.fill(0.0);
+        fluid.velocities_y.fill(0.0);
+        // A clockwise vortex on the screen around the cell (16, 16)
+        fluid.add_velocity(16, 15, 1.0, 0.0);
+        fluid.add_velocity(17, 16, 0.0, 1.0);
+        fluid.add_velocity(16, 17, -1.0, 0.0);
+        fluid.add_velocity(15, 16, 0.0, -1.0);
+
+        let configs = VisualizationConfigs {
+            field: VisualizedField::Vorticity,
+            colormap: Colormap::Coolwarm,
+            range: ValueRange::Auto,
+        };
+        let image = render_field(&configs, &fluid, epaint::Color32::RED);
+
+        assert!(fluid.vorticity()[idx!(16, 16, size, size)] > 0.0);
+        assert_eq!(image.get_pixel(16, 16).0, [180, 4, 38, u8::MAX]);
+        assert_eq!(image.get_pixel(5, 5).0, [221, 221, 221, u8::MAX]);
+        assert_eq!(image.get_pixel(0, 5).0, [255, 0, 0, u8::MAX]);
+    }
+}